@@ -0,0 +1,148 @@
+#ifndef BITFOLD_H
+#define BITFOLD_H
+
+/* Generated by cbindgen from ffi/src/lib.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Result of every call, `BITFOLD_STATUS_OK` on success.
+ */
+typedef enum BitfoldStatus {
+  BITFOLD_STATUS_OK = 0,
+  BITFOLD_STATUS_NULL_POINTER = 1,
+  BITFOLD_STATUS_INVALID_HEADER = 2,
+  BITFOLD_STATUS_INVALID_KEY = 3,
+  BITFOLD_STATUS_INVALID_PROOF = 4,
+  BITFOLD_STATUS_VERIFICATION_FAILED = 5,
+  BITFOLD_STATUS_INTERNAL = 6,
+} BitfoldStatus;
+
+/**
+ * What a verified proof shows about the chain, see [`VerifiedChainState`].
+ */
+typedef struct BitfoldChainState BitfoldChainState;
+
+/**
+ * Key verifying compressed proofs of a chain, see [`Bn254GrumpkinVerifierKey`].
+ */
+typedef struct BitfoldVerifierKey BitfoldVerifierKey;
+
+/**
+ * Block header with fixed size fields, hashes in internal byte order.
+ */
+typedef struct BitfoldHeader {
+  uint32_t version;
+  uint8_t hash_prev_block[32];
+  uint8_t hash_merkle_root[32];
+  uint32_t timestamp;
+  uint32_t bits;
+  uint32_t nonce;
+} BitfoldHeader;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Static description of a status, callers may pass any integer.
+ */
+const char *bitfold_status_message(int status);
+
+/**
+ * Parses a header from its 80 byte serialization.
+ *
+ * # Safety
+ * `data` points to `len` readable bytes and `out` to a writable header.
+ */
+enum BitfoldStatus bitfold_header_parse(const uint8_t *data, size_t len, struct BitfoldHeader *out);
+
+/**
+ * Serializes a header into its 80 bytes.
+ *
+ * # Safety
+ * `header` points to a header and `out` to 80 writable bytes.
+ */
+enum BitfoldStatus bitfold_header_serialize(const struct BitfoldHeader *header, uint8_t *out);
+
+/**
+ * Writes the block hash of a header in internal byte order.
+ *
+ * # Safety
+ * `header` points to a header and `out` to 32 writable bytes.
+ */
+enum BitfoldStatus bitfold_header_hash(const struct BitfoldHeader *header, uint8_t *out);
+
+/**
+ * Loads a verifier key from its bytes.
+ *
+ * # Safety
+ * `data` points to `len` readable bytes and `out` to a writable pointer.
+ */
+enum BitfoldStatus bitfold_verifier_key_load(const uint8_t *data,
+                                             size_t len,
+                                             struct BitfoldVerifierKey **out);
+
+/**
+ * Releases a key returned by `bitfold_verifier_key_load`, null is ignored.
+ *
+ * # Safety
+ * `key` was returned by `bitfold_verifier_key_load` and is not used afterwards.
+ */
+void bitfold_verifier_key_free(struct BitfoldVerifierKey *key);
+
+/**
+ * Verifies a compressed proof and returns the chain state it proves.
+ *
+ * # Safety
+ * `key` is a loaded key, `proof` points to `len` readable bytes and `out` to a writable pointer.
+ */
+enum BitfoldStatus bitfold_verify(const struct BitfoldVerifierKey *key,
+                                  const uint8_t *proof,
+                                  size_t len,
+                                  struct BitfoldChainState **out);
+
+/**
+ * Releases a state returned by `bitfold_verify`, null is ignored.
+ *
+ * # Safety
+ * `state` was returned by `bitfold_verify` and is not used afterwards.
+ */
+void bitfold_chain_state_free(struct BitfoldChainState *state);
+
+/**
+ * Writes the block hash of the tip in internal byte order.
+ *
+ * # Safety
+ * `state` is a verified state and `out` points to 32 writable bytes.
+ */
+enum BitfoldStatus bitfold_chain_state_tip_hash(const struct BitfoldChainState *state,
+                                                uint8_t *out);
+
+/**
+ * Writes the height of the tip.
+ *
+ * # Safety
+ * `state` is a verified state and `out` points to a writable integer.
+ */
+enum BitfoldStatus bitfold_chain_state_tip_height(const struct BitfoldChainState *state,
+                                                  uint32_t *out);
+
+/**
+ * Writes the chainwork of the tip as a 256-bit big-endian integer, as bitcoind reports it.
+ *
+ * # Safety
+ * `state` is a verified state and `out` points to 32 writable bytes.
+ */
+enum BitfoldStatus bitfold_chain_state_chainwork(const struct BitfoldChainState *state,
+                                                 uint8_t *out);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* BITFOLD_H */
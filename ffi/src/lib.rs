@@ -0,0 +1,358 @@
+//! C bindings of the bitfold verifier and of block headers.
+//!
+//! The header `include/bitfold.h` is generated from this file by cbindgen and checked in,
+//! `tests/header.rs` fails when it is out of date. Every function returns a [`BitfoldStatus`],
+//! results are written through out pointers. Verifier keys and verified chain states are opaque
+//! and owned by the caller once returned, they are released with `bitfold_verifier_key_free` and
+//! `bitfold_chain_state_free`.
+//!
+//! Keys are the bytes of [`Bn254GrumpkinVerifierKey::to_bytes`], proofs canonical compressed
+//! serializations of a [`Bn254GrumpkinCompressedProof`].
+
+use std::ffi::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use ark_serialize::CanonicalDeserialize;
+use bitcoin_fold::{
+    BitcoinHeader, Bn254GrumpkinCompressedProof, Bn254GrumpkinVerifierKey, VerifiedChainState,
+    HEADER_LEN,
+};
+
+/// Result of every call, `BITFOLD_STATUS_OK` on success.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitfoldStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidHeader = 2,
+    InvalidKey = 3,
+    InvalidProof = 4,
+    VerificationFailed = 5,
+    // the library panicked, which is a bug
+    Internal = 6,
+}
+
+/// Block header with fixed size fields, hashes in internal byte order.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitfoldHeader {
+    pub version: u32,
+    pub hash_prev_block: [u8; 32],
+    pub hash_merkle_root: [u8; 32],
+    pub timestamp: u32,
+    pub bits: u32,
+    pub nonce: u32,
+}
+
+impl From<&BitcoinHeader> for BitfoldHeader {
+    fn from(header: &BitcoinHeader) -> Self {
+        let mut hash_prev_block = [0u8; 32];
+        hash_prev_block.copy_from_slice(&header.hash_prev_block);
+        let mut hash_merkle_root = [0u8; 32];
+        hash_merkle_root.copy_from_slice(&header.hash_merkle_root);
+        BitfoldHeader {
+            version: header.version,
+            hash_prev_block,
+            hash_merkle_root,
+            timestamp: header.timestamp,
+            bits: header.bits(),
+            nonce: header.nonce,
+        }
+    }
+}
+
+impl From<&BitfoldHeader> for BitcoinHeader {
+    fn from(header: &BitfoldHeader) -> Self {
+        BitcoinHeader {
+            version: header.version,
+            hash_prev_block: header.hash_prev_block.to_vec(),
+            hash_merkle_root: header.hash_merkle_root.to_vec(),
+            timestamp: header.timestamp,
+            target_bits: header.bits.to_le_bytes().to_vec(),
+            nonce: header.nonce,
+        }
+    }
+}
+
+/// Key verifying compressed proofs of a chain, see [`Bn254GrumpkinVerifierKey`].
+pub struct BitfoldVerifierKey(Bn254GrumpkinVerifierKey);
+
+/// What a verified proof shows about the chain, see [`VerifiedChainState`].
+pub struct BitfoldChainState(VerifiedChainState);
+
+// runs `f` and reports a panic as an internal error instead of unwinding into C, nothing `f`
+// touched is observed after a panic
+fn guard(f: impl FnOnce() -> BitfoldStatus) -> BitfoldStatus {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(BitfoldStatus::Internal)
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    if data.is_null() {
+        return if len == 0 { Some(&[]) } else { None };
+    }
+    Some(slice::from_raw_parts(data, len))
+}
+
+/// Static description of a status, callers may pass any integer.
+#[no_mangle]
+pub extern "C" fn bitfold_status_message(status: c_int) -> *const c_char {
+    // an out of range value is not a valid `BitfoldStatus`, so the status is matched as an integer
+    let message: &'static [u8] = match status {
+        s if s == BitfoldStatus::Ok as c_int => b"ok\0",
+        s if s == BitfoldStatus::NullPointer as c_int => b"null pointer\0",
+        s if s == BitfoldStatus::InvalidHeader as c_int => b"invalid header\0",
+        s if s == BitfoldStatus::InvalidKey as c_int => b"invalid verifier key\0",
+        s if s == BitfoldStatus::InvalidProof as c_int => b"invalid proof\0",
+        s if s == BitfoldStatus::VerificationFailed as c_int => b"proof does not verify\0",
+        s if s == BitfoldStatus::Internal as c_int => b"internal error\0",
+        _ => b"unknown status\0",
+    };
+    message.as_ptr() as *const c_char
+}
+
+/// Parses a header from its 80 byte serialization.
+///
+/// # Safety
+/// `data` points to `len` readable bytes and `out` to a writable header.
+#[no_mangle]
+pub unsafe extern "C" fn bitfold_header_parse(
+    data: *const u8,
+    len: usize,
+    out: *mut BitfoldHeader,
+) -> BitfoldStatus {
+    guard(|| {
+        let (Some(data), false) = (bytes(data, len), out.is_null()) else {
+            return BitfoldStatus::NullPointer;
+        };
+        match BitcoinHeader::from_bytes(data) {
+            Ok(header) => {
+                *out = BitfoldHeader::from(&header);
+                BitfoldStatus::Ok
+            }
+            Err(_) => BitfoldStatus::InvalidHeader,
+        }
+    })
+}
+
+/// Serializes a header into its 80 bytes.
+///
+/// # Safety
+/// `header` points to a header and `out` to 80 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bitfold_header_serialize(
+    header: *const BitfoldHeader,
+    out: *mut u8,
+) -> BitfoldStatus {
+    guard(|| {
+        if header.is_null() || out.is_null() {
+            return BitfoldStatus::NullPointer;
+        }
+        let bytes = BitcoinHeader::from(&*header).to_bytes();
+        ptr::copy_nonoverlapping(bytes.as_ptr(), out, HEADER_LEN);
+        BitfoldStatus::Ok
+    })
+}
+
+/// Writes the block hash of a header in internal byte order.
+///
+/// # Safety
+/// `header` points to a header and `out` to 32 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bitfold_header_hash(
+    header: *const BitfoldHeader,
+    out: *mut u8,
+) -> BitfoldStatus {
+    guard(|| {
+        if header.is_null() || out.is_null() {
+            return BitfoldStatus::NullPointer;
+        }
+        let hash = BitcoinHeader::from(&*header).hash();
+        ptr::copy_nonoverlapping(hash.as_ptr(), out, 32);
+        BitfoldStatus::Ok
+    })
+}
+
+/// Loads a verifier key from its bytes.
+///
+/// # Safety
+/// `data` points to `len` readable bytes and `out` to a writable pointer.
+#[no_mangle]
+pub unsafe extern "C" fn bitfold_verifier_key_load(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut BitfoldVerifierKey,
+) -> BitfoldStatus {
+    guard(|| {
+        let (Some(data), false) = (bytes(data, len), out.is_null()) else {
+            return BitfoldStatus::NullPointer;
+        };
+        match Bn254GrumpkinVerifierKey::from_bytes(data) {
+            Ok(key) => {
+                *out = Box::into_raw(Box::new(BitfoldVerifierKey(key)));
+                BitfoldStatus::Ok
+            }
+            Err(_) => BitfoldStatus::InvalidKey,
+        }
+    })
+}
+
+/// Releases a key returned by `bitfold_verifier_key_load`, null is ignored.
+///
+/// # Safety
+/// `key` was returned by `bitfold_verifier_key_load` and is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn bitfold_verifier_key_free(key: *mut BitfoldVerifierKey) {
+    if !key.is_null() {
+        drop(Box::from_raw(key));
+    }
+}
+
+/// Verifies a compressed proof and returns the chain state it proves.
+///
+/// # Safety
+/// `key` is a loaded key, `proof` points to `len` readable bytes and `out` to a writable pointer.
+#[no_mangle]
+pub unsafe extern "C" fn bitfold_verify(
+    key: *const BitfoldVerifierKey,
+    proof: *const u8,
+    len: usize,
+    out: *mut *mut BitfoldChainState,
+) -> BitfoldStatus {
+    guard(|| {
+        let (Some(proof), false, false) = (bytes(proof, len), key.is_null(), out.is_null()) else {
+            return BitfoldStatus::NullPointer;
+        };
+        let Ok(proof) = Bn254GrumpkinCompressedProof::deserialize_compressed(proof) else {
+            return BitfoldStatus::InvalidProof;
+        };
+        match (*key).0.verify(&proof) {
+            Ok(state) => {
+                *out = Box::into_raw(Box::new(BitfoldChainState(state)));
+                BitfoldStatus::Ok
+            }
+            Err(_) => BitfoldStatus::VerificationFailed,
+        }
+    })
+}
+
+/// Releases a state returned by `bitfold_verify`, null is ignored.
+///
+/// # Safety
+/// `state` was returned by `bitfold_verify` and is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn bitfold_chain_state_free(state: *mut BitfoldChainState) {
+    if !state.is_null() {
+        drop(Box::from_raw(state));
+    }
+}
+
+/// Writes the block hash of the tip in internal byte order.
+///
+/// # Safety
+/// `state` is a verified state and `out` points to 32 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bitfold_chain_state_tip_hash(
+    state: *const BitfoldChainState,
+    out: *mut u8,
+) -> BitfoldStatus {
+    guard(|| {
+        if state.is_null() || out.is_null() {
+            return BitfoldStatus::NullPointer;
+        }
+        let tip_hash = &(*state).0.tip_hash;
+        ptr::copy_nonoverlapping(tip_hash.as_ptr(), out, 32);
+        BitfoldStatus::Ok
+    })
+}
+
+/// Writes the height of the tip.
+///
+/// # Safety
+/// `state` is a verified state and `out` points to a writable integer.
+#[no_mangle]
+pub unsafe extern "C" fn bitfold_chain_state_tip_height(
+    state: *const BitfoldChainState,
+    out: *mut u32,
+) -> BitfoldStatus {
+    guard(|| {
+        if state.is_null() || out.is_null() {
+            return BitfoldStatus::NullPointer;
+        }
+        *out = (*state).0.tip_height;
+        BitfoldStatus::Ok
+    })
+}
+
+/// Writes the chainwork of the tip as a 256-bit big-endian integer, as bitcoind reports it.
+///
+/// # Safety
+/// `state` is a verified state and `out` points to 32 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bitfold_chain_state_chainwork(
+    state: *const BitfoldChainState,
+    out: *mut u8,
+) -> BitfoldStatus {
+    guard(|| {
+        if state.is_null() || out.is_null() {
+            return BitfoldStatus::NullPointer;
+        }
+        let chainwork = (*state).0.chainwork.to_bytes_be();
+        let Some(padding) = 32usize.checked_sub(chainwork.len()) else {
+            return BitfoldStatus::Internal;
+        };
+        ptr::write_bytes(out, 0, padding);
+        ptr::copy_nonoverlapping(chainwork.as_ptr(), out.add(padding), chainwork.len());
+        BitfoldStatus::Ok
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bitcoin_fold::ChainParams;
+    use std::ffi::CStr;
+
+    #[test]
+    fn header_roundtrip() {
+        let genesis = ChainParams::mainnet().genesis;
+        let bytes = genesis.to_bytes();
+
+        let mut header = BitfoldHeader::from(&genesis);
+        let status = unsafe { bitfold_header_parse(bytes.as_ptr(), bytes.len(), &mut header) };
+        assert_eq!(status, BitfoldStatus::Ok);
+        assert_eq!(BitcoinHeader::from(&header), genesis);
+
+        let mut serialized = [0u8; HEADER_LEN];
+        let status = unsafe { bitfold_header_serialize(&header, serialized.as_mut_ptr()) };
+        assert_eq!(status, BitfoldStatus::Ok);
+        assert_eq!(serialized.to_vec(), bytes);
+
+        let mut hash = [0u8; 32];
+        let status = unsafe { bitfold_header_hash(&header, hash.as_mut_ptr()) };
+        assert_eq!(status, BitfoldStatus::Ok);
+        assert_eq!(hash.to_vec(), genesis.hash());
+
+        let status = unsafe { bitfold_header_parse(bytes.as_ptr(), 79, &mut header) };
+        assert_eq!(status, BitfoldStatus::InvalidHeader);
+        let status = unsafe { bitfold_header_parse(ptr::null(), 80, &mut header) };
+        assert_eq!(status, BitfoldStatus::NullPointer);
+        let status = unsafe { bitfold_header_serialize(&header, ptr::null_mut()) };
+        assert_eq!(status, BitfoldStatus::NullPointer);
+    }
+
+    #[test]
+    fn error_statuses() {
+        let message =
+            unsafe { CStr::from_ptr(bitfold_status_message(BitfoldStatus::InvalidKey as c_int)) };
+        assert_eq!(message.to_str(), Ok("invalid verifier key"));
+        let message = unsafe { CStr::from_ptr(bitfold_status_message(-1)) };
+        assert_eq!(message.to_str(), Ok("unknown status"));
+
+        let mut key = ptr::null_mut();
+        let status = unsafe { bitfold_verifier_key_load([0u8; 4].as_ptr(), 4, &mut key) };
+        assert_eq!(status, BitfoldStatus::InvalidKey);
+        assert!(key.is_null());
+    }
+}
@@ -0,0 +1,114 @@
+/*
+ * Exercises the C API: test_bitfold <header> <verifier key> <proof>
+ *
+ * The header is the serialization of the tip the proof proves. Prints the verified tip height,
+ * hash and chainwork in hex, and exits non-zero on the first failed check.
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "bitfold.h"
+
+#define CHECK(cond)                                                        \
+    do {                                                                   \
+        if (!(cond)) {                                                     \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #cond);                                                \
+            exit(1);                                                       \
+        }                                                                  \
+    } while (0)
+
+#define CHECK_STATUS(call, expected)                                                   \
+    do {                                                                               \
+        BitfoldStatus status = (call);                                                 \
+        if (status != (expected)) {                                                    \
+            fprintf(stderr, "%s:%d: %s returned \"%s\"\n", __FILE__, __LINE__, #call, \
+                    bitfold_status_message(status));                                   \
+            exit(1);                                                                   \
+        }                                                                              \
+    } while (0)
+
+static uint8_t *read_file(const char *path, size_t *len) {
+    FILE *file = fopen(path, "rb");
+    CHECK(file != NULL);
+    CHECK(fseek(file, 0, SEEK_END) == 0);
+    long size = ftell(file);
+    CHECK(size >= 0);
+    rewind(file);
+    uint8_t *data = malloc(size > 0 ? (size_t)size : 1);
+    CHECK(data != NULL);
+    CHECK(fread(data, 1, (size_t)size, file) == (size_t)size);
+    fclose(file);
+    *len = (size_t)size;
+    return data;
+}
+
+static void print_hex(const char *label, const uint8_t *bytes, size_t len) {
+    printf("%s ", label);
+    for (size_t i = 0; i < len; i++) {
+        printf("%02x", bytes[i]);
+    }
+    printf("\n");
+}
+
+int main(int argc, char **argv) {
+    CHECK(argc == 4);
+    size_t header_len, key_len, proof_len;
+    uint8_t *header_bytes = read_file(argv[1], &header_len);
+    uint8_t *key_bytes = read_file(argv[2], &key_len);
+    uint8_t *proof_bytes = read_file(argv[3], &proof_len);
+
+    /* headers roundtrip through their serialization */
+    BitfoldHeader header;
+    uint8_t serialized[80];
+    CHECK(header_len == sizeof(serialized));
+    CHECK_STATUS(bitfold_header_parse(header_bytes, header_len, &header), BITFOLD_STATUS_OK);
+    CHECK_STATUS(bitfold_header_serialize(&header, serialized), BITFOLD_STATUS_OK);
+    CHECK(memcmp(serialized, header_bytes, sizeof(serialized)) == 0);
+    CHECK_STATUS(bitfold_header_parse(header_bytes, header_len - 1, &header),
+                 BITFOLD_STATUS_INVALID_HEADER);
+    CHECK_STATUS(bitfold_header_parse(NULL, header_len, &header), BITFOLD_STATUS_NULL_POINTER);
+    uint8_t header_hash[32];
+    CHECK_STATUS(bitfold_header_hash(&header, header_hash), BITFOLD_STATUS_OK);
+
+    /* keys */
+    BitfoldVerifierKey *key = NULL;
+    CHECK_STATUS(bitfold_verifier_key_load(key_bytes, key_len / 2, &key),
+                 BITFOLD_STATUS_INVALID_KEY);
+    CHECK(key == NULL);
+    CHECK_STATUS(bitfold_verifier_key_load(key_bytes, key_len, &key), BITFOLD_STATUS_OK);
+    CHECK(key != NULL);
+
+    /* proofs */
+    BitfoldChainState *state = NULL;
+    CHECK_STATUS(bitfold_verify(key, proof_bytes + 1, proof_len - 1, &state),
+                 BITFOLD_STATUS_INVALID_PROOF);
+    CHECK(state == NULL);
+    CHECK_STATUS(bitfold_verify(NULL, proof_bytes, proof_len, &state),
+                 BITFOLD_STATUS_NULL_POINTER);
+    CHECK_STATUS(bitfold_verify(key, proof_bytes, proof_len, &state), BITFOLD_STATUS_OK);
+    CHECK(state != NULL);
+
+    uint32_t tip_height;
+    uint8_t tip_hash[32];
+    uint8_t chainwork[32];
+    CHECK_STATUS(bitfold_chain_state_tip_height(state, &tip_height), BITFOLD_STATUS_OK);
+    CHECK_STATUS(bitfold_chain_state_tip_hash(state, tip_hash), BITFOLD_STATUS_OK);
+    CHECK_STATUS(bitfold_chain_state_chainwork(state, chainwork), BITFOLD_STATUS_OK);
+    CHECK(memcmp(tip_hash, header_hash, sizeof(tip_hash)) == 0);
+
+    printf("height %u\n", tip_height);
+    print_hex("tip", tip_hash, sizeof(tip_hash));
+    print_hex("chainwork", chainwork, sizeof(chainwork));
+
+    bitfold_chain_state_free(state);
+    bitfold_verifier_key_free(key);
+    bitfold_chain_state_free(NULL);
+    bitfold_verifier_key_free(NULL);
+    free(header_bytes);
+    free(key_bytes);
+    free(proof_bytes);
+    return 0;
+}
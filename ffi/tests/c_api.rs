@@ -0,0 +1,89 @@
+//! Compiles `tests/c/test_bitfold.c` against the shared library and the generated header and runs
+//! it on a proof of a regtest chain.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use ark_bn254::Bn254;
+use ark_serialize::CanonicalSerialize;
+use ark_spartan::polycommitments::{zeromorph::Zeromorph, PolyCommitmentScheme};
+use ark_std::test_rng;
+use bitcoin_fold::test_utils::mine_regtest_headers;
+use bitcoin_fold::{Anchor, Bn254GrumpkinIVC, Bn254GrumpkinVerifierKey, ChainParams};
+
+fn hex_be(bytes: &[u8]) -> String {
+    let mut padded = vec![0u8; 32 - bytes.len()];
+    padded.extend_from_slice(bytes);
+    padded.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[test]
+fn c_program_verifies_proof() {
+    let chain_params = ChainParams::regtest();
+    let header = mine_regtest_headers(&chain_params.genesis, 1, 0).remove(0);
+
+    let srs =
+        <Zeromorph<Bn254> as PolyCommitmentScheme<_>>::setup(25, b"test_srs", &mut test_rng())
+            .unwrap();
+    let key = Bn254GrumpkinVerifierKey::setup(&chain_params, Anchor::Genesis, &srs).unwrap();
+    let mut ivc = Bn254GrumpkinIVC::setup(chain_params.clone(), Anchor::Genesis, &srs).unwrap();
+    ivc.extend(&[header.clone()]).unwrap();
+    let mut proof_bytes = Vec::new();
+    ivc.compress(&key.snark_key)
+        .unwrap()
+        .serialize_compressed(&mut proof_bytes)
+        .unwrap();
+
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("c_api");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("header.bin"), header.to_bytes()).unwrap();
+    fs::write(dir.join("key.bin"), key.to_bytes().unwrap()).unwrap();
+    fs::write(dir.join("proof.bin"), &proof_bytes).unwrap();
+
+    // the shared library is built next to the `deps` directory of the test binary
+    let lib_dir = env::current_exe()
+        .unwrap()
+        .parent()
+        .and_then(|deps| deps.parent())
+        .unwrap()
+        .to_path_buf();
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let program = dir.join("test_bitfold");
+    let status = Command::new(env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg(manifest_dir.join("tests/c/test_bitfold.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lbitfold")
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .unwrap();
+    assert!(status.success(), "compiling the C test program failed");
+
+    let output = Command::new(&program)
+        .arg(dir.join("header.bin"))
+        .arg(dir.join("key.bin"))
+        .arg(dir.join("proof.bin"))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let state = ivc.state();
+    let expected = format!(
+        "height 1\ntip {}\nchainwork {}\n",
+        hex_be(&header.hash()),
+        hex_be(&state.chainwork.to_bytes_be())
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
@@ -0,0 +1,31 @@
+//! Checks that the checked-in `include/bitfold.h` matches the C API, run with `UPDATE_HEADER=1` to
+//! regenerate it.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn header_is_up_to_date() {
+    let crate_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("cbindgen.toml is a valid config");
+    // the bindings are all in lib.rs, parsing it alone spares resolving the dependencies
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/lib.rs"))
+        .generate()
+        .expect("the C API generates a header")
+        .write(&mut generated);
+    let generated = String::from_utf8(generated).unwrap();
+
+    let path = crate_dir.join("include/bitfold.h");
+    if env::var_os("UPDATE_HEADER").is_some() {
+        fs::write(&path, &generated).unwrap();
+    }
+    assert!(
+        fs::read_to_string(&path).unwrap() == generated,
+        "include/bitfold.h is out of date, rerun with UPDATE_HEADER=1"
+    );
+}
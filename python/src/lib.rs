@@ -0,0 +1,347 @@
+//! Python bindings of bitfold: reading headers, proving header chains over BN254/Grumpkin from
+//! genesis and verifying compressed proofs.
+//!
+//! Hashes are `bytes` in internal byte order, heights `int`s and chainwork an `int`. Proven and
+//! verified states are dicts with the fields of [`VerifiedChainState`]. Errors raise
+//! `bitfold.BitfoldError`.
+
+use std::fmt::Display;
+
+use ark_bn254::{Bn254, Fr};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_spartan::polycommitments::{zeromorph::Zeromorph, PolyCommitmentScheme};
+use ark_std::rand::{rngs::StdRng, SeedableRng};
+use bitcoin_fold::{
+    Anchor, BitcoinHeader, BlockReader, Bn254GrumpkinCompressedProof, Bn254GrumpkinIVC,
+    Bn254GrumpkinVerifierKey, ChainParams, ChainState, Network, VerifiedChainState, ZeromorphSRS,
+};
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+create_exception!(bitfold, BitfoldError, PyException);
+
+fn bitfold_error(error: impl Display) -> PyErr {
+    BitfoldError::new_err(error.to_string())
+}
+
+fn network(name: &str) -> PyResult<Network> {
+    match name {
+        "mainnet" => Ok(Network::Mainnet),
+        "testnet3" => Ok(Network::Testnet3),
+        "testnet4" => Ok(Network::Testnet4),
+        "signet" => Ok(Network::Signet),
+        "regtest" => Ok(Network::Regtest),
+        _ => Err(bitfold_error(format!("unknown network {name}"))),
+    }
+}
+
+fn network_name(network: Network) -> &'static str {
+    match network {
+        Network::Mainnet => "mainnet",
+        Network::Testnet3 => "testnet3",
+        Network::Testnet4 => "testnet4",
+        Network::Signet => "signet",
+        Network::Regtest => "regtest",
+    }
+}
+
+fn hash_bytes(hash: &[u8]) -> PyResult<Vec<u8>> {
+    if hash.len() != 32 {
+        return Err(bitfold_error(format!(
+            "hash is {} bytes, not 32",
+            hash.len()
+        )));
+    }
+    Ok(hash.to_vec())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn state_dict<'py>(py: Python<'py>, state: &VerifiedChainState) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("tip_hash", PyBytes::new_bound(py, &state.tip_hash))?;
+    dict.set_item("tip_height", state.tip_height)?;
+    dict.set_item("chainwork", state.chainwork.clone())?;
+    dict.set_item(
+        "accumulator_root",
+        PyBytes::new_bound(py, &state.accumulator_root),
+    )?;
+    Ok(dict)
+}
+
+fn chain_state_dict<'py>(py: Python<'py>, state: &ChainState) -> PyResult<Bound<'py, PyDict>> {
+    state_dict(py, &VerifiedChainState::of::<Fr>(state))
+}
+
+/// Block header, see [`BitcoinHeader`].
+#[pyclass(name = "BitcoinHeader", module = "bitfold", eq, frozen)]
+#[derive(Clone, PartialEq)]
+struct PyBitcoinHeader(BitcoinHeader);
+
+#[pymethods]
+impl PyBitcoinHeader {
+    #[new]
+    fn new(
+        version: u32,
+        hash_prev_block: &[u8],
+        hash_merkle_root: &[u8],
+        timestamp: u32,
+        bits: u32,
+        nonce: u32,
+    ) -> PyResult<Self> {
+        Ok(PyBitcoinHeader(BitcoinHeader {
+            version,
+            hash_prev_block: hash_bytes(hash_prev_block)?,
+            hash_merkle_root: hash_bytes(hash_merkle_root)?,
+            timestamp,
+            target_bits: bits.to_le_bytes().to_vec(),
+            nonce,
+        }))
+    }
+
+    /// Parses the 80 byte serialization of a header.
+    #[staticmethod]
+    fn from_bytes(data: &[u8]) -> PyResult<Self> {
+        BitcoinHeader::from_bytes(data)
+            .map(PyBitcoinHeader)
+            .map_err(bitfold_error)
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.0.to_bytes())
+    }
+
+    /// Block hash in internal byte order.
+    fn hash<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.0.hash())
+    }
+
+    #[getter]
+    fn version(&self) -> u32 {
+        self.0.version
+    }
+
+    #[getter]
+    fn hash_prev_block<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.0.hash_prev_block)
+    }
+
+    #[getter]
+    fn hash_merkle_root<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.0.hash_merkle_root)
+    }
+
+    #[getter]
+    fn timestamp(&self) -> u32 {
+        self.0.timestamp
+    }
+
+    #[getter]
+    fn bits(&self) -> u32 {
+        self.0.bits()
+    }
+
+    #[getter]
+    fn nonce(&self) -> u32 {
+        self.0.nonce
+    }
+
+    fn __repr__(&self) -> String {
+        let mut hash = self.0.hash();
+        hash.reverse();
+        format!("BitcoinHeader({})", hex(&hash))
+    }
+}
+
+/// Headers of a network as returned by the json-rpc of bitcoind, see [`BlockReader`].
+#[pyclass(name = "BlockReader", module = "bitfold")]
+struct PyBlockReader(BlockReader);
+
+#[pymethods]
+impl PyBlockReader {
+    #[staticmethod]
+    #[pyo3(signature = (path, network = "mainnet"))]
+    fn from_file(path: &str, network: &str) -> PyResult<Self> {
+        let params = ChainParams::for_network(self::network(network)?);
+        BlockReader::new_from_file_with_params(path, params)
+            .map(PyBlockReader)
+            .map_err(bitfold_error)
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (json, network = "mainnet"))]
+    fn from_json(json: &str, network: &str) -> PyResult<Self> {
+        let params = ChainParams::for_network(self::network(network)?);
+        BlockReader::new_from_json_with_params(json, params)
+            .map(PyBlockReader)
+            .map_err(bitfold_error)
+    }
+
+    #[getter]
+    fn network(&self) -> &'static str {
+        network_name(self.0.params().network)
+    }
+
+    fn header(&self, height: u32) -> PyResult<PyBitcoinHeader> {
+        self.0
+            .get_block_header(height)
+            .map(PyBitcoinHeader)
+            .map_err(bitfold_error)
+    }
+
+    /// Headers by ascending height as `(height, header)` tuples.
+    fn headers(&self) -> PyResult<Vec<(u32, PyBitcoinHeader)>> {
+        let headers = self.0.get_block_headers().map_err(bitfold_error)?;
+        Ok(headers
+            .into_iter()
+            .map(|(height, header)| (height, PyBitcoinHeader(header)))
+            .collect())
+    }
+}
+
+/// Zeromorph SRS the proofs are proven and compressed with.
+#[pyclass(name = "Srs", module = "bitfold")]
+struct PySrs(ZeromorphSRS);
+
+#[pymethods]
+impl PySrs {
+    /// Sets up an SRS for `num_vars` variables from `seed`, or from entropy without a seed.
+    ///
+    /// For testing only: whoever sets up the SRS knows its trapdoor.
+    #[staticmethod]
+    #[pyo3(signature = (num_vars = 25, seed = None))]
+    fn setup(num_vars: usize, seed: Option<u64>) -> PyResult<Self> {
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        <Zeromorph<Bn254> as PolyCommitmentScheme<_>>::setup(num_vars, b"bitfold_srs", &mut rng)
+            .map(PySrs)
+            .map_err(bitfold_error)
+    }
+}
+
+/// Prover of a header chain from the genesis block of a network, see [`Bn254GrumpkinIVC`].
+#[pyclass(name = "BitcoinIVC", module = "bitfold")]
+struct PyBitcoinIVC(Bn254GrumpkinIVC);
+
+#[pymethods]
+impl PyBitcoinIVC {
+    #[staticmethod]
+    #[pyo3(signature = (srs, network = "mainnet"))]
+    fn setup(srs: &PySrs, network: &str) -> PyResult<Self> {
+        let chain_params = ChainParams::for_network(self::network(network)?);
+        Bn254GrumpkinIVC::setup(chain_params, Anchor::Genesis, &srs.0)
+            .map(PyBitcoinIVC)
+            .map_err(bitfold_error)
+    }
+
+    /// Proves the headers on top of the proven tip, other Python threads run meanwhile.
+    fn extend(&mut self, py: Python<'_>, headers: Vec<PyBitcoinHeader>) -> PyResult<()> {
+        let headers: Vec<_> = headers.into_iter().map(|header| header.0).collect();
+        // errors are not `Send`, they leave the closure as their message
+        py.allow_threads(|| self.0.extend(&headers).map_err(|error| error.to_string()))
+            .map_err(bitfold_error)
+    }
+
+    /// State after the last proven header.
+    fn state<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        chain_state_dict(py, self.0.state())
+    }
+
+    #[getter]
+    fn num_steps(&self) -> usize {
+        self.0.num_steps()
+    }
+
+    /// Verifies the uncompressed proof and returns the state it proves.
+    fn verify<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let state = py
+            .allow_threads(|| self.0.verify().map_err(|error| error.to_string()))
+            .map_err(bitfold_error)?;
+        chain_state_dict(py, &state)
+    }
+
+    /// Compresses the proof with the SNARK key of `key`, the bytes are verified by
+    /// `VerifierKey.verify`. Other Python threads run meanwhile.
+    fn compress<'py>(&self, py: Python<'py>, key: &PyVerifierKey) -> PyResult<Bound<'py, PyBytes>> {
+        let bytes = py
+            .allow_threads(|| {
+                let proof = self
+                    .0
+                    .compress(&key.0.snark_key)
+                    .map_err(|error| error.to_string())?;
+                let mut bytes = Vec::new();
+                proof
+                    .serialize_compressed(&mut bytes)
+                    .map_err(|error| error.to_string())?;
+                Ok::<_, String>(bytes)
+            })
+            .map_err(bitfold_error)?;
+        Ok(PyBytes::new_bound(py, &bytes))
+    }
+}
+
+/// Key verifying compressed proofs, see [`Bn254GrumpkinVerifierKey`].
+#[pyclass(name = "VerifierKey", module = "bitfold")]
+struct PyVerifierKey(Bn254GrumpkinVerifierKey);
+
+#[pymethods]
+impl PyVerifierKey {
+    #[staticmethod]
+    #[pyo3(signature = (srs, network = "mainnet"))]
+    fn setup(srs: &PySrs, network: &str) -> PyResult<Self> {
+        let chain_params = ChainParams::for_network(self::network(network)?);
+        Bn254GrumpkinVerifierKey::setup(&chain_params, Anchor::Genesis, &srs.0)
+            .map(PyVerifierKey)
+            .map_err(bitfold_error)
+    }
+
+    #[staticmethod]
+    fn from_bytes(data: &[u8]) -> PyResult<Self> {
+        Bn254GrumpkinVerifierKey::from_bytes(data)
+            .map(PyVerifierKey)
+            .map_err(bitfold_error)
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let bytes = self.0.to_bytes().map_err(bitfold_error)?;
+        Ok(PyBytes::new_bound(py, &bytes))
+    }
+
+    #[getter]
+    fn network(&self) -> &'static str {
+        network_name(self.0.network)
+    }
+
+    /// Verifies a compressed proof and returns the state it proves.
+    fn verify<'py>(&self, py: Python<'py>, proof: &[u8]) -> PyResult<Bound<'py, PyDict>> {
+        let proof =
+            Bn254GrumpkinCompressedProof::deserialize_compressed(proof).map_err(bitfold_error)?;
+        let state = self.0.verify(&proof).map_err(bitfold_error)?;
+        state_dict(py, &state)
+    }
+}
+
+/// Verifies a compressed proof with the bytes of a verifier key and returns the state it proves.
+#[pyfunction]
+fn verify<'py>(py: Python<'py>, proof: &[u8], vk: &[u8]) -> PyResult<Bound<'py, PyDict>> {
+    PyVerifierKey::from_bytes(vk)?.verify(py, proof)
+}
+
+#[pymodule]
+#[pyo3(name = "bitfold")]
+fn bitfold_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("BitfoldError", m.py().get_type_bound::<BitfoldError>())?;
+    m.add_class::<PyBitcoinHeader>()?;
+    m.add_class::<PyBlockReader>()?;
+    m.add_class::<PySrs>()?;
+    m.add_class::<PyBitcoinIVC>()?;
+    m.add_class::<PyVerifierKey>()?;
+    m.add_function(wrap_pyfunction!(verify, m)?)?;
+    Ok(())
+}
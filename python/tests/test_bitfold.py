@@ -0,0 +1,117 @@
+"""Tests of the python bindings, run after `maturin develop --release` in `python/` with
+`python -m unittest discover tests`."""
+
+import hashlib
+import json
+import unittest
+
+import bitfold
+
+GENESIS_MERKLE_ROOT = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+REGTEST_GENESIS_HASH = "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206"
+REGTEST_BITS = 0x207FFFFF
+
+
+def sha256d(data):
+    return hashlib.sha256(hashlib.sha256(data).digest()).digest()
+
+
+def mine_regtest_header(parent):
+    """Header on top of `parent`, the regtest target is met by about half of the hashes."""
+    nonce = 0
+    while True:
+        header = bitfold.BitcoinHeader(
+            parent.version,
+            parent.hash(),
+            parent.hash_merkle_root,
+            parent.timestamp + 600,
+            parent.bits,
+            nonce,
+        )
+        if header.hash()[31] < 0x7F:
+            return header
+        nonce += 1
+
+
+def rpc_header(height, header):
+    """The header as returned by `getblockheader` of bitcoind, hashes in rpc byte order."""
+    return {
+        "hash": header.hash()[::-1].hex(),
+        "confirmations": 1,
+        "height": height,
+        "version": header.version,
+        "merkleroot": header.hash_merkle_root[::-1].hex(),
+        "time": header.timestamp,
+        "nonce": header.nonce,
+        "bits": f"{header.bits:08x}",
+        "previousblockhash": header.hash_prev_block[::-1].hex(),
+    }
+
+
+def regtest_genesis():
+    return bitfold.BitcoinHeader(
+        1, bytes(32), bytes.fromhex(GENESIS_MERKLE_ROOT)[::-1], 1296688602, REGTEST_BITS, 2
+    )
+
+
+class HeaderTest(unittest.TestCase):
+    def test_header_bytes(self):
+        genesis = regtest_genesis()
+        self.assertEqual(genesis.hash(), bytes.fromhex(REGTEST_GENESIS_HASH)[::-1])
+        data = genesis.to_bytes()
+        self.assertEqual(len(data), 80)
+        self.assertEqual(sha256d(data), genesis.hash())
+        self.assertEqual(bitfold.BitcoinHeader.from_bytes(data), genesis)
+        with self.assertRaises(bitfold.BitfoldError):
+            bitfold.BitcoinHeader.from_bytes(data[1:])
+        with self.assertRaises(bitfold.BitfoldError):
+            bitfold.BitcoinHeader(1, bytes(31), bytes(32), 0, REGTEST_BITS, 0)
+
+    def test_block_reader(self):
+        genesis = regtest_genesis()
+        header = mine_regtest_header(genesis)
+        data = json.dumps([rpc_header(0, genesis), rpc_header(1, header)])
+        reader = bitfold.BlockReader.from_json(data, network="regtest")
+        self.assertEqual(reader.network, "regtest")
+        self.assertEqual(reader.header(1), header)
+        self.assertEqual(reader.headers(), [(0, genesis), (1, header)])
+        with self.assertRaises(bitfold.BitfoldError):
+            reader.header(2)
+
+        # the genesis block of the data is checked against the network
+        with self.assertRaises(bitfold.BitfoldError):
+            bitfold.BlockReader.from_json(data, network="mainnet")
+
+
+class ProofTest(unittest.TestCase):
+    def test_prove_and_verify(self):
+        genesis = regtest_genesis()
+        header = mine_regtest_header(genesis)
+
+        srs = bitfold.Srs.setup(25, seed=0)
+        key = bitfold.VerifierKey.setup(srs, network="regtest")
+        ivc = bitfold.BitcoinIVC.setup(srs, network="regtest")
+        ivc.extend([header])
+        self.assertEqual(ivc.num_steps, 1)
+
+        state = ivc.verify()
+        self.assertEqual(state, ivc.state())
+        self.assertEqual(state["tip_hash"], header.hash())
+        self.assertEqual(state["tip_height"], 1)
+        # regtest blocks at the pow limit have a work of 2 each
+        self.assertEqual(state["chainwork"], 4)
+
+        proof = ivc.compress(key)
+        vk = key.to_bytes()
+        self.assertEqual(key.verify(proof), state)
+        self.assertEqual(bitfold.verify(proof, vk), state)
+        self.assertEqual(bitfold.VerifierKey.from_bytes(vk).network, "regtest")
+
+        with self.assertRaises(bitfold.BitfoldError):
+            bitfold.verify(proof[1:], vk)
+        with self.assertRaises(bitfold.BitfoldError):
+            bitfold.verify(proof, vk[: len(vk) // 2])
+
+
+if __name__ == "__main__":
+    unittest.main()
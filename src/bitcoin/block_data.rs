@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 
-use crate::bitcoin::BitcoinHeader;
+use crate::bitcoin::validation::MEDIAN_TIME_SPAN;
+use crate::bitcoin::{BitcoinHeader, ChainParams, Checkpoint};
+use num_bigint::BigUint;
 use std::collections::BTreeMap;
 use std::error::Error as ErrorTrait;
 use std::fs::File;
@@ -28,35 +30,80 @@ struct BlockHeaderRpc {
     nonce: u32,
     #[serde(with = "hex")]
     bits: Vec<u8>,
-    #[serde(with = "hex")]
+    // the genesis block has no previous block
+    #[serde(with = "hex", default = "zero_hash")]
     previousblockhash: Vec<u8>,
+    // total work of the chain up to the block, big-endian
+    #[serde(with = "hex", default)]
+    chainwork: Vec<u8>,
+}
+
+fn zero_hash() -> Vec<u8> {
+    [0; 32].to_vec()
 }
 
 pub struct BlockReader {
     // sorted map of header with height as key
     headers_rpc: BTreeMap<u32, BlockHeaderRpc>,
+    // network the headers belong to
+    params: ChainParams,
 }
 
 impl BlockReader {
+    /// Reads mainnet headers from a json file.
     pub fn new_from_file(data_file_path: &str) -> Result<BlockReader, Box<dyn ErrorTrait>> {
+        Self::new_from_file_with_params(data_file_path, ChainParams::mainnet())
+    }
+
+    pub fn new_from_file_with_params(
+        data_file_path: &str,
+        params: ChainParams,
+    ) -> Result<BlockReader, Box<dyn ErrorTrait>> {
         let path = Path::new(data_file_path);
         let file = File::open(&path)?;
         let reader = BufReader::new(file);
         let headers: Vec<BlockHeaderRpc> = serde_json::from_reader(reader)?;
-        let mut headers_rpc = BTreeMap::new();
-        for header in headers {
-            headers_rpc.insert(header.height, header);
-        }
-        Ok(BlockReader { headers_rpc })
+        Self::new_from_rpc_headers(headers, params)
     }
 
+    /// Reads mainnet headers from a json string.
     pub fn new_from_json(json: &str) -> Result<BlockReader, Box<dyn ErrorTrait>> {
+        Self::new_from_json_with_params(json, ChainParams::mainnet())
+    }
+
+    pub fn new_from_json_with_params(
+        json: &str,
+        params: ChainParams,
+    ) -> Result<BlockReader, Box<dyn ErrorTrait>> {
         let headers: Vec<BlockHeaderRpc> = serde_json::from_str(json)?;
+        Self::new_from_rpc_headers(headers, params)
+    }
+
+    fn new_from_rpc_headers(
+        headers: Vec<BlockHeaderRpc>,
+        params: ChainParams,
+    ) -> Result<BlockReader, Box<dyn ErrorTrait>> {
         let mut headers_rpc = BTreeMap::new();
         for header in headers {
             headers_rpc.insert(header.height, header);
         }
-        Ok(BlockReader { headers_rpc })
+        let block_reader = BlockReader {
+            headers_rpc,
+            params,
+        };
+
+        // a genesis block in the data must be the genesis block of the network
+        if block_reader.headers_rpc.contains_key(&0) {
+            let genesis = block_reader.get_block_header(0)?;
+            if genesis.hash() != block_reader.params.genesis_hash {
+                return Err(Box::new(BlockReaderError));
+            }
+        }
+        Ok(block_reader)
+    }
+
+    pub fn params(&self) -> &ChainParams {
+        &self.params
     }
 
     fn into_internal(&self, header: BlockHeaderRpc) -> BitcoinHeader {
@@ -98,11 +145,41 @@ impl BlockReader {
 
         return Ok(headers);
     }
+
+    /// Checkpoint at the block at `height`, the data must contain its chainwork, the first
+    /// block of its difficulty adjustment period and its 10 predecessors, or all of them if the
+    /// block is one of the first blocks of the chain.
+    pub fn get_checkpoint(&self, height: u32) -> Result<Checkpoint, Box<dyn ErrorTrait>> {
+        let header = self.headers_rpc.get(&height).ok_or(BlockReaderError)?;
+        if header.chainwork.is_empty() {
+            return Err(Box::new(BlockReaderError));
+        }
+        let period_start = height - height % self.params.difficulty_adjustment_interval();
+
+        let window_start = height.saturating_sub(MEDIAN_TIME_SPAN as u32 - 1);
+        let mut mtp_window = (window_start..=height)
+            .map(|height| self.get_block_header(height).map(|header| header.timestamp))
+            .collect::<Result<Vec<u32>, _>>()?;
+        // the window of the first blocks is filled with the genesis timestamp
+        while mtp_window.len() < MEDIAN_TIME_SPAN {
+            mtp_window.insert(0, mtp_window[0]);
+        }
+
+        Ok(Checkpoint {
+            height,
+            hash: self.get_block_header(height)?.hash(),
+            chainwork: BigUint::from_bytes_be(&header.chainwork),
+            bits: self.get_block_header(height)?.bits(),
+            period_start_timestamp: self.get_block_header(period_start)?.timestamp,
+            mtp_window,
+        })
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::bitcoin::data::test_json::{TEST_JSON_RPC, TEST_JSON_RPC_GENESIS};
     use ark_crypto_primitives::crh::sha256::{digest::Digest, Sha256};
     #[test]
     fn read_block_header_in_rpc_format() {
@@ -124,6 +201,24 @@ mod test {
         assert_eq!(height, 838638);
     }
 
+    #[test]
+    fn read_checkpoint() {
+        let reader = BlockReader::new_from_json(TEST_JSON_RPC_GENESIS).unwrap();
+        let genesis = reader.get_checkpoint(0).unwrap();
+        assert_eq!(genesis, Checkpoint::genesis(reader.params()));
+
+        let checkpoint = reader.get_checkpoint(2).unwrap();
+        assert_eq!(checkpoint.hash, reader.get_block_header(2).unwrap().hash());
+        assert_eq!(checkpoint.chainwork, BigUint::from(0x300030003u64));
+        assert_eq!(checkpoint.period_start_timestamp, genesis.mtp_window[0]);
+        assert_eq!(&checkpoint.mtp_window[..9], &genesis.mtp_window[..9]);
+        assert_eq!(checkpoint.mtp_window[10], 1231469744);
+
+        // the first block of the difficulty adjustment period is missing
+        let reader = BlockReader::new_from_json(TEST_JSON_RPC).unwrap();
+        assert!(reader.get_checkpoint(838637).is_err());
+    }
+
     #[test]
     fn read_block_headers_verify_chain_hash() {
         let reader = BlockReader::new_from_file(TEST_DATA_PATH).unwrap();
@@ -0,0 +1,186 @@
+use ark_std::{string::String, vec, vec::Vec};
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+
+use crate::bitcoin::pow;
+use crate::bitcoin::validation::MEDIAN_TIME_SPAN;
+use crate::bitcoin::{BitcoinHeader, ChainParams, Network};
+
+/// A block the proven chain can start from instead of genesis, together with the context of the
+/// chain up to the block that is needed to validate the blocks after it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub height: u32,
+    // block hash in internal byte order
+    pub hash: Vec<u8>,
+    // total work of the chain up to and including the block
+    pub chainwork: BigUint,
+    // bits required for the next block, see `ChainState::bits`
+    pub bits: u32,
+    // timestamp of the first block of the difficulty adjustment period the block belongs to
+    pub period_start_timestamp: u32,
+    // timestamps of the block and its predecessors, oldest first, for the median time past rule
+    pub mtp_window: Vec<u32>,
+}
+
+impl Checkpoint {
+    /// Checkpoint at `header` as if it was the first block of the chain, i.e. the chainwork only
+    /// counts `header`, and the header starts its difficulty adjustment period and fills the whole
+    /// median time past window.
+    ///
+    /// For the genesis block this is more permissive than Bitcoin Core for the next 10 blocks,
+    /// whose median time past is taken over the existing blocks only.
+    pub fn from_header(header: &BitcoinHeader, height: u32) -> Checkpoint {
+        let work = pow::compact_to_target(header.bits())
+            .map(|target| pow::block_work(&target))
+            .unwrap_or_default();
+        Checkpoint {
+            height,
+            hash: header.hash(),
+            chainwork: work,
+            bits: header.bits(),
+            period_start_timestamp: header.timestamp,
+            mtp_window: vec![header.timestamp; MEDIAN_TIME_SPAN],
+        }
+    }
+
+    pub fn genesis(params: &ChainParams) -> Checkpoint {
+        Checkpoint::from_header(&params.genesis, 0)
+    }
+
+    /// Checkpoints proofs of `network` can be anchored to, sorted by height: the genesis block,
+    /// followed by the checkpoints listed in `src/bitcoin/data/checkpoints/<network>.json`.
+    ///
+    /// Additional checkpoints can be read from a node with [`BlockReader::get_checkpoint`](crate::bitcoin::BlockReader::get_checkpoint),
+    /// `bitfold checkpoint` prints them in the format of the curated files.
+    ///
+    /// The mainnet file only lists checkpoints that the header fixtures of the tests cover, which
+    /// checks them against the data of bitcoind.
+    ///
+    /// ToDo: add recent checkpoints of each network, read from a synced node and reviewed against
+    /// independent block explorers, the testnet and signet files are still empty.
+    pub fn curated(network: Network) -> Vec<Checkpoint> {
+        let json = match network {
+            Network::Mainnet => include_str!("data/checkpoints/mainnet.json"),
+            Network::Testnet3 => include_str!("data/checkpoints/testnet3.json"),
+            Network::Testnet4 => include_str!("data/checkpoints/testnet4.json"),
+            Network::Signet => include_str!("data/checkpoints/signet.json"),
+            Network::Regtest => "[]",
+        };
+        let curated: Vec<CuratedCheckpoint> =
+            serde_json::from_str(json).expect("valid curated checkpoints");
+        let mut checkpoints = vec![Checkpoint::genesis(&ChainParams::for_network(network))];
+        checkpoints.extend(
+            curated
+                .into_iter()
+                .map(|curated| curated.into_checkpoint().expect("valid curated checkpoint")),
+        );
+        checkpoints
+    }
+
+    /// The checkpoint in the json format of the curated checkpoint files.
+    pub fn to_curated_json(&self) -> String {
+        let mut hash = self.hash.clone();
+        hash.reverse();
+        let curated = CuratedCheckpoint {
+            height: self.height,
+            hash: hex::encode(hash),
+            chainwork: hex::encode(self.chainwork.to_bytes_be()),
+            bits: hex::encode(self.bits.to_be_bytes()),
+            period_start_time: self.period_start_timestamp,
+            mtp_window: self.mtp_window.clone(),
+        };
+        serde_json::to_string_pretty(&curated).expect("serializable checkpoint")
+    }
+}
+
+// checkpoint as listed in the curated files, hashes and bits are hex strings in the byte order
+// displayed by bitcoind's `getblockheader`
+#[derive(Serialize, Deserialize)]
+struct CuratedCheckpoint {
+    height: u32,
+    hash: String,
+    chainwork: String,
+    bits: String,
+    period_start_time: u32,
+    mtp_window: Vec<u32>,
+}
+
+impl CuratedCheckpoint {
+    fn into_checkpoint(self) -> Option<Checkpoint> {
+        let mut hash = hex::decode(&self.hash).ok()?;
+        hash.reverse();
+        let bits = u32::from_str_radix(&self.bits, 16).ok()?;
+        let chainwork = BigUint::from_bytes_be(&hex::decode(&self.chainwork).ok()?);
+        if hash.len() != 32 || self.mtp_window.len() != MEDIAN_TIME_SPAN {
+            return None;
+        }
+        Some(Checkpoint {
+            height: self.height,
+            hash,
+            chainwork,
+            bits,
+            period_start_timestamp: self.period_start_time,
+            mtp_window: self.mtp_window,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::bitcoin::data::test_json::TEST_JSON_RPC_GENESIS;
+    use crate::bitcoin::BlockReader;
+
+    #[test]
+    fn genesis_checkpoint() {
+        let checkpoint = Checkpoint::genesis(&ChainParams::mainnet());
+        assert_eq!(checkpoint.height, 0);
+        assert_eq!(checkpoint.hash, ChainParams::mainnet().genesis_hash);
+        // work of a block at the mainnet pow limit, as reported by `getblockheader`
+        assert_eq!(checkpoint.chainwork, BigUint::from(0x100010001u64));
+        assert_eq!(checkpoint.mtp_window, vec![1231006505; MEDIAN_TIME_SPAN]);
+    }
+
+    #[test]
+    fn curated_checkpoints() {
+        for network in [
+            Network::Mainnet,
+            Network::Testnet3,
+            Network::Testnet4,
+            Network::Signet,
+            Network::Regtest,
+        ] {
+            let params = ChainParams::for_network(network);
+            let checkpoints = Checkpoint::curated(network);
+            assert_eq!(checkpoints[0], Checkpoint::genesis(&params));
+            assert!(checkpoints
+                .windows(2)
+                .all(|pair| pair[0].height < pair[1].height));
+        }
+    }
+
+    #[test]
+    fn curated_checkpoints_match_headers() {
+        // the mainnet checkpoint at height 2 is derived from the first mainnet headers
+        let reader = BlockReader::new_from_json(TEST_JSON_RPC_GENESIS).unwrap();
+        let curated = Checkpoint::curated(Network::Mainnet);
+        let checkpoint = curated
+            .iter()
+            .find(|checkpoint| checkpoint.height == 2)
+            .unwrap();
+        assert_eq!(checkpoint, &reader.get_checkpoint(2).unwrap());
+    }
+
+    #[test]
+    fn curated_json_round_trip() {
+        let reader = BlockReader::new_from_json(TEST_JSON_RPC_GENESIS).unwrap();
+        let checkpoint = reader.get_checkpoint(2).unwrap();
+        let json = checkpoint.to_curated_json();
+        // hashes are listed as displayed by bitcoind
+        assert!(json.contains("000000006a625f06636b8bb6ac7b960a8d03705d1ace08b1a19da3fdcc99ddbd"));
+
+        let curated: CuratedCheckpoint = serde_json::from_str(&json).unwrap();
+        assert_eq!(curated.into_checkpoint(), Some(checkpoint));
+    }
+}
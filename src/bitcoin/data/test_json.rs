@@ -1,3 +1,5 @@
+use crate::bitcoin::ChainParams;
+
 pub const TEST_JSON_RPC: &str = r#"[
     {
       "hash": "0000000000000000000219957045483495e40610b47cd600152ec76e256ba523",
@@ -67,3 +69,136 @@ pub const TEST_JSON_RPC: &str = r#"[
       "previousblockhash": "000000000000000000034c4c2caedc73b08bd64a6ffe4ae3f5852f9369a15a9d"
     }
   ]"#;
+
+// Synthetic testnet3 headers mined against a relaxed pow limit (`0x207fffff`) and difficulty
+// (`0x2000ffff`), see `min_difficulty_test_params`. Blocks 1002, 1004 and 1005 are mined at the
+// pow limit since they are more than 20 minutes late, block 1003 and 1006 return to the difficulty
+// of block 1001.
+pub const TEST_JSON_RPC_MIN_DIFFICULTY: &str = r#"[
+    {
+      "hash": "004d5a38583a612342a6d7e026467eb729b77f8b3b83b0612a93913099ac4e12",
+      "confirmations": 10,
+      "height": 1000,
+      "version": 536870912,
+      "merkleroot": "1d58e34310a5900fb5d3a1c37686f6e1f040ec78de05940c40c66bfde5d71ee9",
+      "time": 1714780000,
+      "nonce": 229,
+      "bits": "2000ffff",
+      "previousblockhash": "fa46a003ee10503d378214dd59aaf1d1242e8db6285111fae6348835b10548bd"
+    },
+    {
+      "hash": "003f9f50133a787ae1051ad622374271053c1980a525886e4bbf70a10b8485f4",
+      "confirmations": 10,
+      "height": 1001,
+      "version": 536870912,
+      "merkleroot": "39bfe274243d6a8e760e080186b129793cc8e868d3045aa2fd17978c31e2621b",
+      "time": 1714780600,
+      "nonce": 88,
+      "bits": "2000ffff",
+      "previousblockhash": "004d5a38583a612342a6d7e026467eb729b77f8b3b83b0612a93913099ac4e12"
+    },
+    {
+      "hash": "5063f568c6d6d147d32ed2b590c7c215539f38410798aec306f8066ed5321083",
+      "confirmations": 10,
+      "height": 1002,
+      "version": 536870912,
+      "merkleroot": "a7c123484ad80d5871b5a51a5cc7e0d3f0b64ffc0530edbb4c27def2af4295be",
+      "time": 1714782100,
+      "nonce": 0,
+      "bits": "207fffff",
+      "previousblockhash": "003f9f50133a787ae1051ad622374271053c1980a525886e4bbf70a10b8485f4"
+    },
+    {
+      "hash": "00f90e61d67ffd3496e32d0f719390cbad2b2ff917350aa138e798ffc6ebadc0",
+      "confirmations": 10,
+      "height": 1003,
+      "version": 536870912,
+      "merkleroot": "719139e7168bbc603875cced33f7a2b986469764cd1bdb93d015ab0234e2a1da",
+      "time": 1714782400,
+      "nonce": 228,
+      "bits": "2000ffff",
+      "previousblockhash": "5063f568c6d6d147d32ed2b590c7c215539f38410798aec306f8066ed5321083"
+    },
+    {
+      "hash": "644cf3566b131e7b05c3cef8fd8b097fea6a952fb51a2daf1013bb58e460a566",
+      "confirmations": 10,
+      "height": 1004,
+      "version": 536870912,
+      "merkleroot": "1a78e09e7f0df08e426cbaac0d95a7b53ee80926e75ae0c1264280a6cde707d8",
+      "time": 1714783601,
+      "nonce": 3,
+      "bits": "207fffff",
+      "previousblockhash": "00f90e61d67ffd3496e32d0f719390cbad2b2ff917350aa138e798ffc6ebadc0"
+    },
+    {
+      "hash": "5adc2f880cb83b58839aa649f39cd7329749f129d47774d4dafc874a6a7508d7",
+      "confirmations": 10,
+      "height": 1005,
+      "version": 536870912,
+      "merkleroot": "331361f63036b79f59068f9727d150ad841963d8f8b699a7dcbf1e2a629edf35",
+      "time": 1714784901,
+      "nonce": 1,
+      "bits": "207fffff",
+      "previousblockhash": "644cf3566b131e7b05c3cef8fd8b097fea6a952fb51a2daf1013bb58e460a566"
+    },
+    {
+      "hash": "00dae73b3abc2c4deac0bf8338dbfb5afbd06eb82f6cc82869a99a5cd7c7ecde",
+      "confirmations": 10,
+      "height": 1006,
+      "version": 536870912,
+      "merkleroot": "31a4735922cbfab2e491bc92c93aacfdaa96797c15e9b47578a2eb0ba8e3a981",
+      "time": 1714784961,
+      "nonce": 286,
+      "bits": "2000ffff",
+      "previousblockhash": "5adc2f880cb83b58839aa649f39cd7329749f129d47774d4dafc874a6a7508d7"
+    }
+  ]"#;
+
+// The first blocks of mainnet, starting at the genesis block.
+pub const TEST_JSON_RPC_GENESIS: &str = r#"[
+    {
+      "hash": "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+      "confirmations": 900000,
+      "height": 0,
+      "version": 1,
+      "merkleroot": "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b",
+      "time": 1231006505,
+      "chainwork": "0000000000000000000000000000000000000000000000000000000100010001",
+      "nonce": 2083236893,
+      "bits": "1d00ffff"
+    },
+    {
+      "hash": "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048",
+      "confirmations": 899999,
+      "height": 1,
+      "version": 1,
+      "merkleroot": "0e3e2357e806b6cdb1f70b54c3a3a17b6714ee1f0e68bebb44a74b1efd512098",
+      "time": 1231469665,
+      "chainwork": "0000000000000000000000000000000000000000000000000000000200020002",
+      "nonce": 2573394689,
+      "bits": "1d00ffff",
+      "previousblockhash": "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+    },
+    {
+      "hash": "000000006a625f06636b8bb6ac7b960a8d03705d1ace08b1a19da3fdcc99ddbd",
+      "confirmations": 899998,
+      "height": 2,
+      "version": 1,
+      "merkleroot": "9b0fc92260312ce44e74ef369f5c66bbb85848f2eddd5a7a1cde251e54ccfdd5",
+      "time": 1231469744,
+      "chainwork": "0000000000000000000000000000000000000000000000000000000300030003",
+      "nonce": 1639830024,
+      "bits": "1d00ffff",
+      "previousblockhash": "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048"
+    }
+  ]"#;
+
+pub fn min_difficulty_test_params() -> ChainParams {
+    let mut pow_limit = [0xff; 32].to_vec();
+    pow_limit[31] = 0x7f;
+    ChainParams {
+        pow_limit,
+        pow_limit_bits: 0x207fffff,
+        ..ChainParams::testnet3()
+    }
+}
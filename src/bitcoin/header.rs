@@ -1,4 +1,16 @@
+use ark_crypto_primitives::crh::sha256::{digest::Digest, Sha256};
+use ark_std::vec::Vec;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Length of a serialized header.
+pub const HEADER_LEN: usize = 80;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum HeaderError {
+    #[error("header is {0} bytes, not {HEADER_LEN}")]
+    InvalidLength(usize),
+}
 
 /// Bitcoin block header
 /// Nodes collect new transactions into a block, hash them into a hash tree,
@@ -11,7 +23,7 @@ use serde::{Deserialize, Serialize};
 /// ref code: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/primitives/block.h#L21
 /// ref doc: https://developer.bitcoin.org/reference/block_chain.html
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct BitcoinHeader {
     pub version: u32,
     pub hash_prev_block: Vec<u8>,
@@ -25,6 +37,26 @@ pub struct BitcoinHeader {
 }
 
 impl BitcoinHeader {
+    /// Parses a header in its 80 byte serialization, the inverse of [`BitcoinHeader::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<BitcoinHeader, HeaderError> {
+        if bytes.len() != HEADER_LEN {
+            return Err(HeaderError::InvalidLength(bytes.len()));
+        }
+        let u32_at = |offset: usize| {
+            let mut word = [0u8; 4];
+            word.copy_from_slice(&bytes[offset..offset + 4]);
+            u32::from_le_bytes(word)
+        };
+        Ok(BitcoinHeader {
+            version: u32_at(0),
+            hash_prev_block: bytes[4..36].to_vec(),
+            hash_merkle_root: bytes[36..68].to_vec(),
+            timestamp: u32_at(68),
+            target_bits: bytes[72..76].to_vec(),
+            nonce: u32_at(76),
+        })
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut result = Vec::new();
 
@@ -38,6 +70,18 @@ impl BitcoinHeader {
 
         result
     }
+
+    /// Block hash in internal byte order, i.e. sha256d(header).
+    pub fn hash(&self) -> Vec<u8> {
+        Sha256::digest(Sha256::digest(self.to_bytes())).to_vec()
+    }
+
+    /// Compact representation of the target (nBits).
+    pub fn bits(&self) -> u32 {
+        let mut bits = [0u8; 4];
+        bits.copy_from_slice(&self.target_bits[..4]);
+        u32::from_le_bytes(bits)
+    }
 }
 
 impl Default for BitcoinHeader {
@@ -53,9 +97,9 @@ impl Default for BitcoinHeader {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 pub(crate) mod test {
-    use super::BitcoinHeader;
+    use super::{BitcoinHeader, HeaderError};
     use crate::bitcoin::block_data::BlockReader;
     use crate::bitcoin::data::test_json::TEST_JSON_RPC;
 
@@ -76,4 +120,30 @@ pub(crate) mod test {
         let expected_bytes = hex::decode("00605526382006311de7da73939ef66149fe4217f532f7b821bf0000000000000000000002cc1a24a6dbe5d4b689b04168c61c73ff52aaf34ae1317035e80ad7bb2f2708c4cd1666d3620317131926e7").unwrap();
         assert_eq!(bytes, expected_bytes);
     }
+
+    #[test]
+    fn header_hash_and_bits() {
+        let block_reader = BlockReader::new_from_json(TEST_JSON_RPC).unwrap();
+
+        let header = block_reader.get_block_header(838637).unwrap();
+        let mut expected_hash =
+            hex::decode("0000000000000000000219957045483495e40610b47cd600152ec76e256ba523")
+                .unwrap();
+        expected_hash.reverse();
+        assert_eq!(header.hash(), expected_hash);
+        assert_eq!(header.bits(), 0x170362d3);
+    }
+
+    #[test]
+    fn header_from_bytes() {
+        let block_reader = BlockReader::new_from_json(TEST_JSON_RPC).unwrap();
+
+        let header = block_reader.get_block_header(838637).unwrap();
+        let bytes = header.to_bytes();
+        assert_eq!(BitcoinHeader::from_bytes(&bytes), Ok(header));
+        assert_eq!(
+            BitcoinHeader::from_bytes(&bytes[1..]),
+            Err(HeaderError::InvalidLength(79))
+        );
+    }
 }
@@ -1,11 +1,23 @@
 #![allow(unused_imports)]
 
+#[cfg(feature = "std")]
 mod block_data;
+mod checkpoint;
 mod header;
+mod params;
+pub mod pow;
+pub mod signet;
+pub mod tx;
+mod validation;
 
 pub(crate) mod data {
     pub(crate) mod test_json;
 }
 
+#[cfg(feature = "std")]
 pub use block_data::{BlockReader, BlockReaderError};
-pub use header::BitcoinHeader;
+pub use checkpoint::Checkpoint;
+pub use header::{BitcoinHeader, HeaderError, HEADER_LEN};
+pub use params::{ChainParams, Network};
+pub(crate) use validation::MAX_TIMEWARP;
+pub use validation::{HeaderValidator, ValidationError};
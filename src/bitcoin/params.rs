@@ -0,0 +1,232 @@
+use ark_std::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::bitcoin::signet::DEFAULT_SIGNET_CHALLENGE;
+use crate::bitcoin::BitcoinHeader;
+
+/// Bitcoin networks supported by bitfold.
+///
+/// ref code: https://github.com/bitcoin/bitcoin/blob/master/src/kernel/chainparams.cpp
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Testnet3,
+    Testnet4,
+    Signet,
+    Regtest,
+}
+
+impl Network {
+    /// The p2p message start bytes of the network.
+    pub fn message_start(&self) -> [u8; 4] {
+        match self {
+            Network::Mainnet => [0xf9, 0xbe, 0xb4, 0xd9],
+            Network::Testnet3 => [0x0b, 0x11, 0x09, 0x07],
+            Network::Testnet4 => [0x1c, 0x16, 0x3f, 0x28],
+            Network::Signet => [0x0a, 0x03, 0xcf, 0x40],
+            Network::Regtest => [0xfa, 0xbf, 0xb5, 0xda],
+        }
+    }
+
+    /// Numeric id of the network, used to bind the network into the folded state.
+    pub fn id(&self) -> u32 {
+        u32::from_le_bytes(self.message_start())
+    }
+
+    pub fn from_id(id: u32) -> Option<Network> {
+        [
+            Network::Mainnet,
+            Network::Testnet3,
+            Network::Testnet4,
+            Network::Signet,
+            Network::Regtest,
+        ]
+        .into_iter()
+        .find(|network| network.id() == id)
+    }
+}
+
+/// Consensus parameters of a Bitcoin network that are relevant for header validation.
+///
+/// All hashes and the pow limit are kept in internal (little-endian) byte order,
+/// the same as the fields of [`BitcoinHeader`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChainParams {
+    pub network: Network,
+    pub genesis: BitcoinHeader,
+    pub genesis_hash: Vec<u8>,
+    // the highest (easiest) target allowed on the network
+    pub pow_limit: Vec<u8>,
+    pub pow_limit_bits: u32,
+    pub pow_target_spacing: u32,
+    pub pow_target_timespan: u32,
+    // testnets allow a block to be mined at pow_limit if it is 2 * pow_target_spacing late
+    pub pow_allow_min_difficulty_blocks: bool,
+    pub pow_no_retargeting: bool,
+    // testnet4 retarget and time-warp fixes, ref: https://github.com/bitcoin/bips/blob/master/bip-0094.mediawiki
+    pub enforce_bip94: bool,
+    // script signet blocks must carry a solution for, empty on other networks
+    pub signet_challenge: Vec<u8>,
+}
+
+// Note: hashes are written in json-RPC (reversed) byte order, to be comparable with block explorers.
+fn from_rpc_hex(hex_str: &str) -> Vec<u8> {
+    let mut bytes = hex::decode(hex_str).expect("valid hex constant");
+    bytes.reverse();
+    bytes
+}
+
+const GENESIS_MERKLE_ROOT: &str =
+    "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+
+const MAINNET_POW_LIMIT: &str = "00000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffff";
+
+fn genesis_header(merkle_root: &str, timestamp: u32, bits: u32, nonce: u32) -> BitcoinHeader {
+    BitcoinHeader {
+        version: 1,
+        hash_prev_block: [0; 32].to_vec(),
+        hash_merkle_root: from_rpc_hex(merkle_root),
+        timestamp,
+        target_bits: bits.to_le_bytes().to_vec(),
+        nonce,
+    }
+}
+
+impl ChainParams {
+    pub fn mainnet() -> Self {
+        ChainParams {
+            network: Network::Mainnet,
+            genesis: genesis_header(GENESIS_MERKLE_ROOT, 1231006505, 0x1d00ffff, 2083236893),
+            genesis_hash: from_rpc_hex(
+                "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            ),
+            pow_limit: from_rpc_hex(MAINNET_POW_LIMIT),
+            pow_limit_bits: 0x1d00ffff,
+            pow_target_spacing: 10 * 60,
+            pow_target_timespan: 14 * 24 * 60 * 60,
+            pow_allow_min_difficulty_blocks: false,
+            pow_no_retargeting: false,
+            enforce_bip94: false,
+            signet_challenge: Vec::new(),
+        }
+    }
+
+    pub fn testnet3() -> Self {
+        ChainParams {
+            network: Network::Testnet3,
+            genesis: genesis_header(GENESIS_MERKLE_ROOT, 1296688602, 0x1d00ffff, 414098458),
+            genesis_hash: from_rpc_hex(
+                "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943",
+            ),
+            pow_allow_min_difficulty_blocks: true,
+            ..Self::mainnet()
+        }
+    }
+
+    pub fn testnet4() -> Self {
+        ChainParams {
+            network: Network::Testnet4,
+            genesis: genesis_header(
+                "7aa0a7ae1e223414cb807e40cd57e667b718e42aaf9306db9102fe28912b7b4e",
+                1714777860,
+                0x1d00ffff,
+                393743547,
+            ),
+            genesis_hash: from_rpc_hex(
+                "00000000da84f2bafbbc53dee25a72ae507ff4914b867c565be350b0da8bf043",
+            ),
+            pow_allow_min_difficulty_blocks: true,
+            enforce_bip94: true,
+            ..Self::mainnet()
+        }
+    }
+
+    /// Parameters of the default signet, i.e. signet with the default challenge script.
+    pub fn signet() -> Self {
+        ChainParams {
+            network: Network::Signet,
+            genesis: genesis_header(GENESIS_MERKLE_ROOT, 1598918400, 0x1e0377ae, 52613770),
+            genesis_hash: from_rpc_hex(
+                "00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6",
+            ),
+            pow_limit: from_rpc_hex(
+                "00000377ae000000000000000000000000000000000000000000000000000000",
+            ),
+            pow_limit_bits: 0x1e0377ae,
+            signet_challenge: hex::decode(DEFAULT_SIGNET_CHALLENGE).expect("valid hex constant"),
+            ..Self::mainnet()
+        }
+    }
+
+    pub fn regtest() -> Self {
+        ChainParams {
+            network: Network::Regtest,
+            genesis: genesis_header(GENESIS_MERKLE_ROOT, 1296688602, 0x207fffff, 2),
+            genesis_hash: from_rpc_hex(
+                "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+            ),
+            pow_limit: from_rpc_hex(
+                "7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+            ),
+            pow_limit_bits: 0x207fffff,
+            pow_allow_min_difficulty_blocks: true,
+            pow_no_retargeting: true,
+            ..Self::mainnet()
+        }
+    }
+
+    pub fn for_network(network: Network) -> Self {
+        match network {
+            Network::Mainnet => Self::mainnet(),
+            Network::Testnet3 => Self::testnet3(),
+            Network::Testnet4 => Self::testnet4(),
+            Network::Signet => Self::signet(),
+            Network::Regtest => Self::regtest(),
+        }
+    }
+
+    /// Number of blocks between two difficulty adjustments (2016 on all networks).
+    pub fn difficulty_adjustment_interval(&self) -> u32 {
+        self.pow_target_timespan / self.pow_target_spacing
+    }
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        Self::mainnet()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn genesis_hash_matches_genesis_header() {
+        for network in [
+            Network::Mainnet,
+            Network::Testnet3,
+            Network::Testnet4,
+            Network::Signet,
+            Network::Regtest,
+        ] {
+            let params = ChainParams::for_network(network);
+            assert_eq!(params.genesis.hash(), params.genesis_hash);
+            assert_eq!(params.genesis.bits(), params.pow_limit_bits);
+            assert_eq!(Network::from_id(network.id()), Some(network));
+        }
+    }
+
+    #[test]
+    fn difficulty_adjustment_interval() {
+        assert_eq!(
+            ChainParams::mainnet().difficulty_adjustment_interval(),
+            2016
+        );
+        assert_eq!(
+            ChainParams::regtest().difficulty_adjustment_interval(),
+            2016
+        );
+    }
+}
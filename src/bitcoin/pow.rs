@@ -0,0 +1,157 @@
+use num_bigint::BigUint;
+use thiserror::Error;
+
+use crate::bitcoin::ChainParams;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PowError {
+    #[error("compact target {0:#010x} is negative")]
+    Negative(u32),
+    #[error("compact target {0:#010x} overflows 256 bits")]
+    Overflow(u32),
+    #[error("compact target {0:#010x} is zero")]
+    Zero(u32),
+    #[error("compact target {0:#010x} is above the network pow limit")]
+    AbovePowLimit(u32),
+}
+
+/// Decodes a compact target (nBits) into the full 256-bit target.
+///
+/// ref code: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/arith_uint256.cpp#L203
+pub fn compact_to_target(bits: u32) -> Result<BigUint, PowError> {
+    let size = bits >> 24;
+    let word = bits & 0x007fffff;
+    let target = if size <= 3 {
+        BigUint::from(word >> (8 * (3 - size)))
+    } else {
+        BigUint::from(word) << (8 * (size - 3))
+    };
+
+    if word != 0 && (bits & 0x00800000) != 0 {
+        return Err(PowError::Negative(bits));
+    }
+    if word != 0 && (size > 34 || (word > 0xff && size > 33) || (word > 0xffff && size > 32)) {
+        return Err(PowError::Overflow(bits));
+    }
+    Ok(target)
+}
+
+/// Encodes a 256-bit target into its compact representation (nBits).
+pub fn target_to_compact(target: &BigUint) -> u32 {
+    let mut size = ((target.bits() + 7) / 8) as u32;
+    let mut compact = if size <= 3 {
+        let low = target.iter_u64_digits().next().unwrap_or(0) as u32;
+        low << (8 * (3 - size))
+    } else {
+        let shifted: BigUint = target >> (8 * (size - 3));
+        shifted.iter_u64_digits().next().unwrap_or(0) as u32
+    };
+    // the 0x00800000 bit denotes the sign, if it is already set divide the mantissa by 256
+    if compact & 0x00800000 != 0 {
+        compact >>= 8;
+        size += 1;
+    }
+    compact | (size << 24)
+}
+
+/// Target decoded from `bits` and checked against the network's pow limit.
+pub fn checked_target(bits: u32, params: &ChainParams) -> Result<BigUint, PowError> {
+    let target = compact_to_target(bits)?;
+    if target == BigUint::from(0u32) {
+        return Err(PowError::Zero(bits));
+    }
+    if target > pow_limit(params) {
+        return Err(PowError::AbovePowLimit(bits));
+    }
+    Ok(target)
+}
+
+pub fn pow_limit(params: &ChainParams) -> BigUint {
+    BigUint::from_bytes_le(&params.pow_limit)
+}
+
+/// Interprets a block hash in internal byte order as a 256-bit number.
+pub fn hash_to_uint(hash: &[u8]) -> BigUint {
+    BigUint::from_bytes_le(hash)
+}
+
+/// Expected number of hashes to mine a block with the given target, i.e. 2^256 / (target + 1).
+pub fn block_work(target: &BigUint) -> BigUint {
+    (BigUint::from(1u32) << 256) / (target + 1u32)
+}
+
+/// Compact target of the period following a retarget, given the compact target the
+/// retarget is based on and the timestamps of the first and the last block of the period.
+///
+/// ref code: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/pow.cpp#L49
+pub fn calculate_next_work_required(
+    bits: u32,
+    first_block_time: u32,
+    last_block_time: u32,
+    params: &ChainParams,
+) -> Result<u32, PowError> {
+    if params.pow_no_retargeting {
+        return Ok(bits);
+    }
+
+    // limit adjustment step
+    let timespan = params.pow_target_timespan as i64;
+    let actual_timespan =
+        (last_block_time as i64 - first_block_time as i64).clamp(timespan / 4, timespan * 4) as u32;
+
+    let limit = pow_limit(params);
+    let mut target = compact_to_target(bits)?;
+    target *= actual_timespan;
+    target /= params.pow_target_timespan;
+    if target > limit {
+        target = limit;
+    }
+
+    Ok(target_to_compact(&target))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compact_target_round_trip() {
+        for bits in [0x1d00ffffu32, 0x170362d3, 0x1e0377ae, 0x207fffff] {
+            let target = compact_to_target(bits).unwrap();
+            assert_eq!(target_to_compact(&target), bits);
+        }
+        assert_eq!(
+            compact_to_target(0x04923456),
+            Err(PowError::Negative(0x04923456))
+        );
+        assert_eq!(
+            compact_to_target(0xff123456),
+            Err(PowError::Overflow(0xff123456))
+        );
+    }
+
+    #[test]
+    fn pow_limit_matches_pow_limit_bits() {
+        let params = ChainParams::mainnet();
+        assert!(checked_target(params.pow_limit_bits, &params).is_ok());
+        assert_eq!(
+            checked_target(0x1d01ffff, &params),
+            Err(PowError::AbovePowLimit(0x1d01ffff))
+        );
+    }
+
+    #[test]
+    fn genesis_block_work() {
+        // chainwork of the mainnet genesis block is 0x100010001
+        let target = compact_to_target(0x1d00ffff).unwrap();
+        assert_eq!(block_work(&target), BigUint::from(0x100010001u64));
+    }
+
+    #[test]
+    fn retarget_mainnet_block_32256() {
+        // ref: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/test/pow_tests.cpp#L30
+        let params = ChainParams::mainnet();
+        let next = calculate_next_work_required(0x1d00ffff, 1261130161, 1262152739, &params);
+        assert_eq!(next, Ok(0x1d00d86a));
+    }
+}
@@ -0,0 +1,463 @@
+//! Signet block solutions.
+//!
+//! On signet a block is only valid if its coinbase carries a solution to the network's challenge
+//! script, which signs a commitment to the block that excludes the solution itself.
+//!
+//! ref: https://github.com/bitcoin/bips/blob/master/bip-0325.mediawiki
+//! ref code: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/signet.cpp
+
+use ark_std::{vec, vec::Vec};
+use k256::ecdsa::{signature::hazmat::PrehashVerifier, Signature, VerifyingKey};
+use thiserror::Error;
+
+use crate::bitcoin::tx::{
+    self, merkle_root_from_branch, write_var_bytes, OutPoint, Reader, Transaction, TxError, TxIn,
+    TxOut,
+};
+use crate::bitcoin::BitcoinHeader;
+
+/// Prefix of the push in the witness commitment output that carries the signet solution.
+pub const SIGNET_HEADER: [u8; 4] = [0xec, 0xc7, 0xda, 0xa2];
+
+/// The 1-of-2 multisig challenge of the default signet.
+pub const DEFAULT_SIGNET_CHALLENGE: &str = "512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d1e086be430210359ef5021964fe22d6f8e05b2463c9540ce96883fe3b278760f048f5189f2e6c452ae";
+
+// OP_RETURN OP_PUSHBYTES_36 0xaa21a9ed
+const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+const OP_0: u8 = 0x00;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+const OP_1: u8 = 0x51;
+const OP_16: u8 = 0x60;
+const OP_RETURN: u8 = 0x6a;
+const OP_CHECKMULTISIG: u8 = 0xae;
+
+const SIGHASH_ALL: u8 = 0x01;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SignetError {
+    #[error("invalid coinbase transaction: {0}")]
+    InvalidCoinbase(#[from] TxError),
+    #[error("first transaction of the block is not a coinbase")]
+    NotCoinbase,
+    #[error("coinbase does not commit to the merkle root of the header")]
+    MerkleRootMismatch,
+    #[error("coinbase has no witness commitment")]
+    MissingWitnessCommitment,
+    #[error("signet solution is malformed")]
+    MalformedSolution,
+    #[error("challenge script is not a bare multisig")]
+    UnsupportedChallenge,
+    #[error("unsupported sighash type {0:#04x}")]
+    UnsupportedSighashType(u8),
+    #[error("signet solution does not satisfy the challenge")]
+    InvalidSignature,
+}
+
+/// Parses a script into its opcodes and the data they push, `None` if a push is truncated.
+fn parse_script(script: &[u8]) -> Option<Vec<(u8, Vec<u8>)>> {
+    let mut reader = Reader::new(script);
+    let mut ops = Vec::new();
+    while !reader.is_empty() {
+        let opcode = reader.read_u8().ok()?;
+        let len = match opcode {
+            0x01..=0x4b => opcode as usize,
+            OP_PUSHDATA1 => reader.read_u8().ok()? as usize,
+            OP_PUSHDATA2 => reader.read_u16().ok()? as usize,
+            OP_PUSHDATA4 => reader.read_u32().ok()? as usize,
+            _ => 0,
+        };
+        ops.push((opcode, reader.read_bytes(len).ok()?.to_vec()));
+    }
+    Some(ops)
+}
+
+/// Appends the minimal push of `data` to `script`, the same as `CScript << data`.
+fn push_data(script: &mut Vec<u8>, data: &[u8]) {
+    match data.len() {
+        len if len < OP_PUSHDATA1 as usize => script.push(len as u8),
+        len if len <= 0xff => script.extend_from_slice(&[OP_PUSHDATA1, len as u8]),
+        len if len <= 0xffff => {
+            script.push(OP_PUSHDATA2);
+            script.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+        len => {
+            script.push(OP_PUSHDATA4);
+            script.extend_from_slice(&(len as u32).to_le_bytes());
+        }
+    }
+    script.extend_from_slice(data);
+}
+
+/// Index of the output carrying the witness commitment, the last one if there are several.
+pub fn witness_commitment_index(coinbase: &Transaction) -> Option<usize> {
+    coinbase.outputs.iter().rposition(|output| {
+        output.script_pubkey.len() >= 38 && output.script_pubkey[..6] == WITNESS_COMMITMENT_HEADER
+    })
+}
+
+/// Splits the signet solution from the witness commitment script.
+///
+/// Returns the script with the solution removed and the solution, or `None` if the script
+/// carries no solution.
+fn fetch_and_clear_commitment_section(script: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    let mut replacement = Vec::new();
+    let mut solution = None;
+    // a truncated push ends the script, as in CScript::GetOp
+    let ops = parse_script(script).unwrap_or_default();
+    for (opcode, mut data) in ops {
+        if data.is_empty() {
+            replacement.push(opcode);
+            continue;
+        }
+        // a push only counts if it has the header and some data
+        if solution.is_none()
+            && data.len() > SIGNET_HEADER.len()
+            && data.starts_with(&SIGNET_HEADER)
+        {
+            solution = Some(data.split_off(SIGNET_HEADER.len()));
+        }
+        push_data(&mut replacement, &data);
+    }
+    solution.map(|solution| (replacement, solution))
+}
+
+/// The virtual transactions that sign a signet block.
+///
+/// `to_spend` commits to the block without its signet solution in its scriptSig, and pays to the
+/// challenge script. `to_sign` spends it with the solution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignetTxs {
+    pub to_spend: Transaction,
+    pub to_sign: Transaction,
+}
+
+impl SignetTxs {
+    /// Builds the signet transactions of the block with `header`, given its coinbase and the
+    /// merkle branch of the coinbase.
+    pub fn new(
+        header: &BitcoinHeader,
+        coinbase: &Transaction,
+        merkle_branch: &[Vec<u8>],
+        challenge: &[u8],
+    ) -> Result<SignetTxs, SignetError> {
+        let mut modified_coinbase = coinbase.clone();
+        let index =
+            witness_commitment_index(coinbase).ok_or(SignetError::MissingWitnessCommitment)?;
+        let commitment = &mut modified_coinbase.outputs[index].script_pubkey;
+
+        let mut to_sign_input = TxIn::default();
+        // no solution is allowed, to support OP_TRUE as a trivial challenge
+        if let Some((replacement, solution)) = fetch_and_clear_commitment_section(commitment) {
+            *commitment = replacement;
+            let mut reader = Reader::new(&solution);
+            let solution_error = |_| SignetError::MalformedSolution;
+            to_sign_input.script_sig = reader.read_var_bytes().map_err(solution_error)?.to_vec();
+            let num_items = reader.read_compact_size().map_err(solution_error)?;
+            for _ in 0..num_items {
+                let item = reader.read_var_bytes().map_err(solution_error)?;
+                to_sign_input.witness.push(item.to_vec());
+            }
+            reader.finish().map_err(solution_error)?;
+        }
+
+        let signet_merkle_root =
+            merkle_root_from_branch(&modified_coinbase.txid(), 0, merkle_branch);
+        let mut block_data = Vec::with_capacity(72);
+        block_data.extend_from_slice(&header.version.to_le_bytes());
+        block_data.extend_from_slice(&header.hash_prev_block);
+        block_data.extend_from_slice(&signet_merkle_root);
+        block_data.extend_from_slice(&header.timestamp.to_le_bytes());
+
+        let mut to_spend_script_sig = vec![OP_0];
+        push_data(&mut to_spend_script_sig, &block_data);
+        let to_spend = Transaction {
+            version: 0,
+            inputs: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: to_spend_script_sig,
+                sequence: 0,
+                witness: Vec::new(),
+            }],
+            outputs: vec![TxOut {
+                value: 0,
+                script_pubkey: challenge.to_vec(),
+            }],
+            lock_time: 0,
+        };
+
+        to_sign_input.previous_output = OutPoint {
+            txid: to_spend.txid(),
+            vout: 0,
+        };
+        let to_sign = Transaction {
+            version: 0,
+            inputs: vec![to_sign_input],
+            outputs: vec![TxOut {
+                value: 0,
+                script_pubkey: vec![OP_RETURN],
+            }],
+            lock_time: 0,
+        };
+
+        Ok(SignetTxs { to_spend, to_sign })
+    }
+
+    /// Legacy SIGHASH_ALL signature hash of the `to_sign` transaction.
+    ///
+    /// ref code: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/script/interpreter.cpp#L1586
+    pub fn sighash_all(&self, script_code: &[u8]) -> Vec<u8> {
+        let mut tx = self.to_sign.clone();
+        tx.inputs[0].script_sig = script_code.to_vec();
+        tx.inputs[0].witness.clear();
+        let mut data = tx.to_bytes_without_witness();
+        data.extend_from_slice(&(SIGHASH_ALL as u32).to_le_bytes());
+        tx::sha256d(&data)
+    }
+}
+
+/// Public keys and threshold of a bare `OP_m <pubkey>... OP_n OP_CHECKMULTISIG` script.
+fn parse_multisig(challenge: &[u8]) -> Option<(usize, Vec<Vec<u8>>)> {
+    let ops = parse_script(challenge)?;
+    let small_int = |opcode: u8| match opcode {
+        OP_1..=OP_16 => Some((opcode - OP_1 + 1) as usize),
+        _ => None,
+    };
+    let (first, rest) = ops.split_first()?;
+    let (last, rest) = rest.split_last()?;
+    let (num_keys, keys) = rest.split_last()?;
+
+    let threshold = small_int(first.0)?;
+    if last.0 != OP_CHECKMULTISIG
+        || small_int(num_keys.0)? != keys.len()
+        || threshold > keys.len()
+        || keys.iter().any(|(_, key)| key.is_empty())
+    {
+        return None;
+    }
+    Some((threshold, keys.iter().map(|(_, key)| key.clone()).collect()))
+}
+
+fn check_signature(signature: &[u8], pubkey: &[u8], sighash: &[u8]) -> Result<bool, SignetError> {
+    let Some((hash_type, der)) = signature.split_last() else {
+        return Ok(false);
+    };
+    if *hash_type != SIGHASH_ALL {
+        return Err(SignetError::UnsupportedSighashType(*hash_type));
+    }
+    let (Ok(key), Ok(signature)) = (
+        VerifyingKey::from_sec1_bytes(pubkey),
+        Signature::from_der(der),
+    ) else {
+        return Ok(false);
+    };
+    // consensus accepts high-S signatures, which libsecp256k1 verifies after normalizing
+    let signature = signature.normalize_s().unwrap_or(signature);
+    Ok(key.verify_prehash(sighash, &signature).is_ok())
+}
+
+/// Verifies the coinbase commits to the header and carries a solution of the challenge.
+///
+/// Only bare multisig challenges, such as the default signet challenge, are supported. The
+/// solution must be the canonical `OP_0 <sig>...` scriptSig with SIGHASH_ALL signatures.
+pub fn verify_signet_solution(
+    header: &BitcoinHeader,
+    coinbase: &Transaction,
+    merkle_branch: &[Vec<u8>],
+    challenge: &[u8],
+) -> Result<(), SignetError> {
+    if !coinbase.is_coinbase() {
+        return Err(SignetError::NotCoinbase);
+    }
+    if merkle_root_from_branch(&coinbase.txid(), 0, merkle_branch) != header.hash_merkle_root {
+        return Err(SignetError::MerkleRootMismatch);
+    }
+
+    let (threshold, pubkeys) =
+        parse_multisig(challenge).ok_or(SignetError::UnsupportedChallenge)?;
+    let txs = SignetTxs::new(header, coinbase, merkle_branch, challenge)?;
+    let input = &txs.to_sign.inputs[0];
+    if !input.witness.is_empty() {
+        return Err(SignetError::InvalidSignature);
+    }
+
+    // the dummy element popped by OP_CHECKMULTISIG must be empty (BIP147)
+    let ops = parse_script(&input.script_sig).ok_or(SignetError::MalformedSolution)?;
+    let Some(((OP_0, _), signatures)) = ops.split_first() else {
+        return Err(SignetError::InvalidSignature);
+    };
+    if signatures.len() != threshold || signatures.iter().any(|(opcode, _)| *opcode > OP_PUSHDATA4)
+    {
+        return Err(SignetError::InvalidSignature);
+    }
+
+    // signatures must match the public keys in order
+    let sighash = txs.sighash_all(challenge);
+    let mut keys = pubkeys.iter();
+    for (_, signature) in signatures {
+        let mut is_matched = false;
+        for pubkey in keys.by_ref() {
+            if check_signature(signature, pubkey, &sighash)? {
+                is_matched = true;
+                break;
+            }
+        }
+        if !is_matched {
+            return Err(SignetError::InvalidSignature);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::bitcoin::{pow, ChainParams};
+    use ark_std::{vec, vec::Vec};
+    use k256::ecdsa::{signature::hazmat::PrehashSigner, SigningKey};
+
+    pub(crate) fn test_signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32].into()).unwrap()
+    }
+
+    /// 1-of-2 multisig challenge of the keys with seeds 1 and 2, like the default challenge.
+    pub(crate) fn test_challenge() -> Vec<u8> {
+        let mut challenge = vec![OP_1];
+        for seed in [1, 2] {
+            let pubkey = test_signing_key(seed)
+                .verifying_key()
+                .to_encoded_point(true);
+            push_data(&mut challenge, pubkey.as_bytes());
+        }
+        challenge.extend_from_slice(&[OP_1 + 1, OP_CHECKMULTISIG]);
+        challenge
+    }
+
+    /// Signet with the test challenge and a pow limit that makes mining instant.
+    pub(crate) fn test_signet_params() -> ChainParams {
+        let mut pow_limit = [0xff; 32].to_vec();
+        pow_limit[31] = 0x7f;
+        ChainParams {
+            signet_challenge: test_challenge(),
+            pow_limit,
+            pow_limit_bits: 0x207fffff,
+            ..ChainParams::signet()
+        }
+    }
+
+    /// Mines a block on top of `prev` whose coinbase is signed with `key`.
+    ///
+    /// Returns the header, the coinbase and the merkle branch of the coinbase.
+    pub(crate) fn signed_test_block(
+        params: &ChainParams,
+        prev: &BitcoinHeader,
+        key: &SigningKey,
+    ) -> (BitcoinHeader, Transaction, Vec<Vec<u8>>) {
+        let mut commitment = WITNESS_COMMITMENT_HEADER.to_vec();
+        commitment.extend_from_slice(&[0x5a; 32]);
+        // the signed coinbase only carries the header of the signet solution
+        let mut unsigned_commitment = commitment.clone();
+        push_data(&mut unsigned_commitment, &SIGNET_HEADER);
+        let mut coinbase = Transaction {
+            version: 2,
+            inputs: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: vec![0x03, 0x10, 0x27, 0x00],
+                sequence: u32::MAX,
+                witness: vec![[0; 32].to_vec()],
+            }],
+            outputs: vec![
+                TxOut {
+                    value: 50 * 100_000_000,
+                    script_pubkey: vec![OP_1],
+                },
+                TxOut {
+                    value: 0,
+                    script_pubkey: unsigned_commitment,
+                },
+            ],
+            lock_time: 0,
+        };
+        // the coinbase is the left leaf of a block with two transactions
+        let merkle_branch = vec![[0x11; 32].to_vec()];
+        let mut header = BitcoinHeader {
+            version: 0x20000000,
+            hash_prev_block: prev.hash(),
+            hash_merkle_root: [0; 32].to_vec(),
+            timestamp: prev.timestamp + params.pow_target_spacing,
+            target_bits: params.pow_limit_bits.to_le_bytes().to_vec(),
+            nonce: 0,
+        };
+
+        let txs =
+            SignetTxs::new(&header, &coinbase, &merkle_branch, &params.signet_challenge).unwrap();
+        let signature: Signature = key
+            .sign_prehash(&txs.sighash_all(&params.signet_challenge))
+            .unwrap();
+        let mut signature = signature.to_der().as_bytes().to_vec();
+        signature.push(SIGHASH_ALL);
+
+        let mut script_sig = vec![OP_0];
+        push_data(&mut script_sig, &signature);
+        let mut solution = SIGNET_HEADER.to_vec();
+        write_var_bytes(&mut solution, &script_sig);
+        // empty witness stack
+        solution.push(0);
+        push_data(&mut commitment, &solution);
+        coinbase.outputs[1].script_pubkey = commitment;
+
+        header.hash_merkle_root = merkle_root_from_branch(&coinbase.txid(), 0, &merkle_branch);
+        let target = pow::compact_to_target(params.pow_limit_bits).unwrap();
+        while pow::hash_to_uint(&header.hash()) > target {
+            header.nonce += 1;
+        }
+        (header, coinbase, merkle_branch)
+    }
+
+    #[test]
+    fn default_challenge_is_one_of_two_multisig() {
+        let challenge = hex::decode(DEFAULT_SIGNET_CHALLENGE).unwrap();
+        let (threshold, pubkeys) = parse_multisig(&challenge).unwrap();
+        assert_eq!(threshold, 1);
+        assert_eq!(pubkeys.len(), 2);
+        assert_eq!(ChainParams::signet().signet_challenge, challenge);
+    }
+
+    #[test]
+    fn verify_signed_block() {
+        let params = test_signet_params();
+        for seed in [1, 2] {
+            let (header, coinbase, branch) =
+                signed_test_block(&params, &params.genesis, &test_signing_key(seed));
+            assert_eq!(
+                verify_signet_solution(&header, &coinbase, &branch, &params.signet_challenge),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn reject_block_signed_by_unknown_key() {
+        let params = test_signet_params();
+        let (header, coinbase, branch) =
+            signed_test_block(&params, &params.genesis, &test_signing_key(3));
+        assert_eq!(
+            verify_signet_solution(&header, &coinbase, &branch, &params.signet_challenge),
+            Err(SignetError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn reject_coinbase_not_in_block() {
+        let params = test_signet_params();
+        let (header, coinbase, mut branch) =
+            signed_test_block(&params, &params.genesis, &test_signing_key(1));
+        branch[0][0] ^= 1;
+        assert_eq!(
+            verify_signet_solution(&header, &coinbase, &branch, &params.signet_challenge),
+            Err(SignetError::MerkleRootMismatch)
+        );
+    }
+}
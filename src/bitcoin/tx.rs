@@ -0,0 +1,534 @@
+//! Bitcoin transactions and the native SPV verification of their inclusion in a block.
+
+use ark_crypto_primitives::crh::sha256::{digest::Digest, Sha256};
+use ark_std::vec::Vec;
+use thiserror::Error;
+
+use crate::bitcoin::BitcoinHeader;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TxError {
+    #[error("unexpected end of transaction data")]
+    UnexpectedEnd,
+    #[error("transaction has {0} trailing bytes")]
+    TrailingData(usize),
+    #[error("unsupported segwit flag {0:#04x}")]
+    InvalidSegwitFlag(u8),
+    #[error("segwit transaction has no witness data")]
+    EmptyWitness,
+    #[error("compact size {0} is not minimally encoded")]
+    NonCanonicalCompactSize(u64),
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SpvError {
+    #[error("invalid transaction: {0}")]
+    InvalidTransaction(#[from] TxError),
+    #[error("transaction of 64 bytes could be an inner node of the merkle tree")]
+    AmbiguousTransaction,
+    #[error("merkle branch hashes a node with itself as its left sibling (CVE-2012-2459)")]
+    DuplicateNode,
+    #[error("index {0} is out of range of a merkle branch of depth {1}")]
+    IndexOutOfRange(u32, usize),
+    #[error("merkle branch does not lead to the merkle root of the header")]
+    MerkleRootMismatch,
+}
+
+pub fn sha256d(data: &[u8]) -> Vec<u8> {
+    Sha256::digest(Sha256::digest(data)).to_vec()
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OutPoint {
+    // txid in internal byte order
+    pub txid: Vec<u8>,
+    pub vout: u32,
+}
+
+impl OutPoint {
+    /// The outpoint spent by coinbase transactions.
+    pub fn null() -> Self {
+        OutPoint {
+            txid: [0; 32].to_vec(),
+            vout: u32::MAX,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TxIn {
+    pub previous_output: OutPoint,
+    pub script_sig: Vec<u8>,
+    pub sequence: u32,
+    pub witness: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TxOut {
+    pub value: u64,
+    pub script_pubkey: Vec<u8>,
+}
+
+/// Bitcoin transaction
+///
+/// ref code: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/primitives/transaction.h#L295
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Transaction {
+    pub version: i32,
+    pub inputs: Vec<TxIn>,
+    pub outputs: Vec<TxOut>,
+    pub lock_time: u32,
+}
+
+impl Transaction {
+    /// Parses a raw transaction in legacy or segwit (BIP144) serialization.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Transaction, TxError> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.read_u32()? as i32;
+
+        let mut num_inputs = reader.read_compact_size()?;
+        let mut has_witness = false;
+        if num_inputs == 0 {
+            // an empty input list is the segwit marker, followed by the flag
+            let flag = reader.read_u8()?;
+            if flag != 1 {
+                return Err(TxError::InvalidSegwitFlag(flag));
+            }
+            has_witness = true;
+            num_inputs = reader.read_compact_size()?;
+        }
+
+        let mut inputs = Vec::new();
+        for _ in 0..num_inputs {
+            let txid = reader.read_bytes(32)?.to_vec();
+            let vout = reader.read_u32()?;
+            let script_sig = reader.read_var_bytes()?.to_vec();
+            let sequence = reader.read_u32()?;
+            inputs.push(TxIn {
+                previous_output: OutPoint { txid, vout },
+                script_sig,
+                sequence,
+                witness: Vec::new(),
+            });
+        }
+
+        let num_outputs = reader.read_compact_size()?;
+        let mut outputs = Vec::new();
+        for _ in 0..num_outputs {
+            let value = reader.read_u64()?;
+            let script_pubkey = reader.read_var_bytes()?.to_vec();
+            outputs.push(TxOut {
+                value,
+                script_pubkey,
+            });
+        }
+
+        if has_witness {
+            for input in inputs.iter_mut() {
+                let num_items = reader.read_compact_size()?;
+                for _ in 0..num_items {
+                    input.witness.push(reader.read_var_bytes()?.to_vec());
+                }
+            }
+            if inputs.iter().all(|input| input.witness.is_empty()) {
+                return Err(TxError::EmptyWitness);
+            }
+        }
+
+        let lock_time = reader.read_u32()?;
+        reader.finish()?;
+
+        Ok(Transaction {
+            version,
+            inputs,
+            outputs,
+            lock_time,
+        })
+    }
+
+    pub fn has_witness(&self) -> bool {
+        self.inputs.iter().any(|input| !input.witness.is_empty())
+    }
+
+    /// Serialization used to compute the txid, i.e. without witness data.
+    pub fn to_bytes_without_witness(&self) -> Vec<u8> {
+        self.serialize(false)
+    }
+
+    /// Serialization including witness data, if the transaction has any.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.serialize(self.has_witness())
+    }
+
+    fn serialize(&self, with_witness: bool) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.extend_from_slice(&self.version.to_le_bytes());
+        if with_witness {
+            result.extend_from_slice(&[0x00, 0x01]);
+        }
+        write_compact_size(&mut result, self.inputs.len() as u64);
+        for input in &self.inputs {
+            result.extend_from_slice(&input.previous_output.txid);
+            result.extend_from_slice(&input.previous_output.vout.to_le_bytes());
+            write_var_bytes(&mut result, &input.script_sig);
+            result.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+        write_compact_size(&mut result, self.outputs.len() as u64);
+        for output in &self.outputs {
+            result.extend_from_slice(&output.value.to_le_bytes());
+            write_var_bytes(&mut result, &output.script_pubkey);
+        }
+        if with_witness {
+            for input in &self.inputs {
+                write_compact_size(&mut result, input.witness.len() as u64);
+                for item in &input.witness {
+                    write_var_bytes(&mut result, item);
+                }
+            }
+        }
+        result.extend_from_slice(&self.lock_time.to_le_bytes());
+        result
+    }
+
+    /// Transaction id in internal byte order.
+    pub fn txid(&self) -> Vec<u8> {
+        sha256d(&self.to_bytes_without_witness())
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        self.inputs.len() == 1 && self.inputs[0].previous_output == OutPoint::null()
+    }
+}
+
+/// Computes the merkle root from a leaf at `index` and the hashes of its siblings, bottom up.
+pub fn merkle_root_from_branch(leaf: &[u8], index: u32, branch: &[Vec<u8>]) -> Vec<u8> {
+    let mut node = leaf.to_vec();
+    for (level, sibling) in branch.iter().enumerate() {
+        let mut data = Vec::with_capacity(64);
+        if (index >> level) & 1 == 1 {
+            data.extend_from_slice(sibling);
+            data.extend_from_slice(&node);
+        } else {
+            data.extend_from_slice(&node);
+            data.extend_from_slice(sibling);
+        }
+        node = sha256d(&data);
+    }
+    node
+}
+
+/// Checks `leaf` is at `index` of the merkle tree of `root`, given the hashes of its siblings.
+///
+/// Bitcoin duplicates the last node of levels of odd length, so a node equal to its left sibling
+/// is a duplicated node rather than a leaf of the block, and is rejected (CVE-2012-2459).
+///
+/// ref: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/consensus/merkle.cpp#L9
+pub fn verify_merkle_branch(
+    leaf: &[u8],
+    index: u32,
+    branch: &[Vec<u8>],
+    root: &[u8],
+) -> Result<(), SpvError> {
+    if branch.len() < 32 && index >> branch.len() != 0 {
+        return Err(SpvError::IndexOutOfRange(index, branch.len()));
+    }
+    let mut node = leaf.to_vec();
+    for (level, sibling) in branch.iter().enumerate() {
+        let is_right = (index >> level) & 1 == 1;
+        if is_right && *sibling == node {
+            return Err(SpvError::DuplicateNode);
+        }
+        node = merkle_root_from_branch(&node, is_right as u32, core::slice::from_ref(sibling));
+    }
+    if node != root {
+        return Err(SpvError::MerkleRootMismatch);
+    }
+    Ok(())
+}
+
+/// Checks the raw transaction `raw_tx` is the transaction at `index` of the block of `header`,
+/// given the merkle branch of its txid, and returns the parsed transaction.
+///
+/// Transactions of 64 bytes without witness are rejected, since they could be the concatenation
+/// of two inner nodes of the merkle tree.
+pub fn verify_tx_inclusion(
+    raw_tx: &[u8],
+    index: u32,
+    merkle_branch: &[Vec<u8>],
+    header: &BitcoinHeader,
+) -> Result<Transaction, SpvError> {
+    let tx = Transaction::from_bytes(raw_tx)?;
+    if tx.to_bytes_without_witness().len() == 64 {
+        return Err(SpvError::AmbiguousTransaction);
+    }
+    verify_merkle_branch(&tx.txid(), index, merkle_branch, &header.hash_merkle_root)?;
+    Ok(tx)
+}
+
+/// Merkle root of the txids of a block, in internal byte order.
+pub fn merkle_root(txids: &[Vec<u8>]) -> Vec<u8> {
+    let mut level = txids.to_vec();
+    while level.len() > 1 {
+        level = merkle_parents(&level);
+    }
+    level.pop().unwrap_or_else(|| [0; 32].to_vec())
+}
+
+/// Merkle branch of the txid at `index` of the txids of a block.
+pub fn merkle_branch(txids: &[Vec<u8>], index: usize) -> Vec<Vec<u8>> {
+    let mut branch = Vec::new();
+    let mut level = txids.to_vec();
+    let mut index = index;
+    while level.len() > 1 {
+        // the last node of a level of odd length is its own sibling
+        let sibling = (index ^ 1).min(level.len() - 1);
+        branch.push(level[sibling].clone());
+        level = merkle_parents(&level);
+        index >>= 1;
+    }
+    branch
+}
+
+fn merkle_parents(level: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    level
+        .chunks(2)
+        .map(|pair| {
+            let right = pair.last().expect("non-empty chunk");
+            sha256d(&[pair[0].as_slice(), right.as_slice()].concat())
+        })
+        .collect()
+}
+
+pub(crate) fn write_compact_size(result: &mut Vec<u8>, size: u64) {
+    match size {
+        0..=0xfc => result.push(size as u8),
+        0xfd..=0xffff => {
+            result.push(0xfd);
+            result.extend_from_slice(&(size as u16).to_le_bytes());
+        }
+        0x10000..=0xffffffff => {
+            result.push(0xfe);
+            result.extend_from_slice(&(size as u32).to_le_bytes());
+        }
+        _ => {
+            result.push(0xff);
+            result.extend_from_slice(&size.to_le_bytes());
+        }
+    }
+}
+
+pub(crate) fn write_var_bytes(result: &mut Vec<u8>, bytes: &[u8]) {
+    write_compact_size(result, bytes.len() as u64);
+    result.extend_from_slice(bytes);
+}
+
+/// Cursor over consensus serialized data.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data, position: 0 }
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], TxError> {
+        if self.data.len() - self.position < len {
+            return Err(TxError::UnexpectedEnd);
+        }
+        let bytes = &self.data[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, TxError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, TxError> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, TxError> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.read_bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, TxError> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.read_bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn read_compact_size(&mut self) -> Result<u64, TxError> {
+        let (size, min) = match self.read_u8()? {
+            0xfd => (self.read_u16()? as u64, 0xfd),
+            0xfe => (self.read_u32()? as u64, 0x10000),
+            0xff => (self.read_u64()?, 0x100000000),
+            size => (size as u64, 0),
+        };
+        if size < min {
+            return Err(TxError::NonCanonicalCompactSize(size));
+        }
+        Ok(size)
+    }
+
+    pub fn read_var_bytes(&mut self) -> Result<&'a [u8], TxError> {
+        let len = self.read_compact_size()?;
+        if len > (self.data.len() - self.position) as u64 {
+            return Err(TxError::UnexpectedEnd);
+        }
+        self.read_bytes(len as usize)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == self.data.len()
+    }
+
+    pub fn finish(&self) -> Result<(), TxError> {
+        if !self.is_empty() {
+            return Err(TxError::TrailingData(self.data.len() - self.position));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // coinbase of the mainnet genesis block
+    const GENESIS_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+
+    #[test]
+    fn genesis_coinbase_txid_is_genesis_merkle_root() {
+        let tx = Transaction::from_bytes(&hex::decode(GENESIS_COINBASE).unwrap()).unwrap();
+        assert!(tx.is_coinbase());
+        assert!(!tx.has_witness());
+        assert_eq!(tx.outputs[0].value, 50 * 100_000_000);
+        assert_eq!(tx.to_bytes(), hex::decode(GENESIS_COINBASE).unwrap());
+
+        let mut merkle_root =
+            hex::decode("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b")
+                .unwrap();
+        merkle_root.reverse();
+        assert_eq!(tx.txid(), merkle_root);
+        assert_eq!(merkle_root_from_branch(&tx.txid(), 0, &[]), merkle_root);
+    }
+
+    #[test]
+    fn segwit_round_trip() {
+        let mut tx = Transaction::from_bytes(&hex::decode(GENESIS_COINBASE).unwrap()).unwrap();
+        let txid = tx.txid();
+        tx.inputs[0].witness = vec![[0; 32].to_vec()];
+
+        let bytes = tx.to_bytes();
+        assert_eq!(&bytes[4..6], &[0x00, 0x01]);
+        assert_eq!(Transaction::from_bytes(&bytes), Ok(tx.clone()));
+        // the witness is not part of the txid
+        assert_eq!(tx.txid(), txid);
+    }
+
+    // block of `num_txs` transactions derived from the genesis coinbase
+    fn test_block(num_txs: u32) -> (BitcoinHeader, Vec<Vec<u8>>) {
+        let coinbase = Transaction::from_bytes(&hex::decode(GENESIS_COINBASE).unwrap()).unwrap();
+        let raw_txs: Vec<Vec<u8>> = (0..num_txs)
+            .map(|lock_time| {
+                Transaction {
+                    lock_time,
+                    ..coinbase.clone()
+                }
+                .to_bytes()
+            })
+            .collect();
+        let txids: Vec<Vec<u8>> = raw_txs.iter().map(|raw_tx| sha256d(raw_tx)).collect();
+        let header = BitcoinHeader {
+            hash_merkle_root: merkle_root(&txids),
+            ..BitcoinHeader::default()
+        };
+        (header, raw_txs)
+    }
+
+    #[test]
+    fn verify_inclusion_of_every_transaction() {
+        for num_txs in 1..8 {
+            let (header, raw_txs) = test_block(num_txs);
+            let txids: Vec<Vec<u8>> = raw_txs.iter().map(|raw_tx| sha256d(raw_tx)).collect();
+            for (index, raw_tx) in raw_txs.iter().enumerate() {
+                let branch = merkle_branch(&txids, index);
+                let tx = verify_tx_inclusion(raw_tx, index as u32, &branch, &header).unwrap();
+                assert_eq!(tx.lock_time, index as u32);
+            }
+        }
+    }
+
+    #[test]
+    fn reject_duplicated_leaf() {
+        // the third transaction is duplicated to fill the first level
+        let (header, raw_txs) = test_block(3);
+        let txids: Vec<Vec<u8>> = raw_txs.iter().map(|raw_tx| sha256d(raw_tx)).collect();
+        let branch = merkle_branch(&txids, 2);
+        assert_eq!(branch[0], txids[2]);
+        assert!(verify_tx_inclusion(&raw_txs[2], 2, &branch, &header).is_ok());
+
+        // the duplicate hashes to the same root, but it is not a transaction of the block
+        assert_eq!(
+            verify_tx_inclusion(&raw_txs[2], 3, &branch, &header),
+            Err(SpvError::DuplicateNode)
+        );
+        assert_eq!(
+            verify_tx_inclusion(&raw_txs[2], 6, &branch, &header),
+            Err(SpvError::IndexOutOfRange(6, 2))
+        );
+        assert_eq!(
+            verify_tx_inclusion(&raw_txs[1], 2, &branch, &header),
+            Err(SpvError::MerkleRootMismatch)
+        );
+    }
+
+    #[test]
+    fn reject_64_byte_transaction() {
+        // smallest transaction: one input with empty script and one output with a 4 byte script
+        let tx = Transaction {
+            version: 1,
+            inputs: vec![TxIn {
+                previous_output: OutPoint::null(),
+                ..TxIn::default()
+            }],
+            outputs: vec![TxOut {
+                value: 0,
+                script_pubkey: vec![0x51; 4],
+            }],
+            lock_time: 0,
+        };
+        let raw_tx = tx.to_bytes();
+        assert_eq!(raw_tx.len(), 64);
+
+        let header = BitcoinHeader {
+            hash_merkle_root: tx.txid(),
+            ..BitcoinHeader::default()
+        };
+        assert_eq!(
+            verify_tx_inclusion(&raw_tx, 0, &[], &header),
+            Err(SpvError::AmbiguousTransaction)
+        );
+    }
+
+    #[test]
+    fn reject_malformed_transactions() {
+        let bytes = hex::decode(GENESIS_COINBASE).unwrap();
+        assert_eq!(
+            Transaction::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(TxError::UnexpectedEnd)
+        );
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Transaction::from_bytes(&trailing),
+            Err(TxError::TrailingData(1))
+        );
+    }
+}
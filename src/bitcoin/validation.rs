@@ -0,0 +1,381 @@
+use ark_std::vec::Vec;
+use thiserror::Error;
+
+use crate::bitcoin::pow::{self, PowError};
+use crate::bitcoin::signet::{self, SignetError};
+use crate::bitcoin::tx::Transaction;
+use crate::bitcoin::{BitcoinHeader, ChainParams, Network};
+
+// number of blocks the median time past is taken over
+pub(crate) const MEDIAN_TIME_SPAN: usize = 11;
+// seconds the first block of a period may be earlier than its predecessor under BIP94
+pub(crate) const MAX_TIMEWARP: u32 = 600;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("header at height {0} does not follow its predecessor's height")]
+    NonConsecutiveHeight(u32),
+    #[error("header at height {0} does not link to the hash of its predecessor")]
+    BrokenLink(u32),
+    #[error("header at height {height} has an invalid target: {source}")]
+    InvalidTarget { height: u32, source: PowError },
+    #[error("hash of header at height {0} is above its target")]
+    InsufficientWork(u32),
+    #[error("header at height {height} has bits {actual:#010x}, expected {expected:#010x}")]
+    UnexpectedBits {
+        height: u32,
+        expected: u32,
+        actual: u32,
+    },
+    #[error("timestamp of header at height {0} is not after the median time past")]
+    TimeTooOld(u32),
+    #[error("timestamp of header at height {0} is more than 600 seconds before its predecessor")]
+    TimeWarp(u32),
+    #[error("header at height {0} needs ancestors that are not part of the validated headers")]
+    MissingAncestor(u32),
+    #[error("header at height {height} has an invalid signet solution: {source}")]
+    InvalidSignetSolution { height: u32, source: SignetError },
+}
+
+/// Native validator of the header chain consensus rules enforced by bitfold.
+pub struct HeaderValidator {
+    params: ChainParams,
+}
+
+impl HeaderValidator {
+    pub fn new(params: ChainParams) -> Self {
+        HeaderValidator { params }
+    }
+
+    pub fn params(&self) -> &ChainParams {
+        &self.params
+    }
+
+    /// Checks the block hash is not above the target encoded in the header.
+    pub fn check_proof_of_work(
+        &self,
+        height: u32,
+        header: &BitcoinHeader,
+    ) -> Result<(), ValidationError> {
+        let target = pow::checked_target(header.bits(), &self.params)
+            .map_err(|source| ValidationError::InvalidTarget { height, source })?;
+        if pow::hash_to_uint(&header.hash()) > target {
+            return Err(ValidationError::InsufficientWork(height));
+        }
+        Ok(())
+    }
+
+    /// Checks the coinbase of a signet block commits to the header and solves the signet
+    /// challenge, does nothing on other networks.
+    pub fn check_signet_solution(
+        &self,
+        height: u32,
+        header: &BitcoinHeader,
+        coinbase: &Transaction,
+        merkle_branch: &[Vec<u8>],
+    ) -> Result<(), ValidationError> {
+        // the genesis block has no solution
+        if self.params.network != Network::Signet || header.hash() == self.params.genesis_hash {
+            return Ok(());
+        }
+        signet::verify_signet_solution(
+            header,
+            coinbase,
+            merkle_branch,
+            &self.params.signet_challenge,
+        )
+        .map_err(|source| ValidationError::InvalidSignetSolution { height, source })
+    }
+
+    /// Checks the timestamp of `header` is after the median time past of its ancestors, sorted by
+    /// height. Only the last 11 ancestors are used.
+    ///
+    /// ref code: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/validation.cpp#L4137
+    pub fn check_median_time_past(
+        &self,
+        ancestors: &[(u32, BitcoinHeader)],
+        header: &BitcoinHeader,
+    ) -> Result<(), ValidationError> {
+        let (last_height, _) = ancestors.last().expect("at least one ancestor");
+        if header.timestamp <= median_time_past(ancestors) {
+            return Err(ValidationError::TimeTooOld(last_height + 1));
+        }
+        Ok(())
+    }
+
+    /// Checks the first block of a difficulty adjustment period is not more than 10 minutes
+    /// earlier than its predecessor, on networks enforcing BIP94, which prevents the timewarp
+    /// attack of lowering the difficulty by backdating the start of periods.
+    ///
+    /// ref: https://github.com/bitcoin/bips/blob/master/bip-0094.mediawiki
+    pub fn check_timewarp(
+        &self,
+        height: u32,
+        prev_header: &BitcoinHeader,
+        header: &BitcoinHeader,
+    ) -> Result<(), ValidationError> {
+        let interval = self.params.difficulty_adjustment_interval();
+        if self.params.enforce_bip94
+            && height.is_multiple_of(interval)
+            && u64::from(header.timestamp) + u64::from(MAX_TIMEWARP)
+                < u64::from(prev_header.timestamp)
+        {
+            return Err(ValidationError::TimeWarp(height));
+        }
+        Ok(())
+    }
+
+    /// Validates a sorted run of consecutive headers.
+    ///
+    /// The first header is used as trusted context: only its proof of work is checked, every
+    /// following header is checked to link to its predecessor, to meet its target, to carry
+    /// the bits required by the difficulty adjustment rules of the network, to not warp the time
+    /// at the start of a period and, once enough ancestors are part of the run, to be later than
+    /// the median time past.
+    pub fn validate_headers(
+        &self,
+        headers: &[(u32, BitcoinHeader)],
+    ) -> Result<(), ValidationError> {
+        if let Some((height, header)) = headers.first() {
+            self.check_proof_of_work(*height, header)?;
+        }
+
+        for i in 1..headers.len() {
+            let (prev_height, prev_header) = &headers[i - 1];
+            let (height, header) = &headers[i];
+            if *height != prev_height + 1 {
+                return Err(ValidationError::NonConsecutiveHeight(*height));
+            }
+            if header.hash_prev_block != prev_header.hash() {
+                return Err(ValidationError::BrokenLink(*height));
+            }
+            self.check_proof_of_work(*height, header)?;
+            // the median time past of the first blocks of the chain is taken over fewer blocks
+            if i >= MEDIAN_TIME_SPAN || headers[0].0 == 0 {
+                self.check_median_time_past(&headers[..i], header)?;
+            }
+            self.check_timewarp(*height, prev_header, header)?;
+
+            let expected = self.next_work_required(&headers[..i], header)?;
+            if header.bits() != expected {
+                return Err(ValidationError::UnexpectedBits {
+                    height: *height,
+                    expected,
+                    actual: header.bits(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Compact target required for `header`, given its ancestors sorted by height.
+    ///
+    /// ref code: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/pow.cpp#L14
+    pub fn next_work_required(
+        &self,
+        ancestors: &[(u32, BitcoinHeader)],
+        header: &BitcoinHeader,
+    ) -> Result<u32, ValidationError> {
+        let (last_height, last_header) = ancestors.last().expect("at least one ancestor");
+        let height = last_height + 1;
+        let interval = self.params.difficulty_adjustment_interval();
+
+        // only change once per difficulty adjustment interval
+        if !height.is_multiple_of(interval) {
+            if self.params.pow_allow_min_difficulty_blocks {
+                // a block may be mined at min-difficulty if it is more than 2 * 10 minutes late
+                let late_after = u64::from(last_header.timestamp)
+                    + u64::from(self.params.pow_target_spacing) * 2;
+                if u64::from(header.timestamp) > late_after {
+                    return Ok(self.params.pow_limit_bits);
+                }
+                // otherwise use the bits of the last block that was not mined at min-difficulty,
+                // which has to be part of the ancestors
+                let (_, last_normal) = ancestors
+                    .iter()
+                    .rev()
+                    .find(|(height, header)| {
+                        height.is_multiple_of(interval)
+                            || header.bits() != self.params.pow_limit_bits
+                    })
+                    .ok_or(ValidationError::MissingAncestor(height))?;
+                return Ok(last_normal.bits());
+            }
+            return Ok(last_header.bits());
+        }
+
+        if self.params.pow_no_retargeting {
+            return Ok(last_header.bits());
+        }
+
+        // go back by what we want to be 14 days worth of blocks
+        let first_height = height - interval;
+        let (_, first_header) = ancestors
+            .iter()
+            .find(|(height, _)| *height == first_height)
+            .ok_or(ValidationError::MissingAncestor(height))?;
+
+        // BIP94 retargets from the first block of the period, to not inherit a min-difficulty
+        let bits = if self.params.enforce_bip94 {
+            first_header.bits()
+        } else {
+            last_header.bits()
+        };
+        pow::calculate_next_work_required(
+            bits,
+            first_header.timestamp,
+            last_header.timestamp,
+            &self.params,
+        )
+        .map_err(|source| ValidationError::InvalidTarget { height, source })
+    }
+}
+
+/// Median timestamp of the last 11 blocks, or of all blocks if there are fewer.
+pub fn median_time_past(ancestors: &[(u32, BitcoinHeader)]) -> u32 {
+    let start = ancestors.len().saturating_sub(MEDIAN_TIME_SPAN);
+    let mut timestamps: Vec<u32> = ancestors[start..]
+        .iter()
+        .map(|(_, header)| header.timestamp)
+        .collect();
+    timestamps.sort_unstable();
+    timestamps[timestamps.len() / 2]
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::bitcoin::data::test_json::{
+        min_difficulty_test_params, TEST_JSON_RPC, TEST_JSON_RPC_GENESIS,
+        TEST_JSON_RPC_MIN_DIFFICULTY,
+    };
+    use crate::bitcoin::signet::test::{signed_test_block, test_signet_params, test_signing_key};
+    use crate::bitcoin::BlockReader;
+
+    #[test]
+    fn validate_mainnet_headers() {
+        let reader = BlockReader::new_from_json(TEST_JSON_RPC).unwrap();
+        let headers = reader.get_block_headers().unwrap();
+        let validator = HeaderValidator::new(reader.params().clone());
+        assert_eq!(validator.validate_headers(&headers), Ok(()));
+    }
+
+    #[test]
+    fn reject_broken_link() {
+        let reader = BlockReader::new_from_json(TEST_JSON_RPC).unwrap();
+        let mut headers = reader.get_block_headers().unwrap();
+        headers[2].1.hash_prev_block[0] ^= 1;
+        let validator = HeaderValidator::new(reader.params().clone());
+        assert_eq!(
+            validator.validate_headers(&headers),
+            Err(ValidationError::BrokenLink(headers[2].0))
+        );
+    }
+
+    #[test]
+    fn check_signet_solution() {
+        let params = test_signet_params();
+        let (header, coinbase, branch) =
+            signed_test_block(&params, &params.genesis, &test_signing_key(1));
+        let validator = HeaderValidator::new(params);
+        assert_eq!(
+            validator.check_signet_solution(1, &header, &coinbase, &branch),
+            Ok(())
+        );
+
+        let (header, coinbase, branch) =
+            signed_test_block(validator.params(), &header, &test_signing_key(3));
+        assert_eq!(
+            validator.check_signet_solution(2, &header, &coinbase, &branch),
+            Err(ValidationError::InvalidSignetSolution {
+                height: 2,
+                source: SignetError::InvalidSignature
+            })
+        );
+    }
+
+    #[test]
+    fn reject_time_too_old() {
+        let reader = BlockReader::new_from_json(TEST_JSON_RPC_GENESIS).unwrap();
+        let headers = reader.get_block_headers().unwrap();
+        let validator = HeaderValidator::new(reader.params().clone());
+        assert_eq!(validator.validate_headers(&headers), Ok(()));
+
+        // the median of the first two blocks is the later one
+        let mut header = headers[2].1.clone();
+        header.timestamp = headers[1].1.timestamp;
+        assert_eq!(
+            validator.check_median_time_past(&headers[..2], &header),
+            Err(ValidationError::TimeTooOld(2))
+        );
+        header.timestamp += 1;
+        assert_eq!(
+            validator.check_median_time_past(&headers[..2], &header),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn reject_time_warp() {
+        let reader = BlockReader::new_from_json(TEST_JSON_RPC).unwrap();
+        let prev = reader.get_block_header(838637).unwrap();
+        let mut header = reader.get_block_header(838638).unwrap();
+        header.timestamp = prev.timestamp - MAX_TIMEWARP - 1;
+
+        // testnet4 rejects the first block of a period more than 10 minutes before its predecessor
+        let validator = HeaderValidator::new(ChainParams::testnet4());
+        assert_eq!(
+            validator.check_timewarp(2016 * 3, &prev, &header),
+            Err(ValidationError::TimeWarp(2016 * 3))
+        );
+        assert_eq!(
+            validator.check_timewarp(2016 * 3 + 1, &prev, &header),
+            Ok(())
+        );
+        header.timestamp += 1;
+        assert_eq!(validator.check_timewarp(2016 * 3, &prev, &header), Ok(()));
+
+        // mainnet does not enforce BIP94
+        header.timestamp -= 1;
+        let validator = HeaderValidator::new(ChainParams::mainnet());
+        assert_eq!(validator.check_timewarp(2016 * 3, &prev, &header), Ok(()));
+    }
+
+    #[test]
+    fn reject_insufficient_work() {
+        let reader = BlockReader::new_from_json(TEST_JSON_RPC).unwrap();
+        let mut header = reader.get_block_header(838637).unwrap();
+        header.nonce += 1;
+        let validator = HeaderValidator::new(reader.params().clone());
+        assert_eq!(
+            validator.check_proof_of_work(838637, &header),
+            Err(ValidationError::InsufficientWork(838637))
+        );
+    }
+
+    #[test]
+    fn min_difficulty_needs_known_ancestor() {
+        let reader = BlockReader::new_from_json_with_params(
+            TEST_JSON_RPC_MIN_DIFFICULTY,
+            min_difficulty_test_params(),
+        )
+        .unwrap();
+        let headers = reader.get_block_headers().unwrap();
+        let validator = HeaderValidator::new(reader.params().clone());
+        assert_eq!(validator.validate_headers(&headers), Ok(()));
+
+        // blocks 1004 and 1005 are mined at min-difficulty, the bits of 1006 can not be derived
+        assert_eq!(
+            validator.validate_headers(&headers[4..]),
+            Err(ValidationError::MissingAncestor(1006))
+        );
+
+        // a timestamp close to the end of the u32 range must not overflow
+        let mut ancestors = headers[..4].to_vec();
+        ancestors[3].1.timestamp = u32::MAX;
+        assert_eq!(
+            validator.next_work_required(&ancestors, &headers[4].1),
+            Ok(headers[3].1.bits())
+        );
+    }
+}
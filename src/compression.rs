@@ -0,0 +1,231 @@
+//! Compression of the IVC proof of a header chain into the proof handed to verifiers.
+//!
+//! The folded proof grows with the step circuit, not with the chain, but it is still large and
+//! its verifier needs the full public parameters. Spartan compresses it into a SNARK, which
+//! needs a polynomial commitment whose commitments are single group elements, i.e. Zeromorph on
+//! BN254. Transparent polynomial commitments of the Hyrax family commit to a vector of group
+//! elements and do not fit, proofs over a transparent cycle are handed out uncompressed.
+//!
+//! A transparent compression backend is out of scope here. It needs a transparent polynomial
+//! commitment with single group element commitments, e.g. an IPA-based one over Pallas,
+//! implementing `PolyCommitmentScheme` of `ark-spartan`, which neither `ark-spartan` nor
+//! nexus-nova provide. `bitfold prove --cycle pallas-vesta` is the transparent option, at the
+//! size of the uncompressed proof.
+//!
+//! ToDo: verifying compressed proofs on the EVM needs the Spartan verifier, including the
+//! non-native Zeromorph opening check, as a Groth16 circuit over BN254. A wrapper that only binds
+//! the proven state without verifying the proof in-circuit would be forgeable.
+
+use std::error::Error;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ec::short_weierstrass::{Projective, SWCurveConfig};
+use ark_ff::PrimeField;
+use ark_serialize::{CanonicalSerialize, Compress, SerializationError, Write};
+use ark_spartan::polycommitments::PolyCommitmentScheme;
+use nexus_nova::{
+    circuits::nova::sequential::compression::{SNARKKey, PVC},
+    commitment::CommitmentScheme,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::ivc::{self, BitcoinCompressedProof, BitcoinIVCProof};
+use crate::{Anchor, BitcoinIVC, BitcoinPublicParams, ChainParams, ChainState};
+
+/// Which compression produced a proof. There is no transparent backend, see the module docs.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CompressionBackend {
+    /// The IVC proof itself.
+    #[default]
+    Uncompressed,
+    /// A Spartan SNARK over the polynomial commitment of the cycle, Zeromorph on BN254.
+    Spartan,
+}
+
+/// Compresses the IVC proof of a [`BitcoinIVC`] and verifies the compressed proof.
+pub trait Compression<G1, G2, C1, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    C1: CommitmentScheme<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    const BACKEND: CompressionBackend;
+    /// Key of the prover and the verifier, derived from the public parameters.
+    type Key;
+    type Proof: CanonicalSerialize;
+
+    fn compress(
+        key: &Self::Key,
+        ivc: &BitcoinIVC<G1, G2, C1, C2>,
+    ) -> Result<Self::Proof, Box<dyn Error>>;
+
+    /// Verifies a compressed proof of the chain of `chain_params` from `anchor` and returns the
+    /// proven state, as [`BitcoinIVC::verify`] does.
+    fn verify(
+        key: &Self::Key,
+        params: &BitcoinPublicParams<G1, G2, C1, C2>,
+        chain_params: &ChainParams,
+        anchor: &Anchor,
+        proof: &Self::Proof,
+    ) -> Result<ChainState, Box<dyn Error>>;
+}
+
+/// The IVC proof with its number of steps, which its verifier needs.
+pub struct UncompressedProof<G1, G2, C1, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    C1: CommitmentScheme<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    pub proof: BitcoinIVCProof<G1, G2, C1, C2>,
+    pub num_steps: u64,
+}
+
+impl<G1, G2, C1, C2> CanonicalSerialize for UncompressedProof<G1, G2, C1, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    C1: CommitmentScheme<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        self.proof.serialize_with_mode(&mut writer, compress)?;
+        self.num_steps.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        self.proof.serialized_size(compress) + self.num_steps.serialized_size(compress)
+    }
+}
+
+/// Hands out the IVC proof as is, for any commitment scheme.
+pub struct NoCompression;
+
+impl<G1, G2, C1, C2> Compression<G1, G2, C1, C2> for NoCompression
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    C1: CommitmentScheme<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    const BACKEND: CompressionBackend = CompressionBackend::Uncompressed;
+    type Key = ();
+    type Proof = UncompressedProof<G1, G2, C1, C2>;
+
+    fn compress(
+        _key: &Self::Key,
+        ivc: &BitcoinIVC<G1, G2, C1, C2>,
+    ) -> Result<Self::Proof, Box<dyn Error>> {
+        Ok(UncompressedProof {
+            proof: ivc.proof().clone(),
+            num_steps: ivc.num_steps() as u64,
+        })
+    }
+
+    fn verify(
+        _key: &Self::Key,
+        params: &BitcoinPublicParams<G1, G2, C1, C2>,
+        chain_params: &ChainParams,
+        anchor: &Anchor,
+        proof: &Self::Proof,
+    ) -> Result<ChainState, Box<dyn Error>> {
+        ivc::verify_proof(
+            params,
+            chain_params,
+            anchor,
+            &proof.proof,
+            proof.num_steps as usize,
+        )
+    }
+}
+
+/// Compresses into a Spartan SNARK over the polynomial commitment `PC`.
+pub struct SpartanCompression<PC>(PhantomData<PC>);
+
+impl<G1, G2, PC, C2> Compression<G1, G2, PVC<G1, PC>, C2> for SpartanCompression<PC>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    PC: PolyCommitmentScheme<Projective<G1>>,
+    PC::Commitment: Copy + Into<Projective<G1>> + From<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    const BACKEND: CompressionBackend = CompressionBackend::Spartan;
+    type Key = SNARKKey<G1, PC>;
+    type Proof = BitcoinCompressedProof<G1, G2, PC, C2>;
+
+    fn compress(
+        key: &Self::Key,
+        ivc: &BitcoinIVC<G1, G2, PVC<G1, PC>, C2>,
+    ) -> Result<Self::Proof, Box<dyn Error>> {
+        ivc.compress(key)
+    }
+
+    fn verify(
+        key: &Self::Key,
+        params: &BitcoinPublicParams<G1, G2, PVC<G1, PC>, C2>,
+        chain_params: &ChainParams,
+        anchor: &Anchor,
+        proof: &Self::Proof,
+    ) -> Result<ChainState, Box<dyn Error>> {
+        ivc::verify_compressed(key, params, chain_params, anchor, proof)
+    }
+}
+
+/// Size and timings of compressing a proof with a backend, see [`measure_compression`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompressionReport {
+    pub backend: CompressionBackend,
+    /// Size of the compressed serialization of the proof, in bytes.
+    pub proof_size: usize,
+    pub compress_time: Duration,
+    pub verify_time: Duration,
+}
+
+/// Compresses the proof of `ivc` with `S`, verifies it and reports the proof size and timings.
+pub fn measure_compression<S, G1, G2, C1, C2>(
+    key: &S::Key,
+    ivc: &BitcoinIVC<G1, G2, C1, C2>,
+) -> Result<CompressionReport, Box<dyn Error>>
+where
+    S: Compression<G1, G2, C1, C2>,
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    C1: CommitmentScheme<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    let start = Instant::now();
+    let proof = S::compress(key, ivc)?;
+    let compress_time = start.elapsed();
+
+    let start = Instant::now();
+    S::verify(key, ivc.params(), ivc.chain_params(), ivc.anchor(), &proof)?;
+    let verify_time = start.elapsed();
+
+    Ok(CompressionReport {
+        backend: S::BACKEND,
+        proof_size: proof.compressed_size(),
+        compress_time,
+        verify_time,
+    })
+}
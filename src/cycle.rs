@@ -0,0 +1,332 @@
+//! Curve cycles the header chain can be proven over, selected at runtime.
+//!
+//! Nova folds over a cycle of curves, the step circuit runs on the scalar field of the primary
+//! curve. BN254/Grumpkin commits to the primary witnesses with Zeromorph, which needs a trusted
+//! SRS and lets proofs be compressed into a SNARK. Pallas/Vesta commits with Pedersen on both
+//! curves, which has a transparent setup, its proofs are not compressed.
+
+use std::error::Error;
+
+use ark_bn254::{g1::Config as Bn254Config, Bn254};
+use ark_ec::short_weierstrass::Projective;
+use ark_grumpkin::{GrumpkinConfig, Projective as GrumpkinProjective};
+use ark_pallas::{PallasConfig, Projective as PallasProjective};
+use ark_serialize::CanonicalSerialize;
+use ark_spartan::polycommitments::{zeromorph::Zeromorph, PolyCommitmentScheme};
+use ark_vesta::{Projective as VestaProjective, VestaConfig};
+use nexus_nova::{
+    circuits::nova::sequential::compression::{SNARKKey, PVC},
+    pedersen::PedersenCommitment,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::compression::CompressionBackend;
+use crate::fork::ChainProof;
+use crate::ivc::BitcoinSNARK;
+use crate::proof_file::{ProofFile, ProofFileError, PROOF_FILE_VERSION};
+use crate::{Anchor, BitcoinHeader, BitcoinIVC, ChainParams, ChainState, MmrError, MmrProof};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CurveCycle {
+    /// BN254 with Grumpkin, Zeromorph commitments with a trusted SRS, compressible proofs.
+    Bn254Grumpkin,
+    /// Pallas with Vesta, Pedersen commitments with a transparent setup.
+    PallasVesta,
+}
+
+impl CurveCycle {
+    /// Whether the setup of the cycle needs no trusted SRS.
+    pub fn is_transparent(&self) -> bool {
+        match self {
+            CurveCycle::Bn254Grumpkin => false,
+            CurveCycle::PallasVesta => true,
+        }
+    }
+}
+
+pub type Bn254GrumpkinIVC = BitcoinIVC<
+    Bn254Config,
+    GrumpkinConfig,
+    PVC<Bn254Config, Zeromorph<Bn254>>,
+    PedersenCommitment<GrumpkinProjective>,
+>;
+
+pub type PallasVestaIVC = BitcoinIVC<
+    PallasConfig,
+    VestaConfig,
+    PedersenCommitment<PallasProjective>,
+    PedersenCommitment<VestaProjective>,
+>;
+
+pub type ZeromorphSRS = <Zeromorph<Bn254> as PolyCommitmentScheme<Projective<Bn254Config>>>::SRS;
+
+pub type ZeromorphSNARKKey = SNARKKey<Bn254Config, Zeromorph<Bn254>>;
+
+/// What the setup of the public parameters of a cycle takes.
+pub enum CycleSetup {
+    Bn254Grumpkin(ZeromorphSRS),
+    PallasVesta,
+}
+
+impl CycleSetup {
+    pub fn cycle(&self) -> CurveCycle {
+        match self {
+            CycleSetup::Bn254Grumpkin(_) => CurveCycle::Bn254Grumpkin,
+            CycleSetup::PallasVesta => CurveCycle::PallasVesta,
+        }
+    }
+}
+
+/// A [`BitcoinIVC`] over the curve cycle chosen at setup.
+pub enum CycleIVC {
+    Bn254Grumpkin(Box<Bn254GrumpkinIVC>),
+    PallasVesta(Box<PallasVestaIVC>),
+}
+
+macro_rules! dispatch {
+    ($ivc:expr, $inner:ident => $body:expr) => {
+        match $ivc {
+            CycleIVC::Bn254Grumpkin($inner) => $body,
+            CycleIVC::PallasVesta($inner) => $body,
+        }
+    };
+}
+
+impl CycleIVC {
+    /// Sets up the public parameters over the cycle of `setup`, see [`BitcoinIVC::setup`].
+    pub fn setup(
+        chain_params: ChainParams,
+        anchor: Anchor,
+        setup: &CycleSetup,
+    ) -> Result<Self, Box<dyn Error>> {
+        Ok(match setup {
+            CycleSetup::Bn254Grumpkin(srs) => CycleIVC::Bn254Grumpkin(Box::new(
+                Bn254GrumpkinIVC::setup(chain_params, anchor, srs)?,
+            )),
+            CycleSetup::PallasVesta => {
+                CycleIVC::PallasVesta(Box::new(PallasVestaIVC::setup(chain_params, anchor, &())?))
+            }
+        })
+    }
+
+    /// Resumes proving from an uncompressed proof file with the headers it proves from its
+    /// anchor on, see [`BitcoinIVC::resume`].
+    pub fn resume(
+        chain_params: ChainParams,
+        setup: &CycleSetup,
+        file: &ProofFile,
+        headers: &[BitcoinHeader],
+    ) -> Result<Self, Box<dyn Error>> {
+        if setup.cycle() != file.cycle {
+            return Err(ProofFileError::CycleMismatch(setup.cycle(), file.cycle).into());
+        }
+        if chain_params.network != file.network {
+            return Err(ProofFileError::NetworkMismatch(chain_params.network, file.network).into());
+        }
+        if file.compression != CompressionBackend::Uncompressed {
+            return Err(ProofFileError::NotExtendable(file.compression).into());
+        }
+        let anchor = file.anchor.clone();
+        Ok(match setup {
+            CycleSetup::Bn254Grumpkin(srs) => {
+                CycleIVC::Bn254Grumpkin(Box::new(Bn254GrumpkinIVC::resume(
+                    chain_params,
+                    anchor,
+                    srs,
+                    &file.proof,
+                    file.num_steps,
+                    headers,
+                )?))
+            }
+            CycleSetup::PallasVesta => CycleIVC::PallasVesta(Box::new(PallasVestaIVC::resume(
+                chain_params,
+                anchor,
+                &(),
+                &file.proof,
+                file.num_steps,
+                headers,
+            )?)),
+        })
+    }
+
+    pub fn cycle(&self) -> CurveCycle {
+        match self {
+            CycleIVC::Bn254Grumpkin(_) => CurveCycle::Bn254Grumpkin,
+            CycleIVC::PallasVesta(_) => CurveCycle::PallasVesta,
+        }
+    }
+
+    pub fn chain_params(&self) -> &ChainParams {
+        dispatch!(self, ivc => ivc.chain_params())
+    }
+
+    pub fn anchor(&self) -> &Anchor {
+        dispatch!(self, ivc => ivc.anchor())
+    }
+
+    /// State after the last proven header.
+    pub fn state(&self) -> &ChainState {
+        dispatch!(self, ivc => ivc.state())
+    }
+
+    pub fn num_steps(&self) -> usize {
+        dispatch!(self, ivc => ivc.num_steps())
+    }
+
+    /// See [`BitcoinIVC::prove_membership`].
+    pub fn prove_membership(&self, height: u32) -> Result<MmrProof, MmrError> {
+        dispatch!(self, ivc => ivc.prove_membership(height))
+    }
+
+    /// See [`BitcoinIVC::extend`].
+    pub fn extend(&mut self, headers: &[BitcoinHeader]) -> Result<(), Box<dyn Error>> {
+        dispatch!(self, ivc => ivc.extend(headers))
+    }
+
+    /// See [`BitcoinIVC::follow`].
+    pub fn follow(&mut self, headers: &[BitcoinHeader]) -> Result<u32, Box<dyn Error>> {
+        dispatch!(self, ivc => ivc.follow(headers))
+    }
+
+    /// See [`BitcoinIVC::verify`].
+    pub fn verify(&self) -> Result<ChainState, Box<dyn Error>> {
+        dispatch!(self, ivc => ivc.verify())
+    }
+
+    /// The uncompressed proof with what it was proven for, to be verified with
+    /// [`ProofFile::verify`].
+    pub fn proof_file(&self) -> Result<ProofFile, Box<dyn Error>> {
+        let (compression, proof) = dispatch!(self, ivc => {
+            let mut proof = Vec::new();
+            ivc.proof().serialize_compressed(&mut proof)?;
+            (CompressionBackend::Uncompressed, proof)
+        });
+        Ok(self.file_of(compression, proof))
+    }
+
+    /// Key to compress proofs over BN254/Grumpkin with, derived from the public parameters.
+    pub fn snark_key(&self, srs: &ZeromorphSRS) -> Result<ZeromorphSNARKKey, Box<dyn Error>> {
+        match self {
+            CycleIVC::Bn254Grumpkin(ivc) => Ok(BitcoinSNARK::<
+                Bn254Config,
+                GrumpkinConfig,
+                Zeromorph<Bn254>,
+                PedersenCommitment<GrumpkinProjective>,
+            >::setup(ivc.params(), srs)?),
+            CycleIVC::PallasVesta(_) => Err(ProofFileError::UnsupportedCompression(
+                self.cycle(),
+                CompressionBackend::Spartan,
+            )
+            .into()),
+        }
+    }
+
+    /// The proof compressed with Spartan, only proofs over BN254/Grumpkin can be compressed, see
+    /// [`crate::compression`].
+    pub fn compressed_proof_file(
+        &self,
+        key: &ZeromorphSNARKKey,
+    ) -> Result<ProofFile, Box<dyn Error>> {
+        let CycleIVC::Bn254Grumpkin(ivc) = self else {
+            return Err(ProofFileError::UnsupportedCompression(
+                self.cycle(),
+                CompressionBackend::Spartan,
+            )
+            .into());
+        };
+        let mut proof = Vec::new();
+        ivc.compress(key)?.serialize_compressed(&mut proof)?;
+        Ok(self.file_of(CompressionBackend::Spartan, proof))
+    }
+
+    fn file_of(&self, compression: CompressionBackend, proof: Vec<u8>) -> ProofFile {
+        ProofFile {
+            version: PROOF_FILE_VERSION,
+            cycle: self.cycle(),
+            network: self.chain_params().network,
+            anchor: self.anchor().clone(),
+            num_steps: self.num_steps(),
+            compression,
+            proof,
+        }
+    }
+}
+
+impl ChainProof for CycleIVC {
+    fn verify_chain(&self) -> Result<ChainState, Box<dyn Error>> {
+        self.verify()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bitcoin_fold_tests::setup_srs;
+    use crate::test_utils::mine_regtest_headers;
+
+    #[test]
+    fn prove_over_pallas_vesta() {
+        let chain_params = ChainParams::regtest();
+        let headers = mine_regtest_headers(&chain_params.genesis, 2, 1);
+
+        // the transparent cycle needs no SRS
+        assert!(CurveCycle::PallasVesta.is_transparent());
+        let setup = CycleSetup::PallasVesta;
+        let mut ivc = CycleIVC::setup(chain_params.clone(), Anchor::Genesis, &setup).unwrap();
+        assert_eq!(ivc.cycle(), CurveCycle::PallasVesta);
+        ivc.extend(&headers).unwrap();
+        let state = ivc.verify().unwrap();
+        assert_eq!(state.tip_hash, headers[1].hash());
+
+        // the proof file records the cycle the proof is over
+        let file = ProofFile::from_bytes(&ivc.proof_file().unwrap().to_bytes()).unwrap();
+        assert_eq!(file.cycle, CurveCycle::PallasVesta);
+        assert_eq!(
+            file.verify(&chain_params, &Anchor::Genesis, &setup)
+                .unwrap(),
+            state
+        );
+
+        let srs = setup_srs::<Bn254Config, Zeromorph<Bn254>>();
+        let error = file
+            .verify(
+                &chain_params,
+                &Anchor::Genesis,
+                &CycleSetup::Bn254Grumpkin(srs),
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&ProofFileError::CycleMismatch(
+                CurveCycle::Bn254Grumpkin,
+                CurveCycle::PallasVesta
+            ))
+        );
+    }
+
+    #[test]
+    fn compressed_proof_file() {
+        let chain_params = ChainParams::regtest();
+        let headers = mine_regtest_headers(&chain_params.genesis, 1, 1);
+
+        let setup = CycleSetup::Bn254Grumpkin(setup_srs::<Bn254Config, Zeromorph<Bn254>>());
+        let mut ivc = CycleIVC::setup(chain_params.clone(), Anchor::Genesis, &setup).unwrap();
+        ivc.extend(&headers).unwrap();
+        let CycleSetup::Bn254Grumpkin(srs) = &setup else {
+            unreachable!()
+        };
+        let key = ivc.snark_key(srs).unwrap();
+
+        // the proof file records the backend that compressed the proof
+        let file = ivc.compressed_proof_file(&key).unwrap();
+        assert_eq!(file.compression, CompressionBackend::Spartan);
+        let uncompressed = ivc.proof_file().unwrap();
+        assert!(file.proof.len() < uncompressed.proof.len());
+        let file = ProofFile::from_bytes(&file.to_bytes()).unwrap();
+        assert_eq!(
+            file.verify(&chain_params, &Anchor::Genesis, &setup)
+                .unwrap(),
+            ivc.verify().unwrap()
+        );
+    }
+}
@@ -0,0 +1,636 @@
+//! Daemon keeping the proof of a header chain at the tip of a header source.
+//!
+//! Each poll of a [`ProvingDaemon`] fetches the headers past the proven tip from a
+//! [`HeaderSource`], validates them natively with the [`HeaderValidator`] and proves them one
+//! step per header. Headers that fork from the proven chain are proven with
+//! [`CycleIVC::follow`], which rolls back to the stored proof of the fork point. The daemon
+//! persists the uncompressed proofs at checkpoint heights, and compresses and publishes the
+//! proof to a [`ProofStore`] once it reaches the tip of the source, every publish interval while
+//! it catches up, and at every checkpoint height of the store it proves. A reorg drops the
+//! published checkpoints of the reorged blocks, the branch is proven at once and only its tip
+//! is published. After a restart, [`resume_from_checkpoints`] picks up from the newest persisted
+//! checkpoint.
+//!
+//! ToDo: the sources are json files of bitcoind headers, RPC and P2P sources implement
+//! [`HeaderSource`] as well.
+
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ark_bn254::Fr;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::cycle::{CurveCycle, CycleIVC, CycleSetup};
+use crate::{
+    BitcoinHeader, BlockReader, Bn254GrumpkinVerifierKey, ChainParams, HeaderValidator, ProofFile,
+    ProofStore, VerifiedChainState,
+};
+
+// blocks a reorg may reach back, the proofs of as many blocks are stored by the IVC
+const DEFAULT_MAX_REORG_DEPTH: u32 = 100;
+const DEFAULT_BATCH_SIZE: u32 = 100;
+const DEFAULT_PUBLISH_INTERVAL: u32 = 100;
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DaemonError {
+    #[error("only proofs over BN254/Grumpkin can be compressed and published, not {0:?}")]
+    UnsupportedCycle(CurveCycle),
+    #[error("the source does not have the proven tip at height {0}")]
+    UnknownTip(u32),
+    #[error("the source forks from the proven chain below height {0}")]
+    ReorgTooDeep(u32),
+    #[error("the source returned {1} headers from height {0}, expected {2}")]
+    MissingHeaders(u32, usize, usize),
+}
+
+/// Best chain of headers of a node.
+pub trait HeaderSource {
+    /// Height of the tip of the best chain.
+    fn tip_height(&mut self) -> Result<u32, Box<dyn Error>>;
+
+    /// Headers of the best chain at the heights `start..=end`.
+    fn headers(&mut self, start: u32, end: u32) -> Result<Vec<BitcoinHeader>, Box<dyn Error>>;
+}
+
+/// Source reading a json file of headers as returned by bitcoind, see [`BlockReader`], the file
+/// is read again on every poll.
+pub struct JsonFileSource {
+    path: PathBuf,
+    chain_params: ChainParams,
+    reader: Option<BlockReader>,
+}
+
+impl JsonFileSource {
+    pub fn new(path: impl Into<PathBuf>, chain_params: ChainParams) -> Self {
+        JsonFileSource {
+            path: path.into(),
+            chain_params,
+            reader: None,
+        }
+    }
+}
+
+impl HeaderSource for JsonFileSource {
+    fn tip_height(&mut self) -> Result<u32, Box<dyn Error>> {
+        let path = self.path.to_str().ok_or("header file path is not utf-8")?;
+        let reader = BlockReader::new_from_file_with_params(path, self.chain_params.clone())?;
+        let tip_height = reader
+            .get_block_headers()?
+            .last()
+            .map(|(height, _)| *height)
+            .ok_or("header file is empty")?;
+        self.reader = Some(reader);
+        Ok(tip_height)
+    }
+
+    fn headers(&mut self, start: u32, end: u32) -> Result<Vec<BitcoinHeader>, Box<dyn Error>> {
+        if self.reader.is_none() {
+            self.tip_height()?;
+        }
+        let reader = self.reader.as_ref().expect("reader is read");
+        (start..=end)
+            .map(|height| reader.get_block_header(height))
+            .collect()
+    }
+}
+
+/// Resumes proving from the newest checkpoint in `dir`, as written by
+/// [`ProvingDaemon::with_checkpoints`], whose headers are in the chain of `source`, see
+/// [`CycleIVC::resume`]. Returns `None` if there is no such checkpoint.
+pub fn resume_from_checkpoints<S: HeaderSource>(
+    source: &mut S,
+    dir: &Path,
+    chain_params: &ChainParams,
+    setup: &CycleSetup,
+) -> Result<Option<CycleIVC>, Box<dyn Error>> {
+    let mut checkpoints = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let height = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("checkpoint-")?.strip_suffix(".json"))
+            .and_then(|height| height.parse::<u32>().ok());
+        if let Some(height) = height {
+            checkpoints.push((height, path));
+        }
+    }
+    checkpoints.sort_unstable_by_key(|(height, _)| Reverse(*height));
+
+    // checkpoints of a chain the source reorged away from are skipped
+    for (height, path) in checkpoints {
+        match resume_from(source, &path, height, chain_params, setup) {
+            Ok(ivc) => {
+                info!("resuming from the checkpoint at height {height}");
+                return Ok(Some(ivc));
+            }
+            Err(error) => warn!("skipping {}: {error}", path.display()),
+        }
+    }
+    Ok(None)
+}
+
+fn resume_from<S: HeaderSource>(
+    source: &mut S,
+    path: &Path,
+    height: u32,
+    chain_params: &ChainParams,
+    setup: &CycleSetup,
+) -> Result<CycleIVC, Box<dyn Error>> {
+    let file = ProofFile::from_bytes(&fs::read(path)?)?;
+    let start = file.anchor.checkpoint(chain_params).height + 1;
+    let headers = if height < start {
+        Vec::new()
+    } else {
+        source.headers(start, height)?
+    };
+    CycleIVC::resume(chain_params.clone(), setup, &file, &headers)
+}
+
+/// How the daemon is doing, served by the proof server at `/metrics`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ProverMetrics {
+    pub proven_height: u32,
+    pub source_height: u32,
+    // blocks the source is ahead of the proven tip
+    pub lag: u32,
+    pub steps: u64,
+    pub steps_per_sec: f64,
+    pub reorgs: u64,
+    pub published_height: Option<u32>,
+}
+
+/// Proves the chain of a [`HeaderSource`] as it grows, see the module documentation.
+pub struct ProvingDaemon<S: HeaderSource> {
+    source: S,
+    ivc: CycleIVC,
+    key: Bn254GrumpkinVerifierKey,
+    store: ProofStore,
+    validator: HeaderValidator,
+    // validated headers of the proven chain up to its tip, enough to validate the next headers
+    // and to find where a reorg forks
+    headers: VecDeque<(u32, BitcoinHeader)>,
+    metrics: ProverMetrics,
+    proving_time: Duration,
+    max_reorg_depth: u32,
+    batch_size: u32,
+    publish_interval: u32,
+    poll_interval: Duration,
+    checkpoint_dir: Option<PathBuf>,
+    checkpoint_interval: u32,
+}
+
+impl<S: HeaderSource> ProvingDaemon<S> {
+    /// Daemon extending `ivc`, whose proofs are compressed with the SNARK key of `key` and
+    /// published to `store`.
+    pub fn new(
+        source: S,
+        ivc: CycleIVC,
+        key: Bn254GrumpkinVerifierKey,
+        store: ProofStore,
+    ) -> Result<Self, DaemonError> {
+        if ivc.cycle() != CurveCycle::Bn254Grumpkin {
+            return Err(DaemonError::UnsupportedCycle(ivc.cycle()));
+        }
+        let validator = HeaderValidator::new(ivc.chain_params().clone());
+        let metrics = ProverMetrics {
+            proven_height: ivc.state().height,
+            ..Default::default()
+        };
+        Ok(ProvingDaemon {
+            source,
+            ivc,
+            key,
+            store,
+            validator,
+            headers: VecDeque::new(),
+            metrics,
+            proving_time: Duration::ZERO,
+            max_reorg_depth: DEFAULT_MAX_REORG_DEPTH,
+            batch_size: DEFAULT_BATCH_SIZE,
+            publish_interval: DEFAULT_PUBLISH_INTERVAL,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            checkpoint_dir: None,
+            checkpoint_interval: 1,
+        })
+    }
+
+    /// Proves at most `batch_size` headers per poll, reorgs are proven to the tip at once.
+    pub fn with_batch_size(mut self, batch_size: u32) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Publishes at least every `publish_interval` proven blocks while catching up.
+    pub fn with_publish_interval(mut self, publish_interval: u32) -> Self {
+        self.publish_interval = publish_interval.max(1);
+        self
+    }
+
+    /// Follows reorgs forking at most `max_reorg_depth` blocks below the proven tip, at most as
+    /// deep as the recent proofs the IVC stores.
+    pub fn with_max_reorg_depth(mut self, max_reorg_depth: u32) -> Self {
+        self.max_reorg_depth = max_reorg_depth;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Writes the uncompressed proof of every proven height that is a multiple of `interval`
+    /// to `dir`, as `checkpoint-{height}.json`.
+    pub fn with_checkpoints(mut self, dir: impl Into<PathBuf>, interval: u32) -> Self {
+        self.checkpoint_dir = Some(dir.into());
+        self.checkpoint_interval = interval.max(1);
+        self
+    }
+
+    pub fn ivc(&self) -> &CycleIVC {
+        &self.ivc
+    }
+
+    pub fn metrics(&self) -> &ProverMetrics {
+        &self.metrics
+    }
+
+    /// Polls the source until a [`DaemonError`] other than missing headers, other errors are
+    /// logged and the source is polled again.
+    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
+        loop {
+            if let Err(error) = self.poll() {
+                match error.downcast_ref::<DaemonError>() {
+                    None | Some(DaemonError::MissingHeaders(..)) => {}
+                    Some(_) => return Err(error),
+                }
+                warn!("poll failed: {error}");
+            }
+            thread::sleep(self.poll_interval);
+        }
+    }
+
+    /// Proves the new headers of the source and publishes, returns the number of proven blocks.
+    pub fn poll(&mut self) -> Result<u32, Box<dyn Error>> {
+        let tip_height = self.source.tip_height()?;
+        self.metrics.source_height = tip_height;
+        self.store.set_source_height(tip_height);
+        if self.headers.is_empty() {
+            self.load_context()?;
+        }
+
+        let proven_height = self.ivc.state().height;
+        let start = proven_height
+            .saturating_sub(self.max_reorg_depth)
+            .max(self.headers[0].0);
+        let end = tip_height.min(proven_height.saturating_add(self.batch_size));
+        if end <= proven_height {
+            self.update_metrics();
+            return Ok(0);
+        }
+        let mut fetched = self.fetch(start, end)?;
+
+        // the first fetched header that is not part of the proven chain
+        let fork = fetched
+            .iter()
+            .zip(start..)
+            .position(|(header, height)| self.hash_at(height) != Some(header.hash()))
+            .unwrap_or(fetched.len());
+        if fork == 0 {
+            return Err(DaemonError::ReorgTooDeep(start).into());
+        }
+        let ancestor_height = start + fork as u32 - 1;
+        let reorg = ancestor_height < proven_height && fork < fetched.len();
+        // the branch of a reorg must carry more work than the proven chain, it is proven to the
+        // tip of the source
+        if reorg && end < tip_height {
+            fetched.extend(self.fetch(end + 1, tip_height)?);
+        }
+        let new_headers: Vec<(u32, BitcoinHeader)> =
+            (ancestor_height + 1..).zip(fetched.drain(fork..)).collect();
+
+        self.validate(ancestor_height, &new_headers)?;
+        let num_proven = if reorg {
+            self.prove_reorg(ancestor_height, new_headers)?
+        } else {
+            self.prove(new_headers)?
+        };
+        self.update_metrics();
+        self.publish_if_due()?;
+        Ok(num_proven)
+    }
+
+    fn fetch(&mut self, start: u32, end: u32) -> Result<Vec<BitcoinHeader>, Box<dyn Error>> {
+        if end < start {
+            return Ok(Vec::new());
+        }
+        let headers = self.source.headers(start, end)?;
+        let expected = (end - start + 1) as usize;
+        if headers.len() != expected {
+            return Err(DaemonError::MissingHeaders(start, headers.len(), expected).into());
+        }
+        Ok(headers)
+    }
+
+    // headers kept below the proven tip, for the difficulty adjustment and reorgs
+    fn context_len(&self) -> u32 {
+        self.validator.params().difficulty_adjustment_interval() + self.max_reorg_depth
+    }
+
+    // fetches the headers below the proven tip from the source, the tip must be in its chain
+    fn load_context(&mut self) -> Result<(), Box<dyn Error>> {
+        let state = self.ivc.state();
+        let (proven_height, tip_hash) = (state.height, state.tip_hash.clone());
+        let start = proven_height.saturating_sub(self.context_len());
+        let headers = self.fetch(start, proven_height)?;
+        if headers.last().map(BitcoinHeader::hash) != Some(tip_hash) {
+            return Err(DaemonError::UnknownTip(proven_height).into());
+        }
+        self.headers = (start..).zip(headers).collect();
+        Ok(())
+    }
+
+    fn hash_at(&self, height: u32) -> Option<Vec<u8>> {
+        let first = self.headers.front()?.0;
+        let index = height.checked_sub(first)? as usize;
+        self.headers.get(index).map(|(_, header)| header.hash())
+    }
+
+    // validates `new_headers` on top of the proven chain up to `ancestor_height`
+    fn validate(
+        &self,
+        ancestor_height: u32,
+        new_headers: &[(u32, BitcoinHeader)],
+    ) -> Result<(), Box<dyn Error>> {
+        if new_headers.is_empty() {
+            return Ok(());
+        }
+        let mut run: Vec<(u32, BitcoinHeader)> = self
+            .headers
+            .iter()
+            .filter(|(height, _)| *height <= ancestor_height)
+            .cloned()
+            .collect();
+        run.extend_from_slice(new_headers);
+        Ok(self.validator.validate_headers(&run)?)
+    }
+
+    fn prove(&mut self, new_headers: Vec<(u32, BitcoinHeader)>) -> Result<u32, Box<dyn Error>> {
+        let num_proven = new_headers.len() as u32;
+        for (height, header) in new_headers {
+            let start = Instant::now();
+            self.ivc.extend(std::slice::from_ref(&header))?;
+            self.proving_time += start.elapsed();
+            self.metrics.steps += 1;
+            self.push_header(height, header);
+            self.persist_checkpoint()?;
+            if height.is_multiple_of(self.store.checkpoint_interval()) {
+                self.publish()?;
+            }
+        }
+        Ok(num_proven)
+    }
+
+    fn prove_reorg(
+        &mut self,
+        ancestor_height: u32,
+        new_headers: Vec<(u32, BitcoinHeader)>,
+    ) -> Result<u32, Box<dyn Error>> {
+        let headers: Vec<BitcoinHeader> = new_headers.iter().map(|(_, h)| h.clone()).collect();
+        let start = Instant::now();
+        let steps_before = self.ivc.num_steps();
+        let rolled_back = self.ivc.follow(&headers)?;
+        self.proving_time += start.elapsed();
+        self.metrics.steps += (self.ivc.num_steps() + rolled_back as usize - steps_before) as u64;
+        self.metrics.reorgs += 1;
+        info!("reorg of {rolled_back} blocks from height {ancestor_height}");
+
+        let reorged_height = self
+            .headers
+            .back()
+            .map_or(ancestor_height, |(height, _)| *height);
+        while self
+            .headers
+            .back()
+            .is_some_and(|(height, _)| *height > ancestor_height)
+        {
+            self.headers.pop_back();
+        }
+        self.remove_checkpoints(ancestor_height + 1, reorged_height)?;
+        self.store.drop_checkpoints_above(ancestor_height);
+        let num_proven = new_headers.len() as u32;
+        for (height, header) in new_headers {
+            self.push_header(height, header);
+        }
+        self.persist_checkpoint()?;
+        Ok(num_proven)
+    }
+
+    fn push_header(&mut self, height: u32, header: BitcoinHeader) {
+        self.headers.push_back((height, header));
+        while self.headers.len() > self.context_len() as usize + 1 {
+            self.headers.pop_front();
+        }
+    }
+
+    fn persist_checkpoint(&self) -> Result<(), Box<dyn Error>> {
+        let Some(dir) = &self.checkpoint_dir else {
+            return Ok(());
+        };
+        let height = self.ivc.state().height;
+        if !height.is_multiple_of(self.checkpoint_interval) {
+            return Ok(());
+        }
+        let file = self.ivc.proof_file()?;
+        fs::write(
+            dir.join(format!("checkpoint-{height}.json")),
+            file.to_bytes(),
+        )?;
+        Ok(())
+    }
+
+    // the checkpoints of a reorged branch, only the tip of the new branch is persisted
+    fn remove_checkpoints(&self, start: u32, end: u32) -> Result<(), Box<dyn Error>> {
+        let Some(dir) = &self.checkpoint_dir else {
+            return Ok(());
+        };
+        for height in start..=end {
+            let path = dir.join(format!("checkpoint-{height}.json"));
+            if height.is_multiple_of(self.checkpoint_interval) && path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn update_metrics(&mut self) {
+        let proven_height = self.ivc.state().height;
+        self.metrics.proven_height = proven_height;
+        self.metrics.lag = self.metrics.source_height.saturating_sub(proven_height);
+        if !self.proving_time.is_zero() {
+            self.metrics.steps_per_sec =
+                self.metrics.steps as f64 / self.proving_time.as_secs_f64();
+        }
+        self.store.set_metrics(self.metrics.clone());
+    }
+
+    fn publish_if_due(&mut self) -> Result<(), Box<dyn Error>> {
+        let state = self.ivc.state();
+        let published = self.metrics.published_height;
+        if self.ivc.num_steps() == 0 || published == Some(state.height) {
+            return Ok(());
+        }
+        let caught_up = self.metrics.lag == 0;
+        let due = published
+            .is_none_or(|published| state.height.abs_diff(published) >= self.publish_interval);
+        if !caught_up && !due {
+            return Ok(());
+        }
+        self.publish()
+    }
+
+    fn publish(&mut self) -> Result<(), Box<dyn Error>> {
+        let state = self.ivc.state();
+        let file = self.ivc.compressed_proof_file(&self.key.snark_key)?;
+        let state = VerifiedChainState::of::<Fr>(state);
+        info!("publishing proof of height {}", state.tip_height);
+        self.metrics.published_height = Some(state.tip_height);
+        self.store.publish(state, &file);
+        self.store.set_metrics(self.metrics.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bitcoin_fold_tests::setup_srs;
+    use crate::test_utils::mine_regtest_headers;
+    use crate::{Anchor, BitcoinIVCError};
+    use ark_bn254::{g1::Config as Bn254Config, Bn254};
+    use ark_spartan::polycommitments::zeromorph::Zeromorph;
+    use std::cell::RefCell;
+    use std::env;
+    use std::rc::Rc;
+
+    // chain of a node that learns of one more block on every poll
+    struct MockSource {
+        chain: Rc<RefCell<Vec<BitcoinHeader>>>,
+        height: u32,
+    }
+
+    impl HeaderSource for MockSource {
+        fn tip_height(&mut self) -> Result<u32, Box<dyn Error>> {
+            self.height = (self.height + 1).min(self.chain.borrow().len() as u32 - 1);
+            Ok(self.height)
+        }
+
+        fn headers(&mut self, start: u32, end: u32) -> Result<Vec<BitcoinHeader>, Box<dyn Error>> {
+            let chain = self.chain.borrow();
+            Ok(chain[start as usize..=end.min(self.height) as usize].to_vec())
+        }
+    }
+
+    #[test]
+    fn follow_source_and_resume() {
+        let chain_params = ChainParams::regtest();
+        let headers = mine_regtest_headers(&chain_params.genesis, 4, 1);
+        // branch of one more block from block 2, reorging out the 2 last blocks of the chain
+        let branch = mine_regtest_headers(&headers[1], 3, 2);
+        let chain = Rc::new(RefCell::new(
+            [vec![chain_params.genesis.clone()], headers.clone()].concat(),
+        ));
+        let source = MockSource {
+            chain: chain.clone(),
+            height: 2,
+        };
+
+        let srs = setup_srs::<Bn254Config, Zeromorph<Bn254>>();
+        let key = Bn254GrumpkinVerifierKey::setup(&chain_params, Anchor::Genesis, &srs).unwrap();
+        let setup = CycleSetup::Bn254Grumpkin(srs);
+        let ivc = CycleIVC::setup(chain_params.clone(), Anchor::Genesis, &setup).unwrap();
+        let store = ProofStore::new(key.to_bytes().unwrap(), 2);
+        let dir = env::temp_dir().join(format!("bitfold-daemon-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut daemon = ProvingDaemon::new(source, ivc, key, store.clone())
+            .unwrap()
+            .with_batch_size(1)
+            .with_publish_interval(2)
+            .with_checkpoints(&dir, 2);
+
+        // while behind the source, proofs are published every 2 blocks, at every checkpoint
+        // height and at the tip
+        let mut published = Vec::new();
+        for _ in 0..4 {
+            assert_eq!(daemon.poll().unwrap(), 1);
+            published.push(store.latest_state().map(|state| state.tip_height));
+        }
+        assert_eq!(published, [Some(1), Some(2), Some(2), Some(4)]);
+        assert_eq!(store.status().checkpoints, [2, 4]);
+        assert_eq!(store.status().proving_lag, Some(0));
+        assert_eq!(daemon.ivc().state().tip_hash, headers[3].hash());
+
+        // the node switches to the branch, its headers are proven from the fork point
+        chain.borrow_mut().truncate(3);
+        chain.borrow_mut().extend(branch.iter().cloned());
+        assert_eq!(daemon.poll().unwrap(), 3);
+        let state = daemon.ivc().verify().unwrap();
+        assert_eq!(state.height, 5);
+        assert_eq!(state.tip_hash, branch[2].hash());
+        assert_eq!(
+            store.latest_state(),
+            Some(VerifiedChainState::of::<Fr>(&state))
+        );
+        assert_eq!(daemon.poll().unwrap(), 0);
+        // checkpoint 4 of the reorged chain is no longer served
+        assert_eq!(store.status().checkpoints, [2]);
+
+        let metrics = daemon.metrics();
+        assert_eq!(
+            (metrics.proven_height, metrics.lag, metrics.reorgs),
+            (5, 0, 1)
+        );
+        assert_eq!(metrics.steps, 7);
+        assert_eq!(metrics.published_height, Some(5));
+        assert!(metrics.steps_per_sec > 0.0);
+        assert_eq!(store.metrics().as_ref(), Some(metrics));
+
+        // the checkpoint of the reorged chain is removed, the one below the fork is kept
+        assert!(!dir.join("checkpoint-4.json").exists());
+        let checkpoint = fs::read(dir.join("checkpoint-2.json")).unwrap();
+        let checkpoint = ProofFile::from_bytes(&checkpoint).unwrap();
+        let state = checkpoint
+            .verify(&chain_params, &Anchor::Genesis, &setup)
+            .unwrap();
+        assert_eq!(state.tip_hash, headers[1].hash());
+
+        // a restarted daemon resumes from the checkpoint with the headers it proves
+        let mut source = MockSource { chain, height: 5 };
+        let ivc = resume_from_checkpoints(&mut source, &dir, &chain_params, &setup)
+            .unwrap()
+            .unwrap();
+        assert_eq!(ivc.num_steps(), 2);
+        assert_eq!(ivc.verify().unwrap(), state);
+        assert_eq!(
+            ivc.prove_membership(1)
+                .unwrap()
+                .verify::<Fr>(&state, &headers[0].hash()),
+            Ok(())
+        );
+        let error =
+            CycleIVC::resume(chain_params.clone(), &setup, &checkpoint, &headers[..1]).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BitcoinIVCError>(),
+            Some(&BitcoinIVCError::ResumeMismatch(2))
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,307 @@
+//! Fork choice between bitfold proofs of conflicting chains.
+//!
+//! As Bitcoin Core does, the chain with the most work wins, and of two chains with the same work
+//! the one seen first.
+//!
+//! ref: https://github.com/bitcoin/bitcoin/blob/master/src/node/blockstorage.cpp (CBlockIndexWorkComparator)
+
+use std::error::Error;
+
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ec::short_weierstrass::{Projective, SWCurveConfig};
+use ark_ff::PrimeField;
+use ark_spartan::polycommitments::PolyCommitmentScheme;
+use nexus_nova::{
+    circuits::nova::sequential::compression::{SNARKKey, PVC},
+    commitment::CommitmentScheme,
+};
+use thiserror::Error;
+
+use crate::ivc::{self, BitcoinCompressedProof};
+use crate::{Anchor, BitcoinIVC, BitcoinPublicParams, ChainParams, ChainState, Network};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ForkChoiceError {
+    #[error("proofs are for different networks: {0:?} and {1:?}")]
+    NetworkMismatch(Network, Network),
+    #[error("neither proof is valid: {0}; {1}")]
+    InvalidProofs(String, String),
+}
+
+/// A proof of a header chain that can be verified into its proven state.
+pub trait ChainProof {
+    fn verify_chain(&self) -> Result<ChainState, Box<dyn Error>>;
+}
+
+impl<G1, G2, C1, C2> ChainProof for BitcoinIVC<G1, G2, C1, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    C1: CommitmentScheme<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    fn verify_chain(&self) -> Result<ChainState, Box<dyn Error>> {
+        self.verify()
+    }
+}
+
+/// A compressed proof with what its verifier needs, see [`ivc::verify_compressed`].
+pub struct CompressedChainProof<'a, G1, G2, PC, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    PC: PolyCommitmentScheme<Projective<G1>>,
+    PC::Commitment: Copy + Into<Projective<G1>> + From<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    pub key: &'a SNARKKey<G1, PC>,
+    pub params: &'a BitcoinPublicParams<G1, G2, PVC<G1, PC>, C2>,
+    pub chain_params: &'a ChainParams,
+    pub anchor: &'a Anchor,
+    pub proof: &'a BitcoinCompressedProof<G1, G2, PC, C2>,
+}
+
+impl<'a, G1, G2, PC, C2> ChainProof for CompressedChainProof<'a, G1, G2, PC, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    PC: PolyCommitmentScheme<Projective<G1>>,
+    PC::Commitment: Copy + Into<Projective<G1>> + From<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    fn verify_chain(&self) -> Result<ChainState, Box<dyn Error>> {
+        ivc::verify_compressed(
+            self.key,
+            self.params,
+            self.chain_params,
+            self.anchor,
+            self.proof,
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fork {
+    First,
+    Second,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ForkChoiceReason {
+    /// The winner has more chainwork.
+    MoreWork,
+    /// Both chains have the same work, the winner was seen first.
+    FirstSeen,
+    /// Both proofs prove the same tip.
+    SameTip,
+    /// The other proof does not verify.
+    OtherInvalid(String),
+}
+
+/// Outcome of [`compare_proofs`], with the verified states of the valid proofs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ForkChoice {
+    pub winner: Fork,
+    pub reason: ForkChoiceReason,
+    pub first: Option<ChainState>,
+    pub second: Option<ChainState>,
+}
+
+impl ForkChoice {
+    /// State of the winning chain.
+    pub fn winning_state(&self) -> &ChainState {
+        match self.winner {
+            Fork::First => self.first.as_ref(),
+            Fork::Second => self.second.as_ref(),
+        }
+        .expect("the winner is verified")
+    }
+}
+
+/// Verifies both proofs and picks the chain to follow, `first` is the proof seen first.
+pub fn compare_proofs(
+    first: &impl ChainProof,
+    second: &impl ChainProof,
+) -> Result<ForkChoice, ForkChoiceError> {
+    match (first.verify_chain(), second.verify_chain()) {
+        (Ok(first), Ok(second)) => choose_fork(first, second),
+        (Ok(first), Err(error)) => Ok(ForkChoice {
+            winner: Fork::First,
+            reason: ForkChoiceReason::OtherInvalid(error.to_string()),
+            first: Some(first),
+            second: None,
+        }),
+        (Err(error), Ok(second)) => Ok(ForkChoice {
+            winner: Fork::Second,
+            reason: ForkChoiceReason::OtherInvalid(error.to_string()),
+            first: None,
+            second: Some(second),
+        }),
+        (Err(first), Err(second)) => Err(ForkChoiceError::InvalidProofs(
+            first.to_string(),
+            second.to_string(),
+        )),
+    }
+}
+
+/// Picks the chain to follow between two verified states, `first` is the state seen first.
+pub fn choose_fork(first: ChainState, second: ChainState) -> Result<ForkChoice, ForkChoiceError> {
+    if first.network != second.network {
+        return Err(ForkChoiceError::NetworkMismatch(
+            first.network,
+            second.network,
+        ));
+    }
+    let (winner, reason) = if first.tip_hash == second.tip_hash {
+        (Fork::First, ForkChoiceReason::SameTip)
+    } else if second.chainwork > first.chainwork {
+        (Fork::Second, ForkChoiceReason::MoreWork)
+    } else if first.chainwork > second.chainwork {
+        (Fork::First, ForkChoiceReason::MoreWork)
+    } else {
+        (Fork::First, ForkChoiceReason::FirstSeen)
+    };
+    Ok(ForkChoice {
+        winner,
+        reason,
+        first: Some(first),
+        second: Some(second),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::mine_regtest_headers;
+    use crate::{BitcoinHeader, BitcoinIVCError, PallasVestaIVC};
+    use ark_bn254::Fr;
+
+    // fork of `len` regtest blocks of `bits` on top of the genesis block
+    fn fork(len: u32, bits: u32, nonce: u32) -> ChainState {
+        let params = ChainParams::regtest();
+        let mut state = ChainState::from_header::<Fr>(&params, &params.genesis, 0);
+        for _ in 0..len {
+            let header = BitcoinHeader {
+                hash_prev_block: state.tip_hash.clone(),
+                timestamp: params.genesis.timestamp + state.height + 1,
+                target_bits: bits.to_le_bytes().to_vec(),
+                nonce,
+                ..params.genesis.clone()
+            };
+            state = state.next::<Fr>(&params, &header);
+        }
+        state
+    }
+
+    // proof with a known verification outcome
+    struct MockProof(Option<ChainState>);
+
+    impl ChainProof for MockProof {
+        fn verify_chain(&self) -> Result<ChainState, Box<dyn Error>> {
+            self.0.clone().ok_or_else(|| "invalid proof".into())
+        }
+    }
+
+    #[test]
+    fn heaviest_chain_wins() {
+        // two blocks at a higher difficulty outweigh three blocks at the min difficulty
+        let longer = fork(3, 0x207fffff, 1);
+        let heavier = fork(2, 0x2000ffff, 2);
+        assert!(longer.height > heavier.height);
+
+        let choice = choose_fork(longer.clone(), heavier.clone()).unwrap();
+        assert_eq!(choice.winner, Fork::Second);
+        assert_eq!(choice.reason, ForkChoiceReason::MoreWork);
+        assert_eq!(choice.winning_state(), &heavier);
+
+        let choice = choose_fork(heavier.clone(), longer).unwrap();
+        assert_eq!(choice.winner, Fork::First);
+        assert_eq!(choice.reason, ForkChoiceReason::MoreWork);
+    }
+
+    #[test]
+    fn first_seen_wins_ties() {
+        let first = fork(2, 0x207fffff, 1);
+        let second = fork(2, 0x207fffff, 2);
+        assert_eq!(first.chainwork, second.chainwork);
+        assert_ne!(first.tip_hash, second.tip_hash);
+
+        let choice = choose_fork(first.clone(), second.clone()).unwrap();
+        assert_eq!(choice.winner, Fork::First);
+        assert_eq!(choice.reason, ForkChoiceReason::FirstSeen);
+        let choice = choose_fork(second.clone(), first).unwrap();
+        assert_eq!(choice.winning_state(), &second);
+
+        let choice = choose_fork(second.clone(), second).unwrap();
+        assert_eq!(choice.reason, ForkChoiceReason::SameTip);
+    }
+
+    #[test]
+    fn invalid_proofs_lose() {
+        let valid = MockProof(Some(fork(1, 0x207fffff, 1)));
+        let invalid = MockProof(None);
+
+        let choice = compare_proofs(&invalid, &valid).unwrap();
+        assert_eq!(choice.winner, Fork::Second);
+        assert_eq!(
+            choice.reason,
+            ForkChoiceReason::OtherInvalid("invalid proof".to_string())
+        );
+        assert!(matches!(
+            compare_proofs(&invalid, &invalid),
+            Err(ForkChoiceError::InvalidProofs(_, _))
+        ));
+
+        let mut mainnet = fork(1, 0x207fffff, 1);
+        mainnet.network = Network::Mainnet;
+        assert_eq!(
+            compare_proofs(&valid, &MockProof(Some(mainnet))),
+            Err(ForkChoiceError::NetworkMismatch(
+                Network::Regtest,
+                Network::Mainnet
+            ))
+        );
+    }
+
+    #[test]
+    fn compare_regtest_proofs() {
+        let chain_params = ChainParams::regtest();
+        let chain = mine_regtest_headers(&chain_params.genesis, 2, 1);
+        let branch = mine_regtest_headers(&chain_params.genesis, 3, 2);
+
+        let prove = |headers: &[BitcoinHeader]| {
+            let mut ivc =
+                PallasVestaIVC::setup(chain_params.clone(), Anchor::Genesis, &()).unwrap();
+            ivc.extend(headers).unwrap();
+            ivc
+        };
+        let chain_proof = prove(&chain);
+        let branch_proof = prove(&branch);
+
+        // the branch has one more block at the same difficulty
+        let choice = compare_proofs(&chain_proof, &branch_proof).unwrap();
+        assert_eq!(choice.winner, Fork::Second);
+        assert_eq!(choice.reason, ForkChoiceReason::MoreWork);
+        assert_eq!(choice.winning_state(), &branch_proof.verify().unwrap());
+        assert_eq!(choice.first, Some(chain_proof.verify().unwrap()));
+
+        let choice = compare_proofs(&branch_proof, &branch_proof).unwrap();
+        assert_eq!(choice.reason, ForkChoiceReason::SameTip);
+
+        // a proof that does not verify loses against the shorter chain
+        let empty_proof = prove(&[]);
+        let choice = compare_proofs(&empty_proof, &chain_proof).unwrap();
+        assert_eq!(choice.winner, Fork::Second);
+        assert_eq!(
+            choice.reason,
+            ForkChoiceReason::OtherInvalid(BitcoinIVCError::EmptyProof.to_string())
+        );
+        assert_eq!(choice.winning_state().tip_hash, chain[1].hash());
+    }
+}
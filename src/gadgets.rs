@@ -0,0 +1,859 @@
+//! Constraint gadgets shared by the bitfold step circuits.
+
+use ark_crypto_primitives::crh::sha256::constraints::Sha256Gadget;
+use ark_crypto_primitives::sponge::{
+    constraints::CryptographicSpongeVar, poseidon::constraints::PoseidonSpongeVar,
+};
+use ark_ff::{BigInteger, PrimeField};
+use ark_r1cs_std::{fields::fp::FpVar, prelude::*};
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+use ark_std::{vec, vec::Vec};
+use nexus_nova::poseidon_config;
+use num_bigint::BigUint;
+
+use crate::bitcoin::pow;
+use crate::mmr::{LEAF_TAG, NODE_TAG};
+
+pub(crate) mod sha256;
+
+/// Packs little-endian bytes into a single field element, the bytes must fit in the field.
+pub(crate) fn le_bytes_to_fp<F: PrimeField>(
+    bytes: &[UInt8<F>],
+) -> Result<FpVar<F>, SynthesisError> {
+    let mut bits = Vec::with_capacity(bytes.len() * 8);
+    for byte in bytes {
+        bits.extend(byte.to_bits_le()?);
+    }
+    Boolean::le_bits_to_fp_var(&bits)
+}
+
+/// Double SHA-256 of fixed length data, as used for block and transaction hashes.
+pub(crate) fn sha256d<F: PrimeField>(data: &[UInt8<F>]) -> Result<Vec<UInt8<F>>, SynthesisError> {
+    let digest = Sha256Gadget::<F>::digest(data)?;
+    Ok(Sha256Gadget::<F>::digest(&digest.0)?.0)
+}
+
+/// Poseidon digest of `elements`, the in-circuit counterpart of `state::poseidon_hash`.
+pub(crate) fn poseidon_hash<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    elements: &[FpVar<F>],
+) -> Result<FpVar<F>, SynthesisError> {
+    let mut sponge = PoseidonSpongeVar::new(cs, &poseidon_config());
+    sponge.absorb(&elements.to_vec())?;
+    Ok(sponge.squeeze_field_elements(1)?.remove(0))
+}
+
+/// Appends the leaf of the block `hash` at `height`, with the 128-bit limbs of the `chainwork` up
+/// to the block, to the merkle mountain range given by its number of leaves and its peak slots,
+/// and returns the new number of leaves and peak slots.
+///
+/// Appending works like incrementing the number of leaves: each peak of a set low bit is merged
+/// into the carried node and cleared, the peak of the first unset bit is set to the carried node.
+pub(crate) fn mmr_append<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    num_leaves: &FpVar<F>,
+    peaks: &[FpVar<F>],
+    height: &FpVar<F>,
+    hash: &[UInt8<F>],
+    chainwork: &[FpVar<F>],
+) -> Result<(FpVar<F>, Vec<FpVar<F>>), SynthesisError> {
+    let mut node = poseidon_hash(
+        cs.clone(),
+        &[
+            FpVar::constant(F::from(LEAF_TAG)),
+            height.clone(),
+            le_bytes_to_fp(&hash[..16])?,
+            le_bytes_to_fp(&hash[16..])?,
+            chainwork[0].clone(),
+            chainwork[1].clone(),
+        ],
+    )?;
+
+    let bits = to_bits_le_with_length(num_leaves, peaks.len())?;
+    let mut is_carrying = Boolean::TRUE;
+    let mut new_peaks = Vec::with_capacity(peaks.len());
+    for (peak, bit) in peaks.iter().zip(&bits) {
+        let is_merged = is_carrying.and(bit)?;
+        let is_set = is_carrying.and(&bit.not())?;
+        let merged = poseidon_hash(
+            cs.clone(),
+            &[
+                FpVar::constant(F::from(NODE_TAG)),
+                peak.clone(),
+                node.clone(),
+            ],
+        )?;
+        new_peaks.push(is_set.select(&node, &is_merged.select(&FpVar::zero(), peak)?)?);
+        node = is_merged.select(&merged, &node)?;
+        is_carrying = is_merged;
+    }
+    // the range is full
+    is_carrying.enforce_equal(&Boolean::FALSE)?;
+
+    Ok((num_leaves + F::from(1u64), new_peaks))
+}
+
+/// Allocates a transaction, zero padded to `max_blocks` sha256 blocks, and returns its bytes, its
+/// length and its txid.
+///
+/// Transactions of 64 bytes are rejected, since they could be the concatenation of two inner
+/// nodes of the merkle tree.
+pub(crate) fn tx_with_txid<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    tx_bytes: &[u8],
+    max_blocks: usize,
+) -> Result<(Vec<UInt8<F>>, FpVar<F>, Vec<UInt8<F>>), SynthesisError> {
+    let max_len = max_blocks * sha256::BLOCK_LEN;
+    if tx_bytes.len() + 9 > max_len {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let length = FpVar::new_witness(ark_relations::ns!(cs, "tx length"), || {
+        Ok(F::from(tx_bytes.len() as u64))
+    })?;
+    let mut padded = tx_bytes.to_vec();
+    padded.resize(max_len, 0);
+    let bytes = UInt8::new_witness_vec(ark_relations::ns!(cs, "tx bytes"), &padded)?;
+    length
+        .is_eq(&FpVar::constant(F::from(64u64)))?
+        .enforce_equal(&Boolean::FALSE)?;
+
+    // the txid is the double sha256 of the transaction
+    let digest = sha256::sha256_var_len(&bytes, &length)?;
+    let txid = Sha256Gadget::<F>::digest(&digest)?.0;
+    Ok((bytes, length, txid))
+}
+
+/// Returns the `len` bytes of `data` starting at the witnessed `offset`, enforcing
+/// `offset + len <= data.len()`.
+///
+/// The offset is selected by a witnessed one-hot vector, each returned byte costs one constraint
+/// per candidate offset.
+pub(crate) fn bytes_at<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    data: &[FpVar<F>],
+    offset: &FpVar<F>,
+    len: usize,
+) -> Result<Vec<FpVar<F>>, SynthesisError> {
+    if len > data.len() {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let selector = (0..=data.len() - len)
+        .map(|position| {
+            Boolean::new_witness(ark_relations::ns!(cs, "offset selector"), || {
+                Ok(offset.value()? == F::from(position as u64))
+            })
+            .map(FpVar::from)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // exactly one selected position, which is the offset
+    let mut count = FpVar::zero();
+    let mut position = FpVar::zero();
+    for (i, selected) in selector.iter().enumerate() {
+        count += selected;
+        position += selected * F::from(i as u64);
+    }
+    count.enforce_equal(&FpVar::one())?;
+    position.enforce_equal(offset)?;
+
+    (0..len)
+        .map(|j| {
+            let mut byte = FpVar::zero();
+            for (selected, data) in selector.iter().zip(&data[j..]) {
+                byte += selected * data;
+            }
+            Ok(byte)
+        })
+        .collect()
+}
+
+/// Output of a transaction parsed in-circuit by [`tx_output`].
+pub(crate) struct TxOutputVar<F: PrimeField> {
+    pub value: FpVar<F>,
+    pub script_len: FpVar<F>,
+    // script bytes, followed by unconstrained bytes up to the max script length
+    pub script: Vec<FpVar<F>>,
+}
+
+/// Parses the output `vout` of the transaction of `tx_bytes`, serialized without witness and of
+/// `length` bytes.
+///
+/// The transaction is walked from its start through the inputs and the outputs preceding `vout`,
+/// so the returned output is the one a node would parse. Only transactions with single byte
+/// compact sizes, at most `max_inputs` inputs and `vout < max_outputs` can be parsed, the circuit
+/// is unsatisfiable otherwise.
+pub(crate) fn tx_output<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    tx_bytes: &[UInt8<F>],
+    length: &FpVar<F>,
+    vout: &FpVar<F>,
+    max_inputs: usize,
+    max_outputs: usize,
+    max_script_len: usize,
+) -> Result<TxOutputVar<F>, SynthesisError> {
+    if tx_bytes.len() < 5 {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    // reads may run past the padded transaction, the end of the output is checked against its
+    // length below
+    let mut data = tx_bytes
+        .iter()
+        .map(|byte| Boolean::le_bits_to_fp_var(&byte.to_bits_le()?))
+        .collect::<Result<Vec<_>, _>>()?;
+    data.resize(tx_bytes.len() + 9 + max_script_len, FpVar::zero());
+    let compact_size_limit = FpVar::constant(F::from(0xfdu64));
+
+    // version, then the inputs: outpoint, script and sequence
+    let num_inputs = &data[4];
+    is_less_than(num_inputs, &compact_size_limit, 8)?.enforce_equal(&Boolean::TRUE)?;
+    is_less_than(&FpVar::zero(), num_inputs, 8)?.enforce_equal(&Boolean::TRUE)?;
+    is_less_than(
+        num_inputs,
+        &FpVar::constant(F::from(max_inputs as u64 + 1)),
+        8,
+    )?
+    .enforce_equal(&Boolean::TRUE)?;
+    let mut offset = FpVar::constant(F::from(5u64));
+    for input in 0..max_inputs {
+        let is_input = is_less_than(&FpVar::constant(F::from(input as u64)), num_inputs, 8)?;
+        let script_len = &bytes_at(
+            cs.clone(),
+            &data,
+            &is_input.select(&(&offset + F::from(36u64)), &FpVar::zero())?,
+            1,
+        )?[0];
+        is_less_than(script_len, &compact_size_limit, 8)?
+            .or(&is_input.not())?
+            .enforce_equal(&Boolean::TRUE)?;
+        offset = is_input.select(&(&offset + F::from(41u64) + script_len), &offset)?;
+    }
+
+    // then the outputs: value and script
+    let num_outputs = &bytes_at(cs.clone(), &data, &offset, 1)?[0];
+    is_less_than(num_outputs, &compact_size_limit, 8)?.enforce_equal(&Boolean::TRUE)?;
+    to_bits_le_with_length(vout, 32)?;
+    is_less_than(vout, num_outputs, 32)?.enforce_equal(&Boolean::TRUE)?;
+    is_less_than(vout, &FpVar::constant(F::from(max_outputs as u64)), 32)?
+        .enforce_equal(&Boolean::TRUE)?;
+    offset += F::from(1u64);
+    for output in 0..max_outputs {
+        let is_before = is_less_than(&FpVar::constant(F::from(output as u64)), vout, 32)?;
+        let script_len = &bytes_at(
+            cs.clone(),
+            &data,
+            &is_before.select(&(&offset + F::from(8u64)), &FpVar::zero())?,
+            1,
+        )?[0];
+        is_less_than(script_len, &compact_size_limit, 8)?
+            .or(&is_before.not())?
+            .enforce_equal(&Boolean::TRUE)?;
+        offset = is_before.select(&(&offset + F::from(9u64) + script_len), &offset)?;
+    }
+
+    let output = bytes_at(cs, &data, &offset, 9 + max_script_len)?;
+    let value = output[..8]
+        .iter()
+        .rev()
+        .fold(FpVar::zero(), |value, byte| value * F::from(256u64) + byte);
+    let script_len = output[8].clone();
+    is_less_than(
+        &script_len,
+        &FpVar::constant(F::from(max_script_len as u64 + 1)),
+        8,
+    )?
+    .enforce_equal(&Boolean::TRUE)?;
+
+    // the output ends within the transaction
+    let end = &offset + F::from(9u64) + &script_len;
+    let num_bits = (usize::BITS - data.len().leading_zeros()) as usize + 1;
+    is_less_than(&end, &(length + F::from(1u64)), num_bits)?.enforce_equal(&Boolean::TRUE)?;
+
+    Ok(TxOutputVar {
+        value,
+        script_len,
+        script: output[9..].to_vec(),
+    })
+}
+
+/// Computes the merkle root from `leaf` at the position given by the little-endian `index` bits
+/// and its witnessed `branch`, as `tx::verify_merkle_branch` does natively: a node equal to its
+/// left sibling is rejected (CVE-2012-2459).
+///
+/// The branch is allocated padded to `index.len()` levels, levels beyond the branch pass the node
+/// through.
+pub(crate) fn merkle_root_from_branch<F: PrimeField>(
+    cs: ConstraintSystemRef<F>,
+    leaf: &[UInt8<F>],
+    index: &[Boolean<F>],
+    branch: &[Vec<u8>],
+) -> Result<Vec<UInt8<F>>, SynthesisError> {
+    if branch.len() > index.len() {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+    let mut node = leaf.to_vec();
+    let mut was_in_branch = Boolean::TRUE;
+    for (level, is_right) in index.iter().enumerate() {
+        let sibling = branch
+            .get(level)
+            .cloned()
+            .unwrap_or_else(|| [0; 32].to_vec());
+        let sibling =
+            UInt8::new_witness_vec(ark_relations::ns!(cs, "merkle branch sibling"), &sibling)?;
+        let is_in_branch =
+            Boolean::new_witness(ark_relations::ns!(cs, "merkle branch level"), || {
+                Ok(level < branch.len())
+            })?;
+        // the branch is a prefix of the levels
+        is_in_branch
+            .and(&was_in_branch.not())?
+            .enforce_equal(&Boolean::FALSE)?;
+        is_in_branch
+            .and(is_right)?
+            .and(&sibling.is_eq(&node)?)?
+            .enforce_equal(&Boolean::FALSE)?;
+
+        let (left, right): (Vec<_>, Vec<_>) = node
+            .iter()
+            .zip(&sibling)
+            .map(|(node, sibling)| {
+                Ok((
+                    is_right.select(sibling, node)?,
+                    is_right.select(node, sibling)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, SynthesisError>>()?
+            .into_iter()
+            .unzip();
+        let parent = sha256d(&[left, right].concat())?;
+        node = parent
+            .iter()
+            .zip(&node)
+            .map(|(parent, node)| is_in_branch.select(parent, node))
+            .collect::<Result<Vec<_>, _>>()?;
+        was_in_branch = is_in_branch;
+    }
+    Ok(node)
+}
+
+/// Allocates the `num_bits` low bits of `value` and enforces they add up to `value`,
+/// i.e. enforces `value < 2^num_bits`.
+pub(crate) fn to_bits_le_with_length<F: PrimeField>(
+    value: &FpVar<F>,
+    num_bits: usize,
+) -> Result<Vec<Boolean<F>>, SynthesisError> {
+    if value.is_constant() {
+        let bits = value.value()?.into_bigint().to_bits_le();
+        if bits[num_bits..].iter().any(|bit| *bit) {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        return Ok(bits[..num_bits]
+            .iter()
+            .map(|bit| Boolean::constant(*bit))
+            .collect());
+    }
+
+    let cs = value.cs();
+    let bits = (0..num_bits)
+        .map(|i| Boolean::new_witness(cs.clone(), || Ok(value.value()?.into_bigint().get_bit(i))))
+        .collect::<Result<Vec<_>, _>>()?;
+    Boolean::le_bits_to_fp_var(&bits)?.enforce_equal(value)?;
+    Ok(bits)
+}
+
+/// Returns `a < b` for values known to be smaller than `2^num_bits`.
+///
+/// This is cheaper than `FpVar::is_cmp`, which decomposes both values into full field elements,
+/// but it is only sound if the range of both values is enforced elsewhere.
+pub(crate) fn is_less_than<F: PrimeField>(
+    a: &FpVar<F>,
+    b: &FpVar<F>,
+    num_bits: usize,
+) -> Result<Boolean<F>, SynthesisError> {
+    // b - a - 1 + 2^num_bits is in [0, 2^(num_bits + 1)) and has its top bit set iff a < b
+    let offset = F::from(2u64).pow([num_bits as u64]) - F::from(1u64);
+    let diff = b - a + offset;
+    let bits = to_bits_le_with_length(&diff, num_bits + 1)?;
+    Ok(bits[num_bits].clone())
+}
+
+/// Returns whether `value` is a multiple of `modulus`, for values smaller than `2^32 * modulus`.
+pub(crate) fn is_multiple_of<F: PrimeField>(
+    value: &FpVar<F>,
+    modulus: u32,
+) -> Result<Boolean<F>, SynthesisError> {
+    let cs = value.cs();
+    let low_u64 = |value: F| value.into_bigint().as_ref()[0];
+
+    // witness quotient and remainder of the division by modulus
+    let quotient = FpVar::new_witness(cs.clone(), || {
+        Ok(F::from(low_u64(value.value()?) / modulus as u64))
+    })?;
+    let remainder =
+        FpVar::new_witness(cs, || Ok(F::from(low_u64(value.value()?) % modulus as u64)))?;
+    value.enforce_equal(&(&quotient * F::from(modulus) + &remainder))?;
+
+    // quotient < 2^32 and remainder < modulus make the division unique
+    to_bits_le_with_length(&quotient, 32)?;
+    to_bits_le_with_length(&remainder, 32)?;
+    is_less_than(&remainder, &FpVar::constant(F::from(modulus)), 32)?
+        .enforce_equal(&Boolean::TRUE)?;
+
+    remainder.is_zero()
+}
+
+/// An unsigned 256-bit integer as its low and high 128-bit limbs.
+#[derive(Clone, Debug)]
+pub(crate) struct U256Var<F: PrimeField> {
+    pub lo: FpVar<F>,
+    pub hi: FpVar<F>,
+}
+
+impl<F: PrimeField> U256Var<F> {
+    /// Integer of 32 little-endian bytes, e.g. a block hash in internal byte order.
+    pub fn from_le_bytes(bytes: &[UInt8<F>]) -> Result<Self, SynthesisError> {
+        assert_eq!(bytes.len(), 32);
+        Ok(U256Var {
+            lo: le_bytes_to_fp(&bytes[..16])?,
+            hi: le_bytes_to_fp(&bytes[16..])?,
+        })
+    }
+
+    pub fn constant(bytes: &[u8]) -> Self {
+        assert_eq!(bytes.len(), 32);
+        U256Var {
+            lo: FpVar::constant(F::from_le_bytes_mod_order(&bytes[..16])),
+            hi: FpVar::constant(F::from_le_bytes_mod_order(&bytes[16..])),
+        }
+    }
+
+    /// Decodes a compact target (nBits, as 4 little-endian bytes) into the 256-bit target.
+    ///
+    /// Only exponents in [3, 32] are accepted, which covers the pow limits of all networks,
+    /// and the mantissa must not have its sign bit set.
+    pub fn from_compact(bits: &[UInt8<F>]) -> Result<Self, SynthesisError> {
+        Ok(Self::decode_compact(bits)?.0)
+    }
+
+    /// Decodes a compact target into the target and the value of the last byte of the mantissa,
+    /// i.e. 256^(exponent - 3).
+    fn decode_compact(bits: &[UInt8<F>]) -> Result<(Self, Self), SynthesisError> {
+        assert_eq!(bits.len(), 4);
+        let mantissa = bits[..3]
+            .iter()
+            .map(|byte| le_bytes_to_fp(core::slice::from_ref(byte)))
+            .collect::<Result<Vec<_>, _>>()?;
+        bits[2].to_bits_le()?[7].enforce_equal(&Boolean::FALSE)?;
+
+        // one-hot encoding of the byte offset of the mantissa in the target, i.e. exponent - 3
+        let exponent = le_bytes_to_fp(&bits[3..])?;
+        let offsets = (0..30u64)
+            .map(|offset| exponent.is_eq(&FpVar::constant(F::from(offset + 3))))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut num_offsets = FpVar::zero();
+        for is_offset in &offsets {
+            num_offsets += FpVar::from(is_offset.clone());
+        }
+        num_offsets.enforce_equal(&FpVar::one())?;
+
+        let mut target_bytes = vec![FpVar::zero(); 32];
+        let mut unit_bytes = vec![FpVar::zero(); 32];
+        for (offset, is_offset) in offsets.iter().enumerate() {
+            for (i, byte) in mantissa.iter().enumerate() {
+                target_bytes[offset + i] += byte * FpVar::from(is_offset.clone());
+            }
+            unit_bytes[offset] = FpVar::from(is_offset.clone());
+        }
+
+        let pack = |bytes: &[FpVar<F>]| {
+            let mut limb = FpVar::zero();
+            for byte in bytes.iter().rev() {
+                limb = limb * F::from(256u64) + byte;
+            }
+            limb
+        };
+        Ok((
+            U256Var {
+                lo: pack(&target_bytes[..16]),
+                hi: pack(&target_bytes[16..]),
+            },
+            U256Var {
+                lo: pack(&unit_bytes[..16]),
+                hi: pack(&unit_bytes[16..]),
+            },
+        ))
+    }
+
+    /// Encodes the integer as compact target, returning the 4 little-endian bytes of nBits.
+    ///
+    /// The encoding keeps the 3 most significant bytes, so the witnessed bits are enforced to decode
+    /// to `t` with `t <= self < t + 256^(exponent - 3)`, and to have a mantissa of at least 0x8000,
+    /// which makes them the canonical encoding. The integer must be at least 0x8000.
+    pub fn to_compact(&self) -> Result<Vec<UInt8<F>>, SynthesisError> {
+        let cs = self.lo.cs().or(self.hi.cs());
+        let bits = (0..4)
+            .map(|i| {
+                UInt8::new_witness(cs.clone(), || {
+                    Ok(pow::target_to_compact(&self.to_biguint()?).to_le_bytes()[i])
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (target, unit) = Self::decode_compact(&bits)?;
+        target.enforce_le(self)?;
+        target
+            .add(&unit)?
+            .is_le(self)?
+            .enforce_equal(&Boolean::FALSE)?;
+        let mantissa = le_bytes_to_fp(&bits[..3])?;
+        is_less_than(&FpVar::constant(F::from(0x7fffu64)), &mantissa, 24)?
+            .enforce_equal(&Boolean::TRUE)?;
+        Ok(bits)
+    }
+
+    /// Returns `self <= other`.
+    pub fn is_le(&self, other: &Self) -> Result<Boolean<F>, SynthesisError> {
+        let hi_lt = is_less_than(&self.hi, &other.hi, 128)?;
+        let hi_eq = self.hi.is_eq(&other.hi)?;
+        let lo_le = is_less_than(&self.lo, &(&other.lo + F::from(1u64)), 129)?;
+        hi_lt.or(&hi_eq.and(&lo_le)?)
+    }
+
+    pub fn enforce_le(&self, other: &Self) -> Result<(), SynthesisError> {
+        self.is_le(other)?.enforce_equal(&Boolean::TRUE)
+    }
+
+    fn from_limbs(limbs: &[FpVar<F>]) -> Self {
+        let shift = F::from(2u64).pow([64]);
+        U256Var {
+            lo: &limbs[0] + &limbs[1] * shift,
+            hi: &limbs[2] + &limbs[3] * shift,
+        }
+    }
+
+    /// Splits the integer into four 64-bit limbs, enforcing both 128-bit limbs are in range.
+    fn to_limbs(&self) -> Result<Vec<FpVar<F>>, SynthesisError> {
+        let mut limbs = Vec::with_capacity(4);
+        for limb in [&self.lo, &self.hi] {
+            let bits = to_bits_le_with_length(limb, 128)?;
+            limbs.push(Boolean::le_bits_to_fp_var(&bits[..64])?);
+            limbs.push(Boolean::le_bits_to_fp_var(&bits[64..])?);
+        }
+        Ok(limbs)
+    }
+
+    fn to_biguint(&self) -> Result<BigUint, SynthesisError> {
+        let limb = |limb: &FpVar<F>| -> Result<BigUint, SynthesisError> {
+            Ok(BigUint::from_bytes_le(
+                &limb.value()?.into_bigint().to_bytes_le(),
+            ))
+        };
+        Ok(limb(&self.lo)? + (limb(&self.hi)? << 128))
+    }
+
+    /// Allocates `num_limbs` 64-bit limbs of `value`, computed from the values of the circuit.
+    fn new_witness_limbs(
+        cs: ConstraintSystemRef<F>,
+        num_limbs: usize,
+        value: impl Fn() -> Result<BigUint, SynthesisError>,
+    ) -> Result<Vec<FpVar<F>>, SynthesisError> {
+        (0..num_limbs)
+            .map(|i| {
+                let limb = FpVar::new_witness(cs.clone(), || {
+                    let digits = value()?.to_u64_digits();
+                    Ok(F::from(digits.get(i).copied().unwrap_or(0)))
+                })?;
+                to_bits_le_with_length(&limb, 64)?;
+                Ok(limb)
+            })
+            .collect()
+    }
+
+    /// Returns the expected number of hashes to meet `self` as target, i.e. 2^256 / (target + 1),
+    /// for targets smaller than 2^256 - 1.
+    ///
+    /// The quotient `w` and the remainder `r` are witnessed, and `w * target + w + r = 2^256` with
+    /// `r <= target` is enforced on 64-bit limbs, so that no intermediate value exceeds 2^131.
+    pub fn block_work(&self) -> Result<Self, SynthesisError> {
+        let cs = self.lo.cs().or(self.hi.cs());
+        let target = self.to_limbs()?;
+        let numerator = BigUint::from(1u32) << 256;
+        let work = Self::new_witness_limbs(cs.clone(), 4, || {
+            Ok(&numerator / (self.to_biguint()? + 1u32))
+        })?;
+        let remainder = Self::new_witness_limbs(cs.clone(), 4, || {
+            Ok(&numerator % (self.to_biguint()? + 1u32))
+        })?;
+        Self::from_limbs(&remainder).enforce_le(self)?;
+
+        // columns of the schoolbook product, the sum of a column and the carry of the previous
+        // column must be a multiple of 2^64 plus the limb of 2^256
+        let shift = F::from(2u64).pow([64]);
+        let mut carry = FpVar::zero();
+        for k in 0..7 {
+            let mut column = carry.clone();
+            for i in k.saturating_sub(3)..=k.min(3) {
+                column += &work[i] * &target[k - i];
+            }
+            if k < 4 {
+                column += &work[k] + &remainder[k];
+            }
+            let limb = F::from((k == 4) as u64);
+            carry = FpVar::new_witness(cs.clone(), || {
+                let column =
+                    BigUint::from_bytes_le(&(column.value()? - limb).into_bigint().to_bytes_le());
+                Ok(F::from_le_bytes_mod_order(&(column >> 64).to_bytes_le()))
+            })?;
+            // columns are below 2^131, so carries fit in 71 bits
+            to_bits_le_with_length(&carry, 71)?;
+            column.enforce_equal(&(&carry * shift + limb))?;
+        }
+        carry.enforce_equal(&FpVar::zero())?;
+
+        Ok(Self::from_limbs(&work))
+    }
+
+    /// Returns the target of the next difficulty adjustment period, i.e. `self` scaled by the actual
+    /// timespan of the period over `target_timespan` and capped at `pow_limit`. The actual timespan
+    /// is the difference of the timestamps, clamped to [target_timespan / 4, target_timespan * 4].
+    ///
+    /// The quotient `q` and the remainder `r` are witnessed, and `q * target_timespan + r = self *
+    /// timespan` with `r < target_timespan` is enforced on 64-bit limbs. The quotient gets a fifth
+    /// limb, since up to four times the target may not fit in 256 bits before it is capped.
+    ///
+    /// ref code: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/pow.cpp#L49
+    pub fn retarget(
+        &self,
+        first_timestamp: &FpVar<F>,
+        last_timestamp: &FpVar<F>,
+        target_timespan: u32,
+        pow_limit: &Self,
+    ) -> Result<Self, SynthesisError> {
+        // timestamps are below 2^32, so the shifted difference is in (0, 2^33)
+        let offset = 1u64 << 32;
+        let (min_timespan, max_timespan) = (target_timespan as u64 / 4, target_timespan as u64 * 4);
+        let shifted = last_timestamp - first_timestamp + F::from(offset);
+        let is_short = is_less_than(
+            &shifted,
+            &FpVar::constant(F::from(offset + min_timespan)),
+            33,
+        )?;
+        let is_long = is_less_than(
+            &FpVar::constant(F::from(offset + max_timespan)),
+            &shifted,
+            33,
+        )?;
+        let timespan = is_short.select(
+            &FpVar::constant(F::from(min_timespan)),
+            &is_long.select(
+                &FpVar::constant(F::from(max_timespan)),
+                &(shifted - F::from(offset)),
+            )?,
+        )?;
+
+        let cs = self.lo.cs().or(self.hi.cs()).or(timespan.cs());
+        let target = self.to_limbs()?;
+        let product = || -> Result<BigUint, SynthesisError> {
+            let timespan = BigUint::from_bytes_le(&timespan.value()?.into_bigint().to_bytes_le());
+            Ok(self.to_biguint()? * timespan)
+        };
+        let quotient = Self::new_witness_limbs(cs.clone(), 5, || Ok(product()? / target_timespan))?;
+        let remainder = FpVar::new_witness(cs.clone(), || {
+            Ok(F::from_le_bytes_mod_order(
+                &(product()? % target_timespan).to_bytes_le(),
+            ))
+        })?;
+        to_bits_le_with_length(&remainder, 32)?;
+        is_less_than(&remainder, &FpVar::constant(F::from(target_timespan)), 32)?
+            .enforce_equal(&Boolean::TRUE)?;
+
+        // both sides of a column differ by a multiple of 2^64, the columns are below 2^88 in
+        // absolute value, so the signed carries fit in 32 bits and a field element never wraps
+        let shift = F::from(2u64).pow([64]);
+        let shift_inverse = shift.inverse().expect("2^64 is not zero");
+        let carry_offset = F::from(offset);
+        let mut carry = FpVar::zero();
+        for k in 0..5 {
+            let mut column = &quotient[k] * F::from(target_timespan) + &carry;
+            if k < 4 {
+                column -= &target[k] * &timespan;
+            }
+            if k == 0 {
+                column += &remainder;
+            }
+            carry = FpVar::new_witness(cs.clone(), || Ok(column.value()? * shift_inverse))?;
+            to_bits_le_with_length(&(&carry + carry_offset), 33)?;
+            column.enforce_equal(&(&carry * shift))?;
+        }
+        carry.enforce_equal(&FpVar::zero())?;
+
+        let is_overflow = quotient[4].is_zero()?.not();
+        let quotient = Self::from_limbs(&quotient[..4]);
+        let is_capped = is_overflow.or(&pow_limit.is_le(&quotient)?)?;
+        Ok(U256Var {
+            lo: is_capped.select(&pow_limit.lo, &quotient.lo)?,
+            hi: is_capped.select(&pow_limit.hi, &quotient.hi)?,
+        })
+    }
+
+    /// Returns `self + other`, enforcing the sum does not overflow 256 bits.
+    pub fn add(&self, other: &Self) -> Result<Self, SynthesisError> {
+        let lo_bits = to_bits_le_with_length(&(&self.lo + &other.lo), 129)?;
+        let hi = &self.hi + &other.hi + FpVar::from(lo_bits[128].clone());
+        to_bits_le_with_length(&hi, 128)?;
+        Ok(U256Var {
+            lo: Boolean::le_bits_to_fp_var(&lo_bits[..128])?,
+            hi,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_bn254::Fr;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    #[test]
+    fn compact_target_decoding() {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let bits = UInt8::new_witness_vec(cs.clone(), &0x170362d3u32.to_le_bytes()).unwrap();
+        let target = U256Var::from_compact(&bits).unwrap();
+
+        // 0x170362d3 = 0x0362d3 * 256^(0x17 - 3)
+        let mut expected = [0u8; 32];
+        expected[20..23].copy_from_slice(&[0xd3, 0x62, 0x03]);
+        let expected = U256Var::<Fr>::constant(&expected);
+        assert_eq!(target.lo.value().unwrap(), expected.lo.value().unwrap());
+        assert_eq!(target.hi.value().unwrap(), expected.hi.value().unwrap());
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn u256_comparison() {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let mut small = [0u8; 32];
+        small[17] = 1;
+        let mut large = small;
+        large[0] = 1;
+        let small = U256Var::from_le_bytes(&UInt8::new_witness_vec(cs.clone(), &small).unwrap());
+        let large = U256Var::from_le_bytes(&UInt8::new_witness_vec(cs.clone(), &large).unwrap());
+        let (small, large) = (small.unwrap(), large.unwrap());
+
+        assert!(small.is_le(&large).unwrap().value().unwrap());
+        assert!(small.is_le(&small).unwrap().value().unwrap());
+        assert!(!large.is_le(&small).unwrap().value().unwrap());
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn block_work_of_target() {
+        let params = crate::ChainParams::mainnet();
+        for bits in [0x1d00ffffu32, 0x170362d3, params.pow_limit_bits] {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let bits_var = UInt8::new_witness_vec(cs.clone(), &bits.to_le_bytes()).unwrap();
+            let target = U256Var::from_compact(&bits_var).unwrap();
+            let work = target.block_work().unwrap();
+
+            let expected = crate::bitcoin::pow::block_work(
+                &crate::bitcoin::pow::compact_to_target(bits).unwrap(),
+            );
+            assert_eq!(work.to_biguint().unwrap(), expected);
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+
+    #[test]
+    fn compact_target_encoding() {
+        for target in [
+            BigUint::from(0xd86au64) << 200,
+            BigUint::from(0x0080_0000u64) << 100,
+            (BigUint::from(1u32) << 224) - 1u32,
+            BigUint::from(0x8000u64),
+        ] {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let mut bytes = target.to_bytes_le();
+            bytes.resize(32, 0);
+            let value =
+                U256Var::from_le_bytes(&UInt8::new_witness_vec(cs.clone(), &bytes).unwrap());
+            let bits = value.unwrap().to_compact().unwrap();
+            let bits = u32::from_le_bytes(bits.value().unwrap().try_into().unwrap());
+            assert_eq!(bits, pow::target_to_compact(&target));
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+
+    #[test]
+    fn retarget_of_target() {
+        let mainnet = crate::ChainParams::mainnet();
+        let testnet = crate::bitcoin::data::test_json::min_difficulty_test_params();
+        let first = 1261130161;
+        for (params, bits, last) in [
+            // ref: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/test/pow_tests.cpp#L30
+            (&mainnet, 0x1d00ffffu32, 1262152739u32),
+            // clamped to a quarter and to four times the target timespan
+            (&mainnet, 0x1c05a3f4, first + 10000),
+            (&mainnet, 0x1c387f6f, first - 1),
+            (&mainnet, 0x1b02e2a2, first + 30_000_000),
+            // capped at the pow limit, also if four times the target overflows 256 bits
+            (
+                &mainnet,
+                0x1d00ffff,
+                first + 2 * mainnet.pow_target_timespan,
+            ),
+            (&testnet, 0x207fffff, first + 30_000_000),
+        ] {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            let bits_var = UInt8::new_witness_vec(cs.clone(), &bits.to_le_bytes()).unwrap();
+            let timestamp = |t: u32| FpVar::new_witness(cs.clone(), || Ok(Fr::from(t))).unwrap();
+            let target = U256Var::from_compact(&bits_var)
+                .unwrap()
+                .retarget(
+                    &timestamp(first),
+                    &timestamp(last),
+                    params.pow_target_timespan,
+                    &U256Var::constant(&params.pow_limit),
+                )
+                .unwrap();
+            let next = u32::from_le_bytes(
+                target
+                    .to_compact()
+                    .unwrap()
+                    .value()
+                    .unwrap()
+                    .try_into()
+                    .unwrap(),
+            );
+
+            let expected = pow::calculate_next_work_required(bits, first, last, params);
+            assert_eq!(Ok(next), expected);
+            assert!(cs.is_satisfied().unwrap());
+        }
+    }
+
+    #[test]
+    fn u256_addition() {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let mut a = [0u8; 32];
+        a[..16].copy_from_slice(&[0xff; 16]);
+        let a = U256Var::from_le_bytes(&UInt8::new_witness_vec(cs.clone(), &a).unwrap()).unwrap();
+        let sum = a.add(&a).unwrap();
+        assert_eq!(sum.to_biguint().unwrap(), a.to_biguint().unwrap() * 2u32);
+        assert!(cs.is_satisfied().unwrap());
+
+        // the sum must not overflow
+        let max = U256Var::from_le_bytes(&UInt8::new_witness_vec(cs.clone(), &[0xff; 32]).unwrap());
+        max.unwrap().add(&a).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn multiple_of_interval() {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        for (height, expected) in [(2016 * 416u64, true), (838637, false), (0, true)] {
+            let height = FpVar::new_witness(cs.clone(), || Ok(Fr::from(height))).unwrap();
+            let is_multiple = is_multiple_of(&height, 2016).unwrap();
+            assert_eq!(is_multiple.value().unwrap(), expected);
+        }
+        assert!(cs.is_satisfied().unwrap());
+    }
+}
@@ -0,0 +1,253 @@
+//! SHA-256 of messages whose length is only known to the prover.
+//!
+//! `Sha256Gadget` pads the message according to the number of allocated bytes, i.e. the length
+//! of the message is fixed by the shape of the circuit. Transactions have arbitrary lengths, so
+//! here the message is allocated in a buffer of a fixed number of blocks, the padding is derived
+//! from the witnessed length and the digest is selected after the last block of the message.
+//!
+//! ref: https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf
+
+use ark_ff::PrimeField;
+use ark_r1cs_std::{fields::fp::FpVar, prelude::*};
+use ark_relations::r1cs::SynthesisError;
+use ark_std::{vec, vec::Vec};
+
+use super::to_bits_le_with_length;
+
+pub(crate) const BLOCK_LEN: usize = 64;
+
+const H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+// 32-bit words are kept as their little-endian bits
+type Word<F> = Vec<Boolean<F>>;
+
+fn constant_word<F: PrimeField>(value: u32) -> Word<F> {
+    (0..32)
+        .map(|i| Boolean::constant((value >> i) & 1 == 1))
+        .collect()
+}
+
+fn rotr<F: PrimeField>(word: &Word<F>, by: usize) -> Word<F> {
+    (0..32).map(|i| word[(i + by) % 32].clone()).collect()
+}
+
+fn shr<F: PrimeField>(word: &Word<F>, by: usize) -> Word<F> {
+    (0..32)
+        .map(|i| word.get(i + by).cloned().unwrap_or(Boolean::FALSE))
+        .collect()
+}
+
+fn xor3<F: PrimeField>(a: &Word<F>, b: &Word<F>, c: &Word<F>) -> Result<Word<F>, SynthesisError> {
+    (0..32).map(|i| a[i].xor(&b[i])?.xor(&c[i])).collect()
+}
+
+/// Sum of the words modulo 2^32.
+fn add_many<F: PrimeField>(words: &[&Word<F>]) -> Result<Word<F>, SynthesisError> {
+    let mut sum = FpVar::zero();
+    for word in words {
+        sum += Boolean::le_bits_to_fp_var(word)?;
+    }
+    // the sum of up to 8 words fits in 35 bits
+    let carry_bits = usize::BITS as usize - words.len().leading_zeros() as usize;
+    let mut bits = to_bits_le_with_length(&sum, 32 + carry_bits)?;
+    bits.truncate(32);
+    Ok(bits)
+}
+
+/// Word of 4 big-endian bytes.
+fn word_from_be_bytes<F: PrimeField>(bytes: &[Vec<Boolean<F>>]) -> Word<F> {
+    bytes.iter().rev().flatten().cloned().collect()
+}
+
+/// Applies the SHA-256 compression function to `state` and a block of 64 bytes, as bits.
+fn compress<F: PrimeField>(
+    state: &[Word<F>],
+    block: &[Vec<Boolean<F>>],
+) -> Result<Vec<Word<F>>, SynthesisError> {
+    assert_eq!(block.len(), BLOCK_LEN);
+
+    let mut w: Vec<Word<F>> = block.chunks(4).map(word_from_be_bytes).collect();
+    for t in 16..64 {
+        let s0 = xor3(
+            &rotr(&w[t - 15], 7),
+            &rotr(&w[t - 15], 18),
+            &shr(&w[t - 15], 3),
+        )?;
+        let s1 = xor3(
+            &rotr(&w[t - 2], 17),
+            &rotr(&w[t - 2], 19),
+            &shr(&w[t - 2], 10),
+        )?;
+        w.push(add_many(&[&s1, &w[t - 7], &s0, &w[t - 16]])?);
+    }
+
+    let mut v = state.to_vec();
+    for t in 0..64 {
+        let (a, b, c, e, f, g) = (&v[0], &v[1], &v[2], &v[4], &v[5], &v[6]);
+        let s1 = xor3(&rotr(e, 6), &rotr(e, 11), &rotr(e, 25))?;
+        let ch = (0..32)
+            .map(|i| e[i].select(&f[i], &g[i]))
+            .collect::<Result<Word<F>, _>>()?;
+        let temp1 = add_many(&[&v[7], &s1, &ch, &constant_word(K[t]), &w[t]])?;
+
+        let s0 = xor3(&rotr(a, 2), &rotr(a, 13), &rotr(a, 22))?;
+        let maj = (0..32)
+            .map(|i| a[i].select(&b[i].or(&c[i])?, &b[i].and(&c[i])?))
+            .collect::<Result<Word<F>, _>>()?;
+        let temp2 = add_many(&[&s0, &maj])?;
+
+        let new_e = add_many(&[&v[3], &temp1])?;
+        let new_a = add_many(&[&temp1, &temp2])?;
+        v = vec![
+            new_a,
+            v[0].clone(),
+            v[1].clone(),
+            v[2].clone(),
+            new_e,
+            v[4].clone(),
+            v[5].clone(),
+            v[6].clone(),
+        ];
+    }
+
+    state
+        .iter()
+        .zip(&v)
+        .map(|(h, v)| add_many(&[h, v]))
+        .collect()
+}
+
+/// SHA-256 digest of the first `length` bytes of `data`.
+///
+/// `data` is a zero padded buffer of whole blocks, the bytes after the message are enforced to be
+/// zero and the message must leave room for the padding, i.e. `length + 9 <= data.len()`.
+pub(crate) fn sha256_var_len<F: PrimeField>(
+    data: &[UInt8<F>],
+    length: &FpVar<F>,
+) -> Result<Vec<UInt8<F>>, SynthesisError> {
+    assert!(!data.is_empty() && data.len().is_multiple_of(BLOCK_LEN));
+    let num_blocks = data.len() / BLOCK_LEN;
+
+    // one-hot encoding of the length, i.e. of the position of the padding byte 0x80
+    let max_length = data.len() - 9;
+    let is_end = (0..=max_length)
+        .map(|k| length.is_eq(&FpVar::constant(F::from(k as u64))))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut num_ends = FpVar::zero();
+    for end in &is_end {
+        num_ends += FpVar::from(end.clone());
+    }
+    num_ends.enforce_equal(&FpVar::one())?;
+
+    // the message ends in the last block that has room for the 0x80 byte and the 64-bit length
+    let is_last_block = (0..num_blocks)
+        .map(|b| {
+            let first = (b * BLOCK_LEN).saturating_sub(8);
+            let last = (b * BLOCK_LEN + 55).min(max_length);
+            let mut sum = FpVar::zero();
+            for end in &is_end[first.min(last + 1)..=last] {
+                sum += FpVar::from(end.clone());
+            }
+            sum.is_eq(&FpVar::one())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // length of the message in bits as a 64-bit big-endian integer
+    let mut length_bits = vec![Boolean::FALSE; 3];
+    length_bits.extend(to_bits_le_with_length(length, 16)?);
+    length_bits.resize(64, Boolean::FALSE);
+
+    let mut padded = Vec::with_capacity(data.len());
+    let mut is_after_end = Boolean::FALSE;
+    for (k, byte) in data.iter().enumerate() {
+        if let Some(end) = is_end.get(k) {
+            is_after_end = is_after_end.or(end)?;
+        }
+        byte.conditional_enforce_equal(&UInt8::constant(0), &is_after_end)?;
+
+        let mut bits = byte.to_bits_le()?;
+        if let Some(end) = is_end.get(k) {
+            bits[7] = bits[7].or(end)?;
+        }
+        let offset = k % BLOCK_LEN;
+        if offset >= 56 {
+            let is_last = &is_last_block[k / BLOCK_LEN];
+            let length_byte = &length_bits[8 * (63 - offset)..8 * (64 - offset)];
+            for (bit, length_bit) in bits.iter_mut().zip(length_byte) {
+                *bit = bit.or(&is_last.and(length_bit)?)?;
+            }
+        }
+        padded.push(bits);
+    }
+
+    let mut state: Vec<Word<F>> = H.iter().map(|h| constant_word(*h)).collect();
+    let mut digest = state.clone();
+    for (block, is_last) in padded.chunks(BLOCK_LEN).zip(&is_last_block) {
+        state = compress(&state, block)?;
+        for (digest_word, word) in digest.iter_mut().zip(&state) {
+            for (digest_bit, bit) in digest_word.iter_mut().zip(word) {
+                *digest_bit = is_last.select(bit, digest_bit)?;
+            }
+        }
+    }
+
+    // digest words are serialized big-endian
+    let mut result = Vec::with_capacity(32);
+    for word in &digest {
+        for byte in word.chunks(8).rev() {
+            result.push(UInt8::from_bits_le(byte));
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_bn254::Fr;
+    use ark_crypto_primitives::crh::sha256::{digest::Digest, Sha256};
+    use ark_relations::r1cs::ConstraintSystem;
+
+    fn digest_var_len(message: &[u8], num_blocks: usize) -> (Vec<u8>, bool) {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let mut buffer = message.to_vec();
+        buffer.resize(num_blocks * BLOCK_LEN, 0);
+        let data = UInt8::new_witness_vec(cs.clone(), &buffer).unwrap();
+        let length = FpVar::new_witness(cs.clone(), || Ok(Fr::from(message.len() as u64))).unwrap();
+        let digest = sha256_var_len(&data, &length).unwrap();
+        (digest.value().unwrap(), cs.is_satisfied().unwrap())
+    }
+
+    #[test]
+    fn sha256_of_variable_length_messages() {
+        // lengths around the block boundaries, where the padding moves to the next block
+        for len in [0, 1, 55, 56, 63, 64, 119, 120, 183] {
+            let message: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let (digest, is_satisfied) = digest_var_len(&message, 3);
+            assert_eq!(digest, Sha256::digest(&message).to_vec());
+            assert!(is_satisfied);
+        }
+    }
+
+    #[test]
+    fn reject_message_without_room_for_padding() {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let data = UInt8::new_witness_vec(cs.clone(), &[0u8; BLOCK_LEN]).unwrap();
+        let length = FpVar::new_witness(cs.clone(), || Ok(Fr::from(56u64))).unwrap();
+        sha256_var_len(&data, &length).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+}
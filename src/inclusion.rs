@@ -0,0 +1,760 @@
+//! Succinct proof that a transaction is confirmed in the chain proven by a bitfold proof.
+//!
+//! [`TxInclusionCircuit`] proves the statement of a [`TxInclusionProof`] in zero knowledge: the
+//! transaction, its block and their merkle branches are witnesses, the public inputs are the
+//! digest of the verified z_i, the min number of confirmations and the txid or a commitment to it.
+//! The circuit is composed with the IVC proof of [`BitcoinHeaderCircuit`](crate::BitcoinHeaderCircuit)
+//! through the state digest: a verifier verifies the IVC proof, computes [`ChainState::digest`] of
+//! its z_i and passes it as public input to the verification of the inclusion proof.
+//!
+//! The circuit optionally proves a [`PaymentClaim`] on an output of the transaction, the output
+//! is parsed in-circuit from the bytes hashed into the txid.
+//!
+//! [`TxInclusionSNARK`] proves the circuit with Groth16 over BN254, whose scalar field is the one
+//! the BN254/Grumpkin cycle folds over, so the state digest public input is the one of the z_i of
+//! a Bn254/Grumpkin IVC proof.
+
+use std::error::Error;
+
+use ark_bn254::{Bn254, Fr};
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ff::{Field, PrimeField};
+use ark_groth16::{Groth16, Proof, ProvingKey, VerifyingKey};
+use ark_r1cs_std::{fields::fp::FpVar, prelude::*};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_snark::SNARK;
+use ark_std::rand::{CryptoRng, RngCore};
+use num_bigint::BigUint;
+use thiserror::Error;
+
+use crate::gadgets;
+use crate::mmr::{self, MmrProof, LEAF_TAG, MAX_MMR_PEAKS, NODE_TAG};
+use crate::state::{self, poseidon_hash, ChainState};
+use crate::{Transaction, TxInclusionProof};
+
+// max size of the proven transaction without witness, in sha256 blocks including the padding
+pub const MAX_TX_BLOCKS: usize = 16;
+// max depth of the txid merkle branch, i.e. blocks of up to 16384 transactions
+pub const MAX_TX_MERKLE_DEPTH: usize = 14;
+// max number of inputs of a transaction with a claimed payment
+pub const MAX_PAYMENT_INPUTS: usize = 8;
+// max index of the claimed output, exclusive
+pub const MAX_PAYMENT_OUTPUTS: usize = 16;
+// max length of the claimed output script, enough for all standard scripts but bare multisig
+pub const MAX_OUTPUT_SCRIPT_LEN: usize = 64;
+
+/// How the txid of the proven transaction is disclosed in the public inputs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TxidClaim {
+    /// The txid in internal byte order.
+    Txid(Vec<u8>),
+    /// The commitment of [`txid_commitment`] as a little-endian field element.
+    Commitment(Vec<u8>),
+}
+
+/// Claim that the output `vout` of the proven transaction pays at least `min_value` sats to
+/// `script_pubkey`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaymentClaim {
+    pub vout: u32,
+    pub script_pubkey: Vec<u8>,
+    pub min_value: u64,
+}
+
+impl PaymentClaim {
+    pub fn is_paid_by(&self, tx: &Transaction) -> bool {
+        tx.outputs.get(self.vout as usize).is_some_and(|output| {
+            output.script_pubkey == self.script_pubkey && output.value >= self.min_value
+        })
+    }
+
+    // the script is disclosed zero padded, in chunks of 16 bytes
+    fn public_inputs<F: PrimeField>(&self) -> Vec<F> {
+        let mut script = self.script_pubkey.clone();
+        script.resize(MAX_OUTPUT_SCRIPT_LEN, 0);
+        let mut inputs = vec![
+            F::from(self.vout),
+            F::from(self.min_value),
+            F::from(self.script_pubkey.len() as u64),
+        ];
+        inputs.extend(script.chunks(16).map(F::from_le_bytes_mod_order));
+        inputs
+    }
+}
+
+/// Commitment hiding `txid` behind `blinding`.
+pub fn txid_commitment<F: PrimeField + Absorb>(
+    txid: &[u8],
+    blinding: &F,
+) -> Result<F, TxInclusionSNARKError> {
+    let [txid_lo, txid_hi] = txid_halves(txid)?;
+    Ok(poseidon_hash(&[txid_lo, txid_hi, *blinding]))
+}
+
+// the txid as two field elements of 16 bytes
+fn txid_halves<F: PrimeField>(txid: &[u8]) -> Result<[F; 2], TxInclusionSNARKError> {
+    if txid.len() != 32 {
+        return Err(TxInclusionSNARKError::InvalidTxid(txid.len()));
+    }
+    Ok([
+        F::from_le_bytes_mod_order(&txid[..16]),
+        F::from_le_bytes_mod_order(&txid[16..]),
+    ])
+}
+
+/// Public statement of a [`TxInclusionCircuit`]: the transaction of `txid` has at least
+/// `min_confirmations` confirmations in the chain proven by `state`, and makes the payment of
+/// `payment` if any.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxInclusionStatement {
+    pub state: ChainState,
+    pub min_confirmations: u32,
+    pub txid: TxidClaim,
+    pub payment: Option<PaymentClaim>,
+}
+
+impl TxInclusionStatement {
+    pub fn public_inputs<F: PrimeField + Absorb>(&self) -> Result<Vec<F>, TxInclusionSNARKError> {
+        let mut inputs = vec![self.state.digest::<F>(), F::from(self.min_confirmations)];
+        match &self.txid {
+            TxidClaim::Txid(txid) => inputs.extend(txid_halves::<F>(txid)?),
+            TxidClaim::Commitment(commitment) => {
+                inputs.push(F::from_le_bytes_mod_order(commitment));
+            }
+        }
+        if let Some(payment) = &self.payment {
+            inputs.extend(payment.public_inputs::<F>());
+        }
+        Ok(inputs)
+    }
+}
+
+#[derive(Debug)]
+pub struct TxInclusionCircuit<F: Field> {
+    state: ChainState,
+    min_confirmations: u32,
+    proof: TxInclusionProof,
+    // blinding of the txid commitment, `None` if the txid is public
+    blinding: Option<F>,
+    payment: Option<PaymentClaim>,
+}
+
+impl<F: PrimeField + Absorb> TxInclusionCircuit<F> {
+    pub fn new(state: ChainState, min_confirmations: u32, proof: TxInclusionProof) -> Self {
+        TxInclusionCircuit {
+            state,
+            min_confirmations,
+            proof,
+            blinding: None,
+            payment: None,
+        }
+    }
+
+    /// Hides the txid behind a commitment with `blinding`, which must be uniformly random.
+    pub fn with_blinding(mut self, blinding: F) -> Self {
+        self.blinding = Some(blinding);
+        self
+    }
+
+    /// Also proves the transaction makes the payment of `claim`.
+    pub fn with_payment(mut self, claim: PaymentClaim) -> Self {
+        self.payment = Some(claim);
+        self
+    }
+
+    /// Statement proven by the circuit.
+    pub fn statement(&self) -> Result<TxInclusionStatement, Box<dyn Error>> {
+        let txid = Transaction::from_bytes(&self.proof.raw_tx)?.txid();
+        let txid = match &self.blinding {
+            None => TxidClaim::Txid(txid),
+            Some(blinding) => {
+                TxidClaim::Commitment(mmr::field_to_bytes(&txid_commitment(&txid, blinding)?))
+            }
+        };
+        Ok(TxInclusionStatement {
+            state: self.state.clone(),
+            min_confirmations: self.min_confirmations,
+            txid,
+            payment: self.payment.clone(),
+        })
+    }
+
+    /// Enforces the block of `block_hash` at `height` to be part of the chain of `z`, either as
+    /// its tip or as a leaf of its merkle mountain range.
+    fn generate_attestation_constraints(
+        &self,
+        cs: ConstraintSystemRef<F>,
+        z: &[FpVar<F>],
+        block_hash: &[UInt8<F>],
+        height: &FpVar<F>,
+    ) -> Result<(), SynthesisError> {
+        let membership = self.proof.header_membership.as_ref();
+        let is_tip =
+            Boolean::new_witness(
+                ark_relations::ns!(cs, "is tip"),
+                || Ok(membership.is_none()),
+            )?;
+
+        for (byte, tip_byte) in block_hash.iter().zip(&z[state::TIP_HASH]) {
+            let byte = Boolean::le_bits_to_fp_var(&byte.to_bits_le()?)?;
+            byte.conditional_enforce_equal(tip_byte, &is_tip)?;
+        }
+        height.conditional_enforce_equal(&z[state::HEIGHT], &is_tip)?;
+
+        // the path from the leaf ends at the peak of the level of its length
+        let default_proof = MmrProof {
+            leaf_index: 0,
+            height: 0,
+            chainwork: BigUint::default(),
+            num_leaves: 0,
+            siblings: Vec::new(),
+            peaks: Vec::new(),
+        };
+        let membership = membership.unwrap_or(&default_proof);
+        if membership.siblings.len() >= MAX_MMR_PEAKS {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let mut chainwork = membership.chainwork.to_bytes_le();
+        chainwork.resize(32, 0);
+        let chainwork = Vec::<FpVar<F>>::new_witness(ark_relations::ns!(cs, "chainwork"), || {
+            Ok(vec![
+                F::from_le_bytes_mod_order(&chainwork[..16]),
+                F::from_le_bytes_mod_order(&chainwork[16..]),
+            ])
+        })?;
+        let mut node = gadgets::poseidon_hash(
+            cs.clone(),
+            &[
+                FpVar::constant(F::from(LEAF_TAG)),
+                height.clone(),
+                gadgets::le_bytes_to_fp(&block_hash[..16])?,
+                gadgets::le_bytes_to_fp(&block_hash[16..])?,
+                chainwork[0].clone(),
+                chainwork[1].clone(),
+            ],
+        )?;
+        let mut was_in_path = Boolean::TRUE;
+        for level in 0..MAX_MMR_PEAKS {
+            let is_in_path = if level < MAX_MMR_PEAKS - 1 {
+                Boolean::new_witness(ark_relations::ns!(cs, "mmr path level"), || {
+                    Ok(level < membership.siblings.len())
+                })?
+            } else {
+                Boolean::FALSE
+            };
+            is_in_path
+                .and(&was_in_path.not())?
+                .enforce_equal(&Boolean::FALSE)?;
+            let is_peak = was_in_path.and(&is_in_path.not())?;
+            node.conditional_enforce_equal(
+                &z[state::MMR_PEAKS][level],
+                &is_peak.and(&is_tip.not())?,
+            )?;
+            if level == MAX_MMR_PEAKS - 1 {
+                break;
+            }
+
+            let sibling = FpVar::new_witness(ark_relations::ns!(cs, "mmr sibling"), || {
+                Ok(membership
+                    .siblings
+                    .get(level)
+                    .map(|sibling| F::from_le_bytes_mod_order(sibling))
+                    .unwrap_or_default())
+            })?;
+            let is_right = Boolean::new_witness(ark_relations::ns!(cs, "mmr direction"), || {
+                Ok((membership.leaf_index >> level) & 1 == 1)
+            })?;
+            let parent = gadgets::poseidon_hash(
+                cs.clone(),
+                &[
+                    FpVar::constant(F::from(NODE_TAG)),
+                    is_right.select(&sibling, &node)?,
+                    is_right.select(&node, &sibling)?,
+                ],
+            )?;
+            node = is_in_path.select(&parent, &node)?;
+            was_in_path = is_in_path;
+        }
+        Ok(())
+    }
+
+    /// Enforces the output of the claim to pay at least its value to its script.
+    fn generate_payment_constraints(
+        &self,
+        cs: ConstraintSystemRef<F>,
+        claim: &PaymentClaim,
+        tx_bytes: &[UInt8<F>],
+        tx_length: &FpVar<F>,
+    ) -> Result<(), SynthesisError> {
+        if claim.script_pubkey.len() > MAX_OUTPUT_SCRIPT_LEN {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        let vout = FpVar::new_input(ark_relations::ns!(cs, "payment vout"), || {
+            Ok(F::from(claim.vout))
+        })?;
+        let min_value = FpVar::new_input(ark_relations::ns!(cs, "payment min value"), || {
+            Ok(F::from(claim.min_value))
+        })?;
+        let script_len = FpVar::new_input(ark_relations::ns!(cs, "payment script length"), || {
+            Ok(F::from(claim.script_pubkey.len() as u64))
+        })?;
+        let mut padded_script = claim.script_pubkey.clone();
+        padded_script.resize(MAX_OUTPUT_SCRIPT_LEN, 0);
+        let script =
+            UInt8::new_witness_vec(ark_relations::ns!(cs, "payment script"), &padded_script)?;
+        for chunk in script.chunks(16) {
+            let chunk = gadgets::le_bytes_to_fp(chunk)?;
+            FpVar::new_input(ark_relations::ns!(cs, "payment script chunk"), || {
+                chunk.value()
+            })?
+            .enforce_equal(&chunk)?;
+        }
+
+        let output = gadgets::tx_output(
+            cs,
+            tx_bytes,
+            tx_length,
+            &vout,
+            MAX_PAYMENT_INPUTS,
+            MAX_PAYMENT_OUTPUTS,
+            MAX_OUTPUT_SCRIPT_LEN,
+        )?;
+        gadgets::to_bits_le_with_length(&min_value, 64)?;
+        gadgets::is_less_than(&output.value, &min_value, 64)?.enforce_equal(&Boolean::FALSE)?;
+        output.script_len.enforce_equal(&script_len)?;
+        for (i, (byte, expected)) in output.script.iter().zip(&script).enumerate() {
+            let is_script =
+                gadgets::is_less_than(&FpVar::constant(F::from(i as u64)), &script_len, 8)?;
+            let expected = Boolean::le_bits_to_fp_var(&expected.to_bits_le()?)?;
+            byte.conditional_enforce_equal(&expected, &is_script)?;
+        }
+        Ok(())
+    }
+}
+
+impl<F: PrimeField + Absorb> ConstraintSynthesizer<F> for TxInclusionCircuit<F> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        let tx = Transaction::from_bytes(&self.proof.raw_tx)
+            .map_err(|_| SynthesisError::Unsatisfiable)?;
+
+        // public inputs, in the order of `TxInclusionStatement::public_inputs`
+        let state_digest = FpVar::new_input(ark_relations::ns!(cs, "state digest"), || {
+            Ok(self.state.digest::<F>())
+        })?;
+        let min_confirmations =
+            FpVar::new_input(ark_relations::ns!(cs, "min confirmations"), || {
+                Ok(F::from(self.min_confirmations))
+            })?;
+
+        // the witnessed state must be the state of the digest
+        let z = Vec::<FpVar<F>>::new_witness(ark_relations::ns!(cs, "state"), || {
+            Ok(self.state.to_field_elements::<F>())
+        })?;
+        gadgets::poseidon_hash(cs.clone(), &z)?.enforce_equal(&state_digest)?;
+
+        let header_bytes = UInt8::new_witness_vec(
+            ark_relations::ns!(cs, "block header bytes"),
+            &self.proof.header.to_bytes(),
+        )?;
+        let block_hash = gadgets::sha256d(&header_bytes)?;
+        let height = FpVar::new_witness(ark_relations::ns!(cs, "block height"), || {
+            Ok(F::from(self.proof.height))
+        })?;
+        gadgets::to_bits_le_with_length(&height, 32)?;
+        self.generate_attestation_constraints(cs.clone(), &z, &block_hash, &height)?;
+
+        // the block has at least the min number of confirmations, i.e.
+        // height + min_confirmations <= tip height + 1
+        gadgets::to_bits_le_with_length(&min_confirmations, 32)?;
+        gadgets::to_bits_le_with_length(&z[state::HEIGHT], 32)?;
+        gadgets::is_less_than(
+            &(&height + &min_confirmations),
+            &(&z[state::HEIGHT] + F::from(2u64)),
+            34,
+        )?
+        .enforce_equal(&Boolean::TRUE)?;
+
+        // the transaction is committed by the merkle root of the header
+        let (tx_bytes, tx_length, txid) =
+            gadgets::tx_with_txid(cs.clone(), &tx.to_bytes_without_witness(), MAX_TX_BLOCKS)?;
+        let index = gadgets::to_bits_le_with_length(
+            &FpVar::new_witness(ark_relations::ns!(cs, "tx index"), || {
+                Ok(F::from(self.proof.tx_index))
+            })?,
+            MAX_TX_MERKLE_DEPTH,
+        )?;
+        let merkle_root =
+            gadgets::merkle_root_from_branch(cs.clone(), &txid, &index, &self.proof.merkle_branch)?;
+        merkle_root.enforce_equal(&header_bytes[36..68])?;
+
+        // disclose the txid or its commitment
+        let txid_lo = gadgets::le_bytes_to_fp(&txid[..16])?;
+        let txid_hi = gadgets::le_bytes_to_fp(&txid[16..])?;
+        match self.blinding {
+            None => {
+                let lo = FpVar::new_input(ark_relations::ns!(cs, "txid lo"), || txid_lo.value())?;
+                let hi = FpVar::new_input(ark_relations::ns!(cs, "txid hi"), || txid_hi.value())?;
+                lo.enforce_equal(&txid_lo)?;
+                hi.enforce_equal(&txid_hi)?;
+            }
+            Some(blinding) => {
+                let blinding =
+                    FpVar::new_witness(ark_relations::ns!(cs, "txid blinding"), || Ok(blinding))?;
+                let commitment =
+                    FpVar::new_input(ark_relations::ns!(cs, "txid commitment"), || {
+                        txid_commitment(&tx.txid(), &blinding.value()?)
+                            .map_err(|_| SynthesisError::Unsatisfiable)
+                    })?;
+                gadgets::poseidon_hash(cs.clone(), &[txid_lo, txid_hi, blinding])?
+                    .enforce_equal(&commitment)?;
+            }
+        }
+
+        if let Some(claim) = &self.payment {
+            self.generate_payment_constraints(cs, claim, &tx_bytes, &tx_length)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TxInclusionSNARKError {
+    #[error("statement does not have the shape the keys were generated for")]
+    ShapeMismatch,
+    #[error("inclusion proof does not verify")]
+    InvalidProof,
+    #[error("txid has {0} bytes, expected 32")]
+    InvalidTxid(usize),
+}
+
+/// Groth16 prover and verifier of [`TxInclusionCircuit`]s over BN254.
+///
+/// The keys are specific to the shape of the statements of the circuit they were generated
+/// with, i.e. whether the txid is hidden behind a commitment and whether a payment is claimed.
+pub struct TxInclusionSNARK {
+    proving_key: ProvingKey<Bn254>,
+    verifying_key: VerifyingKey<Bn254>,
+    // whether the txid is committed and whether a payment is claimed
+    shape: (bool, bool),
+}
+
+impl TxInclusionSNARK {
+    /// Generates the keys for statements of the shape of `circuit`, the randomness of `rng` is
+    /// toxic waste.
+    pub fn setup<R: RngCore + CryptoRng>(
+        circuit: TxInclusionCircuit<Fr>,
+        rng: &mut R,
+    ) -> Result<Self, Box<dyn Error>> {
+        let shape = (circuit.blinding.is_some(), circuit.payment.is_some());
+        let (proving_key, verifying_key) = Groth16::<Bn254>::circuit_specific_setup(circuit, rng)?;
+        Ok(TxInclusionSNARK {
+            proving_key,
+            verifying_key,
+            shape,
+        })
+    }
+
+    pub fn verifying_key(&self) -> &VerifyingKey<Bn254> {
+        &self.verifying_key
+    }
+
+    /// Proves `circuit` and returns the proof with the statement it proves.
+    pub fn prove<R: RngCore + CryptoRng>(
+        &self,
+        circuit: TxInclusionCircuit<Fr>,
+        rng: &mut R,
+    ) -> Result<(TxInclusionStatement, Proof<Bn254>), Box<dyn Error>> {
+        let statement = circuit.statement()?;
+        if Self::shape_of(&statement) != self.shape {
+            return Err(TxInclusionSNARKError::ShapeMismatch.into());
+        }
+        let proof = Groth16::<Bn254>::prove(&self.proving_key, circuit, rng)?;
+        Ok((statement, proof))
+    }
+
+    /// Verifies `proof` of `statement`. The state of the statement must have been verified
+    /// against an IVC proof, see [`BitcoinIVC::verify`](crate::BitcoinIVC::verify).
+    pub fn verify(
+        &self,
+        statement: &TxInclusionStatement,
+        proof: &Proof<Bn254>,
+    ) -> Result<(), Box<dyn Error>> {
+        if Self::shape_of(statement) != self.shape {
+            return Err(TxInclusionSNARKError::ShapeMismatch.into());
+        }
+        if !Groth16::<Bn254>::verify(&self.verifying_key, &statement.public_inputs()?, proof)? {
+            return Err(TxInclusionSNARKError::InvalidProof.into());
+        }
+        Ok(())
+    }
+
+    fn shape_of(statement: &TxInclusionStatement) -> (bool, bool) {
+        (
+            matches!(statement.txid, TxidClaim::Commitment(_)),
+            statement.payment.is_some(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bitcoin::data::test_json::TEST_JSON_RPC_GENESIS;
+    use crate::bitcoin::tx::{merkle_branch, merkle_root, OutPoint, TxIn, TxOut};
+    use crate::bitcoin::BlockReader;
+    use crate::mmr::HeaderMmr;
+    use crate::{BitcoinHeader, ChainParams, Transaction};
+    use ark_relations::r1cs::ConstraintSystem;
+    use ark_std::test_rng;
+
+    // coinbase of block 1, the only transaction of the block
+    const BLOCK_1_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0704ffff001d0104ffffffff0100f2052a0100000043410496b538e853519c726a2c91e61ec11600ae1390813a627c66fb8be7947be63c52da7589379515d4e0a604f8141781e62294721166bf621e73a82cbf2342c858eeac00000000";
+
+    // checks the circuit is satisfied and its public inputs are the ones of its statement
+    fn is_satisfied(circuit: TxInclusionCircuit<Fr>) -> bool {
+        let statement = circuit.statement().unwrap();
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert_eq!(
+            cs.borrow().unwrap().instance_assignment[1..],
+            statement.public_inputs::<Fr>().unwrap()
+        );
+        cs.is_satisfied().unwrap()
+    }
+
+    // state after the mainnet blocks 1 and 2, and the proof of the coinbase of block 1
+    fn proven_coinbase() -> (ChainState, TxInclusionProof) {
+        let reader = BlockReader::new_from_json(TEST_JSON_RPC_GENESIS).unwrap();
+        let headers = reader.get_block_headers().unwrap();
+        let mut state = ChainState::from_header::<Fr>(reader.params(), &headers[0].1, 0);
+        let mut mmr = HeaderMmr::<Fr>::new();
+        for (height, header) in &headers[1..] {
+            state = state.next::<Fr>(reader.params(), header);
+            mmr.push(*height, &header.hash(), &state.chainwork).unwrap();
+        }
+        let proof = TxInclusionProof {
+            raw_tx: hex::decode(BLOCK_1_COINBASE).unwrap(),
+            tx_index: 0,
+            merkle_branch: Vec::new(),
+            header: headers[1].1.clone(),
+            height: 1,
+            header_membership: Some(mmr.prove(1).unwrap()),
+        };
+        (state, proof)
+    }
+
+    // state at the tip of a regtest block of `txs`, and the proof of the transaction at `index`
+    fn proven_at_tip(txs: &[Transaction], index: usize) -> (ChainState, TxInclusionProof) {
+        let txids: Vec<Vec<u8>> = txs.iter().map(|tx| tx.txid()).collect();
+        let header = BitcoinHeader {
+            hash_merkle_root: merkle_root(&txids),
+            ..ChainParams::regtest().genesis
+        };
+        let state = ChainState::from_header::<Fr>(&ChainParams::regtest(), &header, 10);
+        let proof = TxInclusionProof {
+            raw_tx: txs[index].to_bytes(),
+            tx_index: index as u32,
+            merkle_branch: merkle_branch(&txids, index),
+            header,
+            height: 10,
+            header_membership: None,
+        };
+        (state, proof)
+    }
+
+    #[test]
+    fn prove_inclusion_in_older_block() {
+        let (state, proof) = proven_coinbase();
+        assert!(proof.verify::<Fr>(&state).is_ok());
+
+        // block 1 has 2 confirmations in a chain of 2 blocks
+        let circuit = TxInclusionCircuit::new(state.clone(), 2, proof.clone());
+        assert!(is_satisfied(circuit));
+        let circuit = TxInclusionCircuit::new(state.clone(), 3, proof.clone());
+        assert!(!is_satisfied(circuit));
+
+        // the txid can be hidden
+        let circuit =
+            TxInclusionCircuit::new(state.clone(), 1, proof.clone()).with_blinding(Fr::from(7u64));
+        assert!(matches!(
+            circuit.statement().unwrap().txid,
+            TxidClaim::Commitment(_)
+        ));
+        assert!(is_satisfied(circuit));
+
+        // the block must be in the accumulator
+        let mut other_block = proof;
+        other_block.header.nonce += 1;
+        let circuit = TxInclusionCircuit::new(state, 1, other_block);
+        assert!(!is_satisfied(circuit));
+    }
+
+    #[test]
+    fn prove_inclusion_with_groth16() {
+        let mut rng = test_rng();
+        let (state, proof) = proven_coinbase();
+        let snark = TxInclusionSNARK::setup(
+            TxInclusionCircuit::new(state.clone(), 2, proof.clone()),
+            &mut rng,
+        )
+        .unwrap();
+
+        let circuit = TxInclusionCircuit::new(state.clone(), 2, proof.clone());
+        let (statement, groth16_proof) = snark.prove(circuit, &mut rng).unwrap();
+        assert_eq!(statement.state, state);
+        snark.verify(&statement, &groth16_proof).unwrap();
+
+        // the proof is bound to its statement
+        let other_statement = TxInclusionStatement {
+            min_confirmations: 1,
+            ..statement.clone()
+        };
+        let error = snark.verify(&other_statement, &groth16_proof).unwrap_err();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&TxInclusionSNARKError::InvalidProof)
+        );
+
+        // txids are 32 bytes, shorter ones are rejected instead of sliced
+        let short_txid = TxInclusionStatement {
+            txid: TxidClaim::Txid(vec![0; 8]),
+            ..statement.clone()
+        };
+        let error = snark.verify(&short_txid, &groth16_proof).unwrap_err();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&TxInclusionSNARKError::InvalidTxid(8))
+        );
+        assert_eq!(
+            txid_commitment::<Fr>(&[0; 33], &Fr::from(7u64)),
+            Err(TxInclusionSNARKError::InvalidTxid(33))
+        );
+
+        // the keys prove transactions of the tip with the same shape
+        let tx = Transaction::from_bytes(&hex::decode(BLOCK_1_COINBASE).unwrap()).unwrap();
+        let (tip_state, tip_proof) = proven_at_tip(&[tx], 0);
+        let circuit = TxInclusionCircuit::new(tip_state, 1, tip_proof);
+        let (statement, groth16_proof) = snark.prove(circuit, &mut rng).unwrap();
+        snark.verify(&statement, &groth16_proof).unwrap();
+
+        // but not statements with a hidden txid
+        let circuit = TxInclusionCircuit::new(state, 2, proof).with_blinding(Fr::from(7u64));
+        let error = snark.prove(circuit, &mut rng).unwrap_err();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&TxInclusionSNARKError::ShapeMismatch)
+        );
+    }
+
+    #[test]
+    fn prove_inclusion_in_tip() {
+        // block of three transactions at the tip of the state
+        let txs: Vec<Transaction> = (0..3)
+            .map(|lock_time| Transaction {
+                version: 2,
+                inputs: vec![TxIn {
+                    previous_output: OutPoint::null(),
+                    script_sig: vec![0x51; 10],
+                    ..TxIn::default()
+                }],
+                outputs: vec![TxOut::default()],
+                lock_time,
+            })
+            .collect();
+        let (state, proof) = proven_at_tip(&txs, 2);
+        assert!(proof.verify::<Fr>(&state).is_ok());
+        assert!(is_satisfied(TxInclusionCircuit::new(
+            state.clone(),
+            1,
+            proof.clone()
+        )));
+
+        // the duplicate of the last transaction is not part of the block (CVE-2012-2459)
+        let duplicate = TxInclusionProof {
+            tx_index: 3,
+            ..proof
+        };
+        assert!(!is_satisfied(TxInclusionCircuit::new(state, 1, duplicate)));
+    }
+
+    #[test]
+    fn prove_payment_to_script() {
+        // p2wpkh payment at output 1 of a transaction of two inputs and three outputs
+        let p2wpkh = [[0x00, 0x14].to_vec(), vec![0xab; 20]].concat();
+        let p2tr = [[0x51, 0x20].to_vec(), vec![0xcd; 32]].concat();
+        let input = |vout| TxIn {
+            previous_output: OutPoint {
+                txid: vec![0x11; 32],
+                vout,
+            },
+            script_sig: vec![0x51; 3],
+            sequence: 0xffffffff,
+            witness: vec![vec![0x30; 72]],
+        };
+        let payment = Transaction {
+            version: 2,
+            inputs: vec![input(0), input(1)],
+            outputs: vec![
+                TxOut {
+                    value: 1_000,
+                    script_pubkey: p2tr.clone(),
+                },
+                TxOut {
+                    value: 50_000,
+                    script_pubkey: p2wpkh.clone(),
+                },
+                TxOut {
+                    value: 20_000,
+                    script_pubkey: p2tr.clone(),
+                },
+            ],
+            lock_time: 0,
+        };
+        let coinbase = Transaction {
+            version: 2,
+            inputs: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: vec![0x51; 10],
+                ..TxIn::default()
+            }],
+            outputs: vec![TxOut::default()],
+            lock_time: 0,
+        };
+        let (state, proof) = proven_at_tip(&[coinbase, payment.clone()], 1);
+        let claim = PaymentClaim {
+            vout: 1,
+            script_pubkey: p2wpkh,
+            min_value: 50_000,
+        };
+        assert!(claim.is_paid_by(&payment));
+        let circuit = |claim: &PaymentClaim| {
+            TxInclusionCircuit::new(state.clone(), 1, proof.clone()).with_payment(claim.clone())
+        };
+        assert!(is_satisfied(circuit(&claim)));
+
+        // the payment is binding on the value, the script and the output
+        let more_value = PaymentClaim {
+            min_value: 50_001,
+            ..claim.clone()
+        };
+        let other_script = PaymentClaim {
+            script_pubkey: p2tr.clone(),
+            ..claim.clone()
+        };
+        let other_output = PaymentClaim {
+            vout: 2,
+            ..claim.clone()
+        };
+        let missing_output = PaymentClaim { vout: 3, ..claim };
+        for claim in [more_value, other_script, other_output, missing_output] {
+            assert!(!claim.is_paid_by(&payment));
+            assert!(!is_satisfied(circuit(&claim)));
+        }
+        let last_output = PaymentClaim {
+            vout: 2,
+            script_pubkey: p2tr,
+            min_value: 20_000,
+        };
+        assert!(is_satisfied(circuit(&last_output)));
+    }
+}
@@ -0,0 +1,744 @@
+//! Incremental proving of a header chain with [`BitcoinHeaderCircuit`] as the step circuit.
+
+use core::error::Error;
+
+use ark_crypto_primitives::sponge::{poseidon::PoseidonSponge, Absorb};
+use ark_ec::{
+    short_weierstrass::{Projective, SWCurveConfig},
+    CurveConfig,
+};
+use ark_ff::PrimeField;
+use ark_serialize::CanonicalDeserialize;
+use ark_spartan::polycommitments::PolyCommitmentScheme;
+use ark_std::{boxed::Box, collections::VecDeque, vec, vec::Vec};
+use nexus_nova::{
+    circuits::nova::sequential::{compression::*, *},
+    commitment::CommitmentScheme,
+    poseidon_config,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::mmr::{HeaderMmr, MmrError, MmrProof};
+use crate::{BitcoinHeader, BitcoinHeaderCircuit, ChainParams, ChainState, Checkpoint, Network};
+
+pub type BitcoinPublicParams<G1, G2, C1, C2> = PublicParams<
+    G1,
+    G2,
+    C1,
+    C2,
+    PoseidonSponge<<G1 as CurveConfig>::ScalarField>,
+    BitcoinHeaderCircuit<<G1 as CurveConfig>::ScalarField>,
+>;
+
+pub type BitcoinIVCProof<G1, G2, C1, C2> = IVCProof<
+    G1,
+    G2,
+    C1,
+    C2,
+    PoseidonSponge<<G1 as CurveConfig>::ScalarField>,
+    BitcoinHeaderCircuit<<G1 as CurveConfig>::ScalarField>,
+>;
+
+pub type BitcoinSNARK<G1, G2, PC, C2> = SNARK<
+    G1,
+    G2,
+    PC,
+    C2,
+    PoseidonSponge<<G1 as CurveConfig>::ScalarField>,
+    BitcoinHeaderCircuit<<G1 as CurveConfig>::ScalarField>,
+>;
+
+pub type BitcoinCompressedProof<G1, G2, PC, C2> = CompressedProof<
+    G1,
+    G2,
+    PC,
+    C2,
+    PoseidonSponge<<G1 as CurveConfig>::ScalarField>,
+    BitcoinHeaderCircuit<<G1 as CurveConfig>::ScalarField>,
+>;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BitcoinIVCError {
+    #[error("header at height {0} does not link to the proven tip")]
+    BrokenLink(u32),
+    #[error("proving {0:?} is not supported, the block signatures are not verified in-circuit")]
+    UnsupportedNetwork(Network),
+    #[error("proof has no steps to verify")]
+    EmptyProof,
+    #[error("proof does not start from the checkpoint at height {0}")]
+    UnexpectedAnchor(u32),
+    #[error("headers do not fork from a block with a stored proof")]
+    NoStoredAncestor,
+    #[error("branch to height {0} does not have more work than the proven chain")]
+    NotMoreWork(u32),
+    #[error("proof segments do not link at step {0}")]
+    BrokenSegments(u64),
+    #[error("segment does not continue the chain at height {0}: {1} differs")]
+    SegmentMismatch(u32, &'static str),
+    #[error("segment from step {0} does not leave its first step open to merge on")]
+    UnmergeableSegment(u64),
+    #[error("proof does not deserialize")]
+    MalformedProof,
+    #[error("headers do not lead to the proven state at height {0}")]
+    ResumeMismatch(u32),
+}
+
+// number of proofs of the last blocks kept for reorgs
+pub const DEFAULT_RECENT_PROOFS: usize = 100;
+// height interval of the older proofs kept for reorgs
+pub const DEFAULT_PROOF_INTERVAL: u32 = 2016;
+// number of older proofs kept for reorgs, besides the proof of the anchor
+pub const DEFAULT_INTERVAL_PROOFS: usize = 16;
+
+// proof of the chain up to `state`, to roll back to in a reorg
+struct StoredProof<G1, G2, C1, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    C1: CommitmentScheme<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    proof: BitcoinIVCProof<G1, G2, C1, C2>,
+    state: ChainState,
+    num_steps: usize,
+}
+
+/// Where proofs of the header chain start from.
+///
+/// Whatever the anchor, the proven state carries the digest of the state the proof started from,
+/// see [`ChainState::find_checkpoint`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum Anchor {
+    /// z_0 is pinned to the genesis block of the network.
+    Genesis,
+    /// z_0 is pinned to a checkpoint of the network, e.g. one of [`Checkpoint::curated`].
+    Checkpoint(Checkpoint),
+    /// z_0 is not pinned, proving starts from the given checkpoint.
+    ///
+    /// For testing only: a proof verified against unanchored public parameters does not show
+    /// that the proven chain extends the real chain of the network, unless its anchor digest
+    /// is checked against trusted checkpoints.
+    Unanchored(Checkpoint),
+}
+
+impl Anchor {
+    pub fn checkpoint(&self, params: &ChainParams) -> Checkpoint {
+        match self {
+            Anchor::Genesis => Checkpoint::genesis(params),
+            Anchor::Checkpoint(checkpoint) | Anchor::Unanchored(checkpoint) => checkpoint.clone(),
+        }
+    }
+
+    /// State the first proven header is applied to.
+    pub fn initial_state<F: PrimeField + Absorb>(&self, params: &ChainParams) -> ChainState {
+        ChainState::from_checkpoint::<F>(params.network, &self.checkpoint(params))
+    }
+
+    /// State z_0 is pinned to by the public parameters, `None` if unanchored.
+    pub fn pinned_state<F: PrimeField + Absorb>(&self, params: &ChainParams) -> Option<ChainState> {
+        match self {
+            Anchor::Unanchored(_) => None,
+            anchor => Some(anchor.initial_state::<F>(params)),
+        }
+    }
+}
+
+/// Prover and verifier of the IVC proof of a header chain.
+///
+/// The public parameters are specific to the network and the anchor, so verifying with
+/// anchored parameters also guarantees the proven chain starts at the anchor.
+///
+/// `C1` commits to the witnesses on the primary curve, proofs can only be compressed if it is a
+/// polynomial commitment, see [`BitcoinIVC::compress`].
+pub struct BitcoinIVC<G1, G2, C1, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    C1: CommitmentScheme<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    chain_params: ChainParams,
+    anchor: Anchor,
+    params: BitcoinPublicParams<G1, G2, C1, C2>,
+    proof: BitcoinIVCProof<G1, G2, C1, C2>,
+    // state after the last proven header
+    state: ChainState,
+    // all nodes of the merkle mountain range accumulated in the state
+    mmr: HeaderMmr<G1::ScalarField>,
+    num_steps: usize,
+    // proofs of the last `max_recent` steps, the last one is the current proof
+    recent_proofs: VecDeque<StoredProof<G1, G2, C1, C2>>,
+    // proof of the anchor, followed by the last `max_interval` proofs at every `proof_interval`
+    // heights
+    interval_proofs: Vec<StoredProof<G1, G2, C1, C2>>,
+    max_recent: usize,
+    proof_interval: u32,
+    max_interval: usize,
+}
+
+impl<G1, G2, C1, C2> BitcoinIVC<G1, G2, C1, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    C1: CommitmentScheme<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    /// Sets up the public parameters for proving the chain of `chain_params` from `anchor`,
+    /// `setup_aux` is the SRS of a polynomial commitment and `()` for a Pedersen commitment.
+    pub fn setup(
+        chain_params: ChainParams,
+        anchor: Anchor,
+        setup_aux: &C1::SetupAux,
+    ) -> Result<Self, Box<dyn Error>> {
+        let params = setup_params(&chain_params, &anchor, setup_aux)?;
+        let state = anchor.initial_state::<G1::ScalarField>(&chain_params);
+        let proof = IVCProof::new(&state.to_field_elements());
+        let anchor_proof = StoredProof {
+            proof: proof.clone(),
+            state: state.clone(),
+            num_steps: 0,
+        };
+        Ok(BitcoinIVC {
+            chain_params,
+            anchor,
+            params,
+            proof,
+            state,
+            mmr: HeaderMmr::new(),
+            num_steps: 0,
+            recent_proofs: VecDeque::new(),
+            interval_proofs: vec![anchor_proof],
+            max_recent: DEFAULT_RECENT_PROOFS,
+            proof_interval: DEFAULT_PROOF_INTERVAL,
+            max_interval: DEFAULT_INTERVAL_PROOFS,
+        })
+    }
+
+    /// Resumes proving from a serialized uncompressed proof of `num_steps` steps, with the
+    /// headers it proves from the anchor on.
+    ///
+    /// The proof is verified and the merkle mountain range is rebuilt from `headers`, which must
+    /// lead to the proven state. Besides the anchor, the proof is the only stored proof, reorgs
+    /// forking below it fail with [`BitcoinIVCError::NoStoredAncestor`].
+    pub fn resume(
+        chain_params: ChainParams,
+        anchor: Anchor,
+        setup_aux: &C1::SetupAux,
+        proof: &[u8],
+        num_steps: usize,
+        headers: &[BitcoinHeader],
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut ivc = Self::setup(chain_params, anchor, setup_aux)?;
+        let proof = BitcoinIVCProof::<G1, G2, C1, C2>::deserialize_compressed(proof)
+            .map_err(|_| BitcoinIVCError::MalformedProof)?;
+        let proven = verify_proof(
+            &ivc.params,
+            &ivc.chain_params,
+            &ivc.anchor,
+            &proof,
+            num_steps,
+        )?;
+        for header in headers {
+            ivc.check_link(header)?;
+            let next_state = ivc.state.next::<G1::ScalarField>(&ivc.chain_params, header);
+            ivc.mmr.push(
+                next_state.height,
+                &next_state.tip_hash,
+                &next_state.chainwork,
+            )?;
+            ivc.state = next_state;
+        }
+        if ivc.state != proven || headers.len() != num_steps {
+            return Err(BitcoinIVCError::ResumeMismatch(proven.height).into());
+        }
+        ivc.proof = proof;
+        ivc.num_steps = num_steps;
+        ivc.store_proof();
+        Ok(ivc)
+    }
+
+    /// Keeps the proofs of the last `max_recent` blocks and of the last `max_interval` multiples
+    /// of `proof_interval`, to roll back to in reorgs, see [`BitcoinIVC::follow`]. The proof of
+    /// the anchor is always kept.
+    ///
+    /// Each stored proof is a full folded proof, i.e. up to `max_recent + max_interval + 1` of
+    /// them are held in memory besides the current one.
+    pub fn with_proof_history(
+        mut self,
+        max_recent: usize,
+        proof_interval: u32,
+        max_interval: usize,
+    ) -> Self {
+        self.max_recent = max_recent;
+        self.proof_interval = proof_interval.max(1);
+        self.max_interval = max_interval;
+        self
+    }
+
+    pub fn chain_params(&self) -> &ChainParams {
+        &self.chain_params
+    }
+
+    pub fn anchor(&self) -> &Anchor {
+        &self.anchor
+    }
+
+    pub fn params(&self) -> &BitcoinPublicParams<G1, G2, C1, C2> {
+        &self.params
+    }
+
+    pub fn proof(&self) -> &BitcoinIVCProof<G1, G2, C1, C2> {
+        &self.proof
+    }
+
+    /// State after the last proven header.
+    pub fn state(&self) -> &ChainState {
+        &self.state
+    }
+
+    pub fn num_steps(&self) -> usize {
+        self.num_steps
+    }
+
+    /// Merkle mountain range of the proven headers, its root is the root of the proven state.
+    pub fn mmr(&self) -> &HeaderMmr<G1::ScalarField> {
+        &self.mmr
+    }
+
+    /// Proves the proven header at `height` is part of the proven chain, the proof is checked
+    /// against the verified state with [`MmrProof::verify`].
+    pub fn prove_membership(&self, height: u32) -> Result<MmrProof, MmrError> {
+        self.mmr.prove(height)
+    }
+
+    fn step_circuit(&self, header: BitcoinHeader) -> BitcoinHeaderCircuit<G1::ScalarField> {
+        step_circuit(&self.chain_params, &self.anchor, header)
+    }
+
+    fn check_link(&self, header: &BitcoinHeader) -> Result<(), BitcoinIVCError> {
+        check_link(&self.state, header)
+    }
+
+    fn prove_step(
+        &mut self,
+        circuit: BitcoinHeaderCircuit<G1::ScalarField>,
+    ) -> Result<(), Box<dyn Error>> {
+        let next_state = self
+            .state
+            .next::<G1::ScalarField>(&self.chain_params, &circuit.header);
+        self.proof = self.proof.clone().prove_step(&self.params, &circuit)?;
+        self.mmr.push(
+            next_state.height,
+            &next_state.tip_hash,
+            &next_state.chainwork,
+        )?;
+        self.state = next_state;
+        self.num_steps += 1;
+        self.store_proof();
+        Ok(())
+    }
+
+    fn store_proof(&mut self) {
+        let stored = || StoredProof {
+            proof: self.proof.clone(),
+            state: self.state.clone(),
+            num_steps: self.num_steps,
+        };
+        if self.state.height.is_multiple_of(self.proof_interval) && self.max_interval > 0 {
+            // the anchor proof at index 0 is kept, the oldest interval proof is dropped
+            if self.interval_proofs.len() > self.max_interval {
+                self.interval_proofs.remove(1);
+            }
+            self.interval_proofs.push(stored());
+        }
+        if self.max_recent > 0 {
+            if self.recent_proofs.len() == self.max_recent {
+                self.recent_proofs.pop_front();
+            }
+            self.recent_proofs.push_back(stored());
+        }
+    }
+
+    /// Most recent stored proof of a block of `hashes`, with the index of the block in `hashes`.
+    fn find_stored_proof(
+        &self,
+        hashes: &[Vec<u8>],
+    ) -> Option<(usize, &StoredProof<G1, G2, C1, C2>)> {
+        self.recent_proofs
+            .iter()
+            .chain(&self.interval_proofs)
+            .filter_map(|stored| {
+                let position = hashes
+                    .iter()
+                    .position(|hash| *hash == stored.state.tip_hash)?;
+                Some((position, stored))
+            })
+            .max_by_key(|(_, stored)| stored.num_steps)
+    }
+
+    /// Rolls back to the stored proof after `num_steps` steps, dropping the later proofs.
+    fn roll_back(&mut self, num_steps: usize) {
+        let stored = self
+            .recent_proofs
+            .iter()
+            .chain(&self.interval_proofs)
+            .find(|stored| stored.num_steps == num_steps)
+            .expect("stored proof");
+        self.proof = stored.proof.clone();
+        self.state = stored.state.clone();
+        self.num_steps = num_steps;
+        self.mmr.truncate(self.state.mmr_num_leaves);
+        self.recent_proofs
+            .retain(|stored| stored.num_steps <= num_steps);
+        self.interval_proofs
+            .retain(|stored| stored.num_steps <= num_steps);
+    }
+
+    /// Proves `headers` on top of the proven tip, one step per header.
+    pub fn extend(&mut self, headers: &[BitcoinHeader]) -> Result<(), Box<dyn Error>> {
+        for header in headers {
+            self.check_link(header)?;
+            self.prove_step(self.step_circuit(header.clone()))?;
+        }
+        Ok(())
+    }
+
+    /// Proves `headers` of the chain followed from a source, rolling back reorged blocks, and
+    /// returns the number of rolled back blocks.
+    ///
+    /// If the headers do not link to the proven tip, proving restarts from the most recent
+    /// stored proof of their parent or of one of them, the branch must have more work than the
+    /// proven chain. Reorgs deeper than the stored proofs fail with
+    /// [`BitcoinIVCError::NoStoredAncestor`]. The branch is proven before the proven chain is
+    /// rolled back, if it fails to prove the proven chain is kept.
+    pub fn follow(&mut self, headers: &[BitcoinHeader]) -> Result<u32, Box<dyn Error>> {
+        let Some(first) = headers.first() else {
+            return Ok(0);
+        };
+        if first.hash_prev_block == self.state.tip_hash {
+            self.extend(headers)?;
+            return Ok(0);
+        }
+
+        // the parent of the headers, then the headers
+        let hashes: Vec<Vec<u8>> = core::iter::once(first.hash_prev_block.clone())
+            .chain(headers.iter().map(|header| header.hash()))
+            .collect();
+        let (position, stored) = self
+            .find_stored_proof(&hashes)
+            .ok_or(BitcoinIVCError::NoStoredAncestor)?;
+        let branch = &headers[position..];
+        let branch_tip = branch.iter().fold(stored.state.clone(), |state, header| {
+            state.next::<G1::ScalarField>(&self.chain_params, header)
+        });
+        // headers of the proven chain are ignored
+        if branch_tip.tip_hash == self.state.tip_hash {
+            return Ok(0);
+        }
+        if branch_tip.chainwork <= self.state.chainwork {
+            return Err(BitcoinIVCError::NotMoreWork(branch_tip.height).into());
+        }
+
+        // only the proofs to be stored and the last proof are kept, see `store_proof`
+        let mut proof = stored.proof.clone();
+        let mut state = stored.state.clone();
+        let mut proven = Vec::with_capacity(branch.len());
+        for (i, header) in branch.iter().enumerate() {
+            check_link(&state, header)?;
+            proof = proof.prove_step(&self.params, &self.step_circuit(header.clone()))?;
+            state = state.next::<G1::ScalarField>(&self.chain_params, header);
+            let is_stored = i + self.max_recent.max(1) >= branch.len()
+                || state.height.is_multiple_of(self.proof_interval);
+            proven.push((state.clone(), is_stored.then(|| proof.clone())));
+        }
+
+        let rolled_back = self.state.height - stored.state.height;
+        let num_steps = stored.num_steps;
+        self.roll_back(num_steps);
+        for (state, proof) in proven {
+            self.mmr
+                .push(state.height, &state.tip_hash, &state.chainwork)?;
+            self.state = state;
+            self.num_steps += 1;
+            if let Some(proof) = proof {
+                self.proof = proof;
+                self.store_proof();
+            }
+        }
+        Ok(rolled_back)
+    }
+
+    /// Verifies the proof against the public parameters and returns the proven state.
+    ///
+    /// The anchor digest of the proven state must match the checkpoint of the anchor, which
+    /// the verifier can then also look up with [`ChainState::find_checkpoint`].
+    pub fn verify(&self) -> Result<ChainState, Box<dyn Error>> {
+        verify_proof(
+            &self.params,
+            &self.chain_params,
+            &self.anchor,
+            &self.proof,
+            self.num_steps,
+        )
+    }
+}
+
+impl<G1, G2, PC, C2> BitcoinIVC<G1, G2, PVC<G1, PC>, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    PC: PolyCommitmentScheme<Projective<G1>>,
+    PC::Commitment: Copy + Into<Projective<G1>> + From<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    /// Compresses the proof into a SNARK, verified with [`verify_compressed`].
+    pub fn compress(
+        &self,
+        key: &SNARKKey<G1, PC>,
+    ) -> Result<BitcoinCompressedProof<G1, G2, PC, C2>, Box<dyn Error>> {
+        if self.num_steps == 0 {
+            return Err(BitcoinIVCError::EmptyProof.into());
+        }
+        Ok(BitcoinSNARK::<G1, G2, PC, C2>::compress(
+            &self.params,
+            key,
+            self.proof.clone(),
+        )?)
+    }
+}
+
+/// Sets up the public parameters for proving the chain of `chain_params` from `anchor`, see
+/// [`BitcoinIVC::setup`].
+pub fn setup_params<G1, G2, C1, C2>(
+    chain_params: &ChainParams,
+    anchor: &Anchor,
+    setup_aux: &C1::SetupAux,
+) -> Result<BitcoinPublicParams<G1, G2, C1, C2>, Box<dyn Error>>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    C1: CommitmentScheme<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    check_network(chain_params)?;
+    let step_circuit = BitcoinHeaderCircuit::setup(chain_params.clone())
+        .with_anchor(anchor.pinned_state::<G1::ScalarField>(chain_params));
+    Ok(BitcoinPublicParams::<G1, G2, C1, C2>::setup(
+        poseidon_config(),
+        &step_circuit,
+        setup_aux,
+        &(),
+    )?)
+}
+
+/// Verifies a proof of `num_steps` steps of the chain of `chain_params` from `anchor` and returns
+/// the proven state, see [`BitcoinIVC::verify`].
+pub fn verify_proof<G1, G2, C1, C2>(
+    params: &BitcoinPublicParams<G1, G2, C1, C2>,
+    chain_params: &ChainParams,
+    anchor: &Anchor,
+    proof: &BitcoinIVCProof<G1, G2, C1, C2>,
+    num_steps: usize,
+) -> Result<ChainState, Box<dyn Error>>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    C1: CommitmentScheme<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    if num_steps == 0 {
+        return Err(BitcoinIVCError::EmptyProof.into());
+    }
+    proof.verify(params, num_steps)?;
+    proven_state::<G1::ScalarField>(&proof.z_i()[..], chain_params, anchor)
+}
+
+/// Extends a serialized uncompressed proof of `num_steps` steps by `headers`, one step per
+/// header, and returns the proof of `num_steps + headers.len()` steps.
+///
+/// The proof is verified first, the first header must link to the tip hash of its `z_i`. Only
+/// the proof is extended: the stored proofs and the merkle mountain range of [`BitcoinIVC`]
+/// are not rebuilt from it.
+pub fn extend_proof<G1, G2, C1, C2>(
+    params: &BitcoinPublicParams<G1, G2, C1, C2>,
+    chain_params: &ChainParams,
+    anchor: &Anchor,
+    proof: &[u8],
+    num_steps: usize,
+    headers: &[BitcoinHeader],
+) -> Result<BitcoinIVCProof<G1, G2, C1, C2>, Box<dyn Error>>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    C1: CommitmentScheme<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    let mut proof = BitcoinIVCProof::<G1, G2, C1, C2>::deserialize_compressed(proof)
+        .map_err(|_| BitcoinIVCError::MalformedProof)?;
+    let mut state = verify_proof(params, chain_params, anchor, &proof, num_steps)?;
+    for header in headers {
+        check_link(&state, header)?;
+        let circuit = step_circuit::<G1::ScalarField>(chain_params, anchor, header.clone());
+        proof = proof.prove_step(params, &circuit)?;
+        state = state.next::<G1::ScalarField>(chain_params, header);
+    }
+    Ok(proof)
+}
+
+fn step_circuit<F: PrimeField + Absorb>(
+    chain_params: &ChainParams,
+    anchor: &Anchor,
+    header: BitcoinHeader,
+) -> BitcoinHeaderCircuit<F> {
+    BitcoinHeaderCircuit::new(header, chain_params.clone())
+        .with_anchor(anchor.pinned_state::<F>(chain_params))
+}
+
+fn check_link(state: &ChainState, header: &BitcoinHeader) -> Result<(), BitcoinIVCError> {
+    if header.hash_prev_block != state.tip_hash {
+        return Err(BitcoinIVCError::BrokenLink(state.height + 1));
+    }
+    Ok(())
+}
+
+// ToDo: verify the ECDSA signet solution in-circuit, until then a signet proof would only attest
+// to the proof of work, which the signet challenge replaces as the consensus rule
+pub(crate) fn check_network(chain_params: &ChainParams) -> Result<(), BitcoinIVCError> {
+    if chain_params.network == Network::Signet {
+        return Err(BitcoinIVCError::UnsupportedNetwork(Network::Signet));
+    }
+    Ok(())
+}
+
+/// Verifies a compressed proof of the chain of `chain_params` from `anchor` and returns the
+/// proven state, as [`BitcoinIVC::verify`] does for the uncompressed proof.
+pub fn verify_compressed<G1, G2, PC, C2>(
+    key: &SNARKKey<G1, PC>,
+    params: &BitcoinPublicParams<G1, G2, PVC<G1, PC>, C2>,
+    chain_params: &ChainParams,
+    anchor: &Anchor,
+    proof: &BitcoinCompressedProof<G1, G2, PC, C2>,
+) -> Result<ChainState, Box<dyn Error>>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    PC: PolyCommitmentScheme<Projective<G1>>,
+    PC::Commitment: Copy + Into<Projective<G1>> + From<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    BitcoinSNARK::<G1, G2, PC, C2>::verify(key, params, proof)?;
+    proven_state::<G1::ScalarField>(&proof.z_i, chain_params, anchor)
+}
+
+// state of a verified z_i, which must start from the checkpoint of `anchor`
+pub(crate) fn proven_state<F: PrimeField + Absorb>(
+    z_i: &[F],
+    chain_params: &ChainParams,
+    anchor: &Anchor,
+) -> Result<ChainState, Box<dyn Error>> {
+    let state = ChainState::from_field_elements(z_i)?;
+    let checkpoint = anchor.checkpoint(chain_params);
+    if state
+        .find_checkpoint::<F>(core::slice::from_ref(&checkpoint))
+        .is_none()
+    {
+        return Err(BitcoinIVCError::UnexpectedAnchor(checkpoint.height).into());
+    }
+    Ok(state)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::bitcoin_fold_tests::setup_srs;
+    use crate::test_utils::mine_regtest_headers;
+    use crate::{Bn254GrumpkinIVC, PallasVestaIVC};
+    use ark_bn254::{g1::Config as Bn254Config, Bn254, Fr};
+    use ark_spartan::polycommitments::zeromorph::Zeromorph;
+
+    #[test]
+    fn follow_reorg() {
+        let chain_params = ChainParams::regtest();
+        let chain = mine_regtest_headers(&chain_params.genesis, 4, 1);
+        // branch of one more block from block 1, reorging out the 3 last blocks of the chain
+        let branch = mine_regtest_headers(&chain[0], 4, 2);
+
+        let srs = setup_srs::<Bn254Config, Zeromorph<Bn254>>();
+        let mut ivc = Bn254GrumpkinIVC::setup(chain_params.clone(), Anchor::Genesis, &srs).unwrap();
+        assert_eq!(ivc.follow(&chain).unwrap(), 0);
+        assert_eq!(ivc.state().height, 4);
+
+        // a branch with more work that breaks partway keeps the proven chain
+        let mut broken = branch.clone();
+        broken[2].hash_prev_block[0] ^= 1;
+        let error = ivc.follow(&broken).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BitcoinIVCError>(),
+            Some(&BitcoinIVCError::BrokenLink(4))
+        );
+        assert_eq!(ivc.state().tip_hash, chain[3].hash());
+        assert_eq!(ivc.verify().unwrap().height, 4);
+
+        assert_eq!(ivc.follow(&branch).unwrap(), 3);
+        let state = ivc.verify().unwrap();
+        assert_eq!(state.height, 5);
+        assert_eq!(state.tip_hash, branch[3].hash());
+        let expected_state = chain[..1].iter().chain(&branch).fold(
+            Anchor::Genesis.initial_state::<Fr>(&chain_params),
+            |state, header| state.next::<Fr>(&chain_params, header),
+        );
+        assert_eq!(state, expected_state);
+        let proof = ivc.prove_membership(4).unwrap();
+        assert_eq!(proof.verify::<Fr>(&state, &branch[2].hash()), Ok(()));
+
+        // the old chain has less work, and unknown branches do not fork from a stored proof
+        let error = ivc.follow(&chain[1..]).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BitcoinIVCError>(),
+            Some(&BitcoinIVCError::NotMoreWork(4))
+        );
+        let unknown = mine_regtest_headers(&chain_params.genesis, 2, 3);
+        let error = ivc.follow(&unknown[1..]).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BitcoinIVCError>(),
+            Some(&BitcoinIVCError::NoStoredAncestor)
+        );
+        assert_eq!(ivc.state(), &expected_state);
+    }
+
+    #[test]
+    fn interval_proofs_are_capped() {
+        let chain_params = ChainParams::regtest();
+        let headers = mine_regtest_headers(&chain_params.genesis, 4, 1);
+
+        // a proof at every height, of which the last 2 are kept besides the anchor proof
+        let mut ivc = PallasVestaIVC::setup(chain_params, Anchor::Genesis, &())
+            .unwrap()
+            .with_proof_history(0, 1, 2);
+        ivc.extend(&headers).unwrap();
+        let heights: Vec<u32> = ivc
+            .interval_proofs
+            .iter()
+            .map(|stored| stored.state.height)
+            .collect();
+        assert_eq!(heights, [0, 3, 4]);
+        assert!(ivc.recent_proofs.is_empty());
+    }
+}
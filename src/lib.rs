@@ -1,16 +1,116 @@
+// the verifier path builds without std, see the `std` feature
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
 const NOVA_TARGET: &str = "layerX::bitfold";
 
 // for profiling
+#[cfg(feature = "std")]
 use std::time::Instant;
 
-use std::marker::PhantomData;
+use core::marker::PhantomData;
 
 mod bitcoin;
-pub use bitcoin::BitcoinHeader;
-use bitcoin::BlockReader;
+pub use bitcoin::tx::{self, Transaction};
+pub use bitcoin::{
+    BitcoinHeader, ChainParams, Checkpoint, HeaderError, HeaderValidator, Network, ValidationError,
+    HEADER_LEN,
+};
+#[cfg(feature = "std")]
+pub use bitcoin::{BlockReader, BlockReaderError};
+
+#[cfg(feature = "std")]
+mod compression;
+#[cfg(feature = "std")]
+pub use compression::{
+    measure_compression, Compression, CompressionBackend, CompressionReport, NoCompression,
+    SpartanCompression, UncompressedProof,
+};
+
+#[cfg(feature = "std")]
+mod cycle;
+#[cfg(feature = "std")]
+pub use cycle::{
+    Bn254GrumpkinIVC, CurveCycle, CycleIVC, CycleSetup, PallasVestaIVC, ZeromorphSNARKKey,
+    ZeromorphSRS,
+};
+
+#[cfg(feature = "server")]
+mod daemon;
+#[cfg(feature = "server")]
+pub use daemon::{
+    resume_from_checkpoints, DaemonError, HeaderSource, JsonFileSource, ProverMetrics,
+    ProvingDaemon,
+};
+
+#[cfg(feature = "std")]
+mod fork;
+#[cfg(feature = "std")]
+pub use fork::{
+    choose_fork, compare_proofs, ChainProof, CompressedChainProof, Fork, ForkChoice,
+    ForkChoiceError, ForkChoiceReason,
+};
+
+mod gadgets;
+use gadgets::U256Var;
+
+#[cfg(feature = "std")]
+mod inclusion;
+#[cfg(feature = "std")]
+pub use inclusion::{
+    txid_commitment, PaymentClaim, TxInclusionCircuit, TxInclusionSNARK, TxInclusionSNARKError,
+    TxInclusionStatement, TxidClaim, MAX_OUTPUT_SCRIPT_LEN, MAX_PAYMENT_INPUTS,
+    MAX_PAYMENT_OUTPUTS, MAX_TX_BLOCKS, MAX_TX_MERKLE_DEPTH,
+};
+
+mod ivc;
+pub use ivc::{
+    extend_proof, setup_params, verify_compressed, verify_proof, Anchor, BitcoinCompressedProof,
+    BitcoinIVC, BitcoinIVCError, BitcoinIVCProof, BitcoinPublicParams, BitcoinSNARK,
+    DEFAULT_INTERVAL_PROOFS, DEFAULT_PROOF_INTERVAL, DEFAULT_RECENT_PROOFS,
+};
+
+mod mmr;
+pub use mmr::{Burial, HeaderMmr, MmrError, MmrProof, MAX_MMR_PEAKS};
+
+#[cfg(feature = "std")]
+mod pcd;
+#[cfg(feature = "std")]
+pub use pcd::{BitcoinPCD, BitcoinPCDNode, BitcoinPCDParams, BitcoinPCDProof};
+
+#[cfg(feature = "std")]
+mod proof_file;
+#[cfg(feature = "std")]
+pub use proof_file::{ProofFile, ProofFileError, PROOF_FILE_VERSION};
+
+#[cfg(feature = "server")]
+mod server;
+#[cfg(feature = "server")]
+pub use server::{
+    verify_proof_file, ProofServer, ProofStore, ServerError, ServerHandle, ServerStatus,
+};
+
+#[cfg(feature = "std")]
+mod spv;
+#[cfg(feature = "std")]
+pub use spv::{InclusionError, TxInclusionProof};
+
+mod state;
+pub use state::{ChainState, StateError};
+
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+
+#[cfg(feature = "verifier")]
+mod verifier;
+#[cfg(feature = "verifier")]
+pub use verifier::{
+    Bn254GrumpkinCompressedProof, Bn254GrumpkinVerifierKey, VerifiedChainState, VerifierError,
+    VerifierKey,
+};
 
 // nova
 use ark_spartan::polycommitments::{zeromorph::Zeromorph, PolyCommitmentScheme};
+use core::error::Error;
 use nexus_nova::{
     circuits::nova::{
         sequential::{compression::*, *},
@@ -20,7 +120,6 @@ use nexus_nova::{
     pedersen::PedersenCommitment,
     poseidon_config,
 };
-use std::error::Error;
 
 // ark
 use ark_crypto_primitives::{
@@ -50,6 +149,7 @@ use ark_r1cs_std::{
 };
 use ark_relations::r1cs::{ConstraintSystem, ConstraintSystemRef, SynthesisError, SynthesisMode};
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use ark_std::{vec, vec::Vec};
 
 // ToDo: replace with a production ready crypto-rng
 use ark_std::test_rng;
@@ -57,23 +157,182 @@ use ark_std::test_rng;
 use ark_bn254::{g1::Config as Bn254Config, Bn254};
 use ark_grumpkin::{GrumpkinConfig, Projective as GrumpkinProjective};
 
+#[cfg(feature = "std")]
 use tracing_subscriber::{
     filter, fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt,
 };
 #[derive(Debug, Default)]
 pub struct BitcoinHeaderCircuit<F: Field> {
     header: BitcoinHeader,
+    params: ChainParams,
+    // state z_0 is pinned to, if the circuit is anchored
+    anchor: Option<ChainState>,
     _p: PhantomData<F>,
 }
 
+impl<F: Field> BitcoinHeaderCircuit<F> {
+    /// Circuit with a placeholder header, used to set up the public parameters for a network.
+    pub fn setup(params: ChainParams) -> Self {
+        Self::new(BitcoinHeader::default(), params)
+    }
+
+    pub fn new(header: BitcoinHeader, params: ChainParams) -> Self {
+        BitcoinHeaderCircuit {
+            header,
+            params,
+            anchor: None,
+            _p: PhantomData,
+        }
+    }
+
+    /// Pins z_0 to `anchor`, see [`Anchor`].
+    pub fn with_anchor(mut self, anchor: Option<ChainState>) -> Self {
+        self.anchor = anchor;
+        self
+    }
+}
+
+impl<F: PrimeField> BitcoinHeaderCircuit<F> {
+    /// Enforces the header bits to follow the difficulty adjustment rules of the network, and
+    /// returns the bits required for the next block, to be passed in z_i.
+    ///
+    /// Networks that allow min-difficulty blocks (testnet3, testnet4) synthesize the testnet
+    /// variant of the rules: a block more than 2 * target spacing later than its predecessor must
+    /// use the pow limit, and min-difficulty blocks are skipped when looking back for the bits of
+    /// the next block. Networks enforcing BIP94 (testnet4) also bound how much earlier than its
+    /// predecessor the first block of a period may be, see [`HeaderValidator::check_timewarp`].
+    ///
+    /// ref code: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/pow.cpp#L14
+    fn generate_difficulty_constraints(
+        &self,
+        z: &[FpVar<F>],
+        is_retarget: &Boolean<F>,
+        timestamp: &FpVar<F>,
+        bits: &FpVar<F>,
+    ) -> Result<FpVar<F>, SynthesisError> {
+        if self.params.enforce_bip94 {
+            let is_warped = gadgets::is_less_than(
+                &(timestamp + F::from(bitcoin::MAX_TIMEWARP)),
+                &z[state::TIMESTAMP],
+                33,
+            )?;
+            is_retarget
+                .and(&is_warped)?
+                .enforce_equal(&Boolean::FALSE)?;
+        }
+
+        let last_bits = &z[state::BITS];
+        let retarget_bits = self.generate_retarget_constraints(z)?;
+
+        if !self.params.pow_allow_min_difficulty_blocks {
+            let expected_bits = match &retarget_bits {
+                Some(retarget_bits) => is_retarget.select(retarget_bits, last_bits)?,
+                None => last_bits.clone(),
+            };
+            bits.enforce_equal(&expected_bits)?;
+            return Ok(bits.clone());
+        }
+
+        let min_difficulty_time =
+            &z[state::TIMESTAMP] + F::from(self.params.pow_target_spacing * 2);
+        let is_late = gadgets::is_less_than(&min_difficulty_time, timestamp, 33)?;
+        let pow_limit_bits = FpVar::constant(F::from(self.params.pow_limit_bits));
+        let mut expected_bits = is_late.select(&pow_limit_bits, last_bits)?;
+        if let Some(retarget_bits) = &retarget_bits {
+            expected_bits = is_retarget.select(retarget_bits, &expected_bits)?;
+        }
+        bits.enforce_equal(&expected_bits)?;
+
+        let is_min_difficulty = bits.is_eq(&pow_limit_bits)?;
+        is_retarget
+            .or(&is_min_difficulty.not())?
+            .select(bits, last_bits)
+    }
+
+    /// Returns the bits of the first block of a difficulty adjustment period, retargeted at the end
+    /// of the period of the last block in z_{i-1}, or `None` if the network does not retarget.
+    ///
+    /// ToDo: the retarget is synthesized in every step, though only the first block of a period
+    /// needs it. Splitting the step circuit into a plain and a retarget circuit needs a
+    /// non-uniform folding scheme, Nova folds a single step circuit.
+    ///
+    /// ref code: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/pow.cpp#L49
+    fn generate_retarget_constraints(
+        &self,
+        z: &[FpVar<F>],
+    ) -> Result<Option<FpVar<F>>, SynthesisError> {
+        if self.params.pow_no_retargeting {
+            return Ok(None);
+        }
+
+        // BIP94 retargets from the bits of the first block of the period, which are the last bits not
+        // at min-difficulty in z_{i-1}, otherwise the bits of the last block are used, which is at
+        // min-difficulty iff it was late
+        let last_timestamp = &z[state::TIMESTAMP];
+        let mut last_bits = z[state::BITS].clone();
+        if self.params.pow_allow_min_difficulty_blocks && !self.params.enforce_bip94 {
+            let min_difficulty_time =
+                &z[state::TIMESTAMP - 1] + F::from(self.params.pow_target_spacing * 2);
+            let was_late = gadgets::is_less_than(&min_difficulty_time, last_timestamp, 33)?;
+            let pow_limit_bits = FpVar::constant(F::from(self.params.pow_limit_bits));
+            last_bits = was_late.select(&pow_limit_bits, &last_bits)?;
+        }
+        let last_bits = gadgets::to_bits_le_with_length(&last_bits, 32)?
+            .chunks(8)
+            .map(UInt8::from_bits_le)
+            .collect::<Vec<_>>();
+
+        let target = U256Var::from_compact(&last_bits)?.retarget(
+            &z[state::PERIOD_START_TIMESTAMP],
+            last_timestamp,
+            self.params.pow_target_timespan,
+            &U256Var::constant(&self.params.pow_limit),
+        )?;
+        gadgets::le_bytes_to_fp(&target.to_compact()?).map(Some)
+    }
+
+    /// Enforces the header timestamp to be later than the median time past, i.e. than the median of
+    /// the timestamps of the last 11 blocks in z_{i-1}.
+    ///
+    /// ref code: https://github.com/bitcoin/bitcoin/blob/b5d21182e5a66110ce2796c2c99da39c8ebf0d72/src/validation.cpp#L4137
+    fn generate_median_time_past_constraints(
+        &self,
+        z: &[FpVar<F>],
+        timestamp: &FpVar<F>,
+    ) -> Result<(), SynthesisError> {
+        // the timestamp is above the median iff it is above at least 6 of the 11 timestamps
+        let mut num_earlier = FpVar::zero();
+        for window_timestamp in &z[state::MTP_WINDOW] {
+            let is_earlier = gadgets::is_less_than(window_timestamp, timestamp, 32)?;
+            num_earlier += FpVar::from(is_earlier);
+        }
+        let half = FpVar::constant(F::from((state::MTP_WINDOW.len() / 2) as u64));
+        gadgets::is_less_than(&half, &num_earlier, 4)?.enforce_equal(&Boolean::TRUE)
+    }
+}
+
 impl<F: PrimeField> StepCircuit<F> for BitcoinHeaderCircuit<F> {
-    const ARITY: usize = 32;
+    const ARITY: usize = state::STATE_LEN;
     fn generate_constraints(
         &self,
         cs: ConstraintSystemRef<F>,
         i: &FpVar<F>,
         z: &[FpVar<F>],
     ) -> Result<Vec<FpVar<F>>, SynthesisError> {
+        // in anchored mode the first step must start from the anchor, since the anchor is a constant
+        // of the constraint system this binds it into the public parameters
+        let is_first_step = i.is_zero()?;
+        if let Some(anchor) = &self.anchor {
+            for (z, anchor) in z.iter().zip(anchor.to_field_elements::<F>()) {
+                z.conditional_enforce_equal(&FpVar::constant(anchor), &is_first_step)?;
+            }
+        }
+
+        // z_0 carries its own digest, which is passed on unchanged, so that z_i identifies the state
+        // the proof started from, e.g. a checkpoint
+        let anchor_digest = gadgets::poseidon_hash(cs.clone(), &z[..state::ANCHOR])?;
+        z[state::ANCHOR].conditional_enforce_equal(&anchor_digest, &is_first_step)?;
+
         // serialize the header to bytes
         let header_le_bytes = self.header.to_bytes();
 
@@ -86,9 +345,14 @@ impl<F: PrimeField> StepCircuit<F> for BitcoinHeaderCircuit<F> {
         // variables for previous block hash extracted from current block header
         let header_previous_hash = &allocated_header_bytes[4..36];
 
+        // enforce the network id passed in z_{i-1} to be the network of the circuit, since the id is
+        // a constant of the constraint system this also binds the network into the public parameters.
+        let network_id = &z[state::NETWORK_ID];
+        network_id.enforce_equal(&FpVar::constant(F::from(self.params.network.id())))?;
+
         // variables for previous block hash from IVC input (passed as IVC input z_{i-1})
         let mut input_previous_hash: Vec<UInt8<F>> = Vec::new();
-        for fp in z {
+        for fp in &z[state::TIP_HASH] {
             let bytes = fp.to_bytes()?;
             // take the 1st byte since the signature bytes are serialized in z in little-endian
             input_previous_hash.push(bytes[0].clone());
@@ -97,7 +361,23 @@ impl<F: PrimeField> StepCircuit<F> for BitcoinHeaderCircuit<F> {
         // enforce the previous block hash from block header to be equal with hash that is passed in input z_{i-1}.
         header_previous_hash.enforce_equal(&input_previous_hash)?;
 
-        // ToDo: enforce the block hash is under target_bits difficulty
+        // the header extends the chain in z_{i-1} by one block
+        let height = &z[state::HEIGHT] + F::from(1u64);
+        let timestamp = gadgets::le_bytes_to_fp(&allocated_header_bytes[68..72])?;
+        let bits_bytes = &allocated_header_bytes[72..76];
+        let bits = gadgets::le_bytes_to_fp(bits_bytes)?;
+
+        // a difficulty adjustment period starts at each retarget
+        let is_retarget = if self.params.pow_no_retargeting {
+            Boolean::FALSE
+        } else {
+            gadgets::is_multiple_of(&height, self.params.difficulty_adjustment_interval())?
+        };
+        let period_start_timestamp =
+            is_retarget.select(&timestamp, &z[state::PERIOD_START_TIMESTAMP])?;
+
+        let next_bits = self.generate_difficulty_constraints(z, &is_retarget, &timestamp, &bits)?;
+        self.generate_median_time_past_constraints(z, &timestamp)?;
 
         // calculate and allocate block hash (bitcoin does double sha256 hash as sha256(sha256(header)) a.k.a sha256d)
         let header_digest = <Sha256Gadget<F> as CRHSchemeGadget<Sha256, F>>::evaluate(
@@ -109,14 +389,46 @@ impl<F: PrimeField> StepCircuit<F> for BitcoinHeaderCircuit<F> {
             &header_digest.0,
         )?;
 
+        // enforce the block hash is under target_bits difficulty, and the target is not above the pow limit
+        let target = U256Var::from_compact(bits_bytes)?;
+        target.enforce_le(&U256Var::constant(&self.params.pow_limit))?;
+        U256Var::from_le_bytes(&digest_digest.0)?.enforce_le(&target)?;
+
+        // accumulate the work of the header
+        let chainwork = U256Var {
+            lo: z[state::CHAINWORK.start].clone(),
+            hi: z[state::CHAINWORK.start + 1].clone(),
+        }
+        .add(&target.block_work()?)?;
+
+        // accumulate the block hash for membership proofs of older blocks
+        let (mmr_num_leaves, mmr_peaks) = gadgets::mmr_append(
+            cs.clone(),
+            &z[state::MMR_NUM_LEAVES],
+            &z[state::MMR_PEAKS],
+            &height,
+            &digest_digest.0,
+            &[chainwork.lo.clone(), chainwork.hi.clone()],
+        )?;
+
         // convert digest to FpVar for z_out
-        let mut z_out: Vec<FpVar<F>> = Vec::new();
+        let mut z_out: Vec<FpVar<F>> = vec![network_id.clone()];
         for byte in digest_digest.0 {
             // convert to FpVar for output
             // ToDo: find a better solution to convert UInt8 bytes directly to FpVar with no intermediate bit conversion
             let fp_var = Boolean::le_bits_to_fp_var(&byte.to_bits_le()?)?;
             z_out.push(fp_var);
         }
+        z_out.push(height);
+        z_out.push(next_bits);
+        z_out.push(chainwork.lo);
+        z_out.push(chainwork.hi);
+        z_out.push(period_start_timestamp);
+        z_out.extend_from_slice(&z[state::MTP_WINDOW.start + 1..state::MTP_WINDOW.end]);
+        z_out.push(timestamp);
+        z_out.push(mmr_num_leaves);
+        z_out.extend(mmr_peaks);
+        z_out.push(z[state::ANCHOR].clone());
 
         Ok(z_out)
     }
@@ -158,89 +470,40 @@ where
     }
 }*/
 
-/*pub struct BitcoinIVC<G1, G2, C1, C2, RO, SC>
-where
-    G1: SWCurveConfig,
-    G2: SWCurveConfig,
-    C1: CommitmentScheme<Projective<G1>>,
-    C2: CommitmentScheme<Projective<G2>>,
-    RO: CryptographicSponge + Send + Sync,
-    RO::Config: CanonicalSerialize + CanonicalDeserialize + Sync,
-    SC: StepCircuit<G1::ScalarField>,
-{
-    initial_height: usize,
-    height: usize,
-    params: PublicParams<G1, G2, C1, C2, RO, SC>,
-    proof: IVCProof<G1, G2, C1, C2, RO, SC>,
-}
-
-impl BitcoinIVC<G1, G2, C1, C2, RO, SC> {
-    pub fn setup(self) -> Self {
-        self
-    }
-}*/
-
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 pub(crate) mod bitcoin_fold_tests {
     use super::*;
-    use crate::bitcoin::data::test_json::TEST_JSON_RPC;
-    use crate::bitcoin::BlockReader;
-    use ark_crypto_primitives::crh::CRHScheme;
+    use crate::bitcoin::data::test_json::{
+        min_difficulty_test_params, TEST_JSON_RPC, TEST_JSON_RPC_GENESIS,
+        TEST_JSON_RPC_MIN_DIFFICULTY,
+    };
+    use crate::bitcoin::{pow, BlockReader};
+    use crate::test_utils::mine_regtest_headers;
+    use ark_bn254::Fr;
+    use ark_ff::BigInteger;
     use nexus_nova::circuits;
 
-    fn setup_srs_params<G1, G2, PC, C2, RO, SC>(
-        ro_config: RO::Config,
-        step_circuit: &SC,
-    ) -> (PC::SRS, PublicParams<G1, G2, PVC<G1, PC>, C2, RO, SC>)
+    pub(crate) fn setup_srs<G1, PC>() -> PC::SRS
     where
         G1: SWCurveConfig,
-        G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
-        G1::BaseField: PrimeField + Absorb,
-        G2::BaseField: PrimeField + Absorb,
         PC: PolyCommitmentScheme<Projective<G1>>,
-        PC::Commitment: Copy + Into<Projective<G1>> + From<Projective<G1>>,
-        C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
-        RO: SpongeWithGadget<G1::ScalarField> + Send + Sync,
-        RO::Var: CryptographicSpongeVar<G1::ScalarField, RO, Parameters = RO::Config>,
-        RO::Config: CanonicalSerialize + CanonicalDeserialize + Clone + Sync,
-        SC: StepCircuit<G1::ScalarField>,
     {
         let mut rng = test_rng();
 
-        let mut start = Instant::now();
-        let (shape, _) = SetupParams::<(G1, G2, PVC<G1, PC>, C2, RO, SC)>::get_shape(
-            ro_config.clone(),
-            &step_circuit,
-        )
-        .unwrap();
-        /*       println!("Extract_R1CS_SHAPE {} s", start.elapsed().as_secs());
-
-                let min_num_vars = SNARKKey::<G1, PC>::get_min_srs_size(&shape).unwrap();
-                println!("min_srs: {min_num_vars}");
-        */
-
-        // hardcoded min_num_vars, if circuit changes use commented code above to calculate the min required number.
+        // hardcoded min_num_vars, if circuit changes use `SNARKKey::get_min_srs_size` on the shape
+        // of the step circuit to calculate the min required number.
         let min_num_vars = 25;
 
-        let mut start = Instant::now();
+        let start = Instant::now();
         let srs = PC::setup(min_num_vars, b"test_srs", &mut rng).unwrap();
         println!("SETUP_SRS {} s", start.elapsed().as_secs());
-
-        start = Instant::now();
-        let params = PublicParams::<G1, G2, PVC<G1, PC>, C2, RO, SC>::setup(
-            ro_config,
-            &step_circuit,
-            &srs,
-            &(),
-        )
-        .expect("setup should not fail");
-        println!("SETUP_PUBLIC_PARAMS {} s", start.elapsed().as_secs());
-
-        (srs, params)
+        srs
     }
 
     fn bitcoin_fold_with_cycles<G1, G2, PC, C2>(
+        anchor: Anchor,
         header_chain: Vec<BitcoinHeader>,
+        chain_params: ChainParams,
     ) -> Result<(), Box<dyn Error>>
     where
         G1: SWCurveConfig,
@@ -251,108 +514,131 @@ pub(crate) mod bitcoin_fold_tests {
         PC::Commitment: Copy + Into<Projective<G1>> + From<Projective<G1>>,
         C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
     {
-        let mut circuit_for_setup = BitcoinHeaderCircuit::<G1::ScalarField> {
-            header: BitcoinHeader::default(),
-            _p: PhantomData,
-        };
-
-        let ro_config = poseidon_config();
-
-        // pass in previous block hash
-        let z_0: Vec<G1::ScalarField> = header_chain[0]
-            .hash_prev_block
-            .iter()
-            .map(|byte| G1::ScalarField::from(byte.clone()))
-            .collect();
-
-        // run IVC for one step
-        let num_steps = header_chain.len();
+        // the state of the chain up to the block before the first header
+        let initial_state = anchor.initial_state::<G1::ScalarField>(&chain_params);
 
         println!("-> IVC started!");
+        let srs = setup_srs::<G1, PC>();
         let mut start = Instant::now();
-        let (srs, params) = setup_srs_params::<
-            G1,
-            G2,
-            PC,
-            C2,
-            PoseidonSponge<G1::ScalarField>,
-            BitcoinHeaderCircuit<G1::ScalarField>,
-        >(ro_config, &circuit_for_setup);
-        println!("SETUP_SRS_PARAMS {} s", start.elapsed().as_secs());
+        let mut ivc =
+            BitcoinIVC::<G1, G2, PVC<G1, PC>, C2>::setup(chain_params.clone(), anchor, &srs)?;
+        println!("SETUP_PUBLIC_PARAMS {} s", start.elapsed().as_secs());
         println!("-> Setup is done!");
 
-        let mut nova_proof = IVCProof::new(&z_0);
-
-        for (header) in header_chain.clone() {
-            let circuit = BitcoinHeaderCircuit::<G1::ScalarField> {
-                header: header,
-                _p: PhantomData,
-            };
-
+        for header in &header_chain {
             start = Instant::now();
-            nova_proof = nova_proof.prove_step(&params, &circuit)?;
+            ivc.extend(std::slice::from_ref(header))?;
             println!("NOVA_PROOF {} s", start.elapsed().as_secs());
 
             println!("-> Proof is generated!");
         }
 
         start = Instant::now();
-        nova_proof.verify(&params, num_steps).unwrap();
+        let proven_state = ivc.verify()?;
         println!("NOVA_PROOF_VERIFY {} s", start.elapsed().as_secs());
         println!("-> Proof is verified!");
 
-        // check z_i is equal to the final block hash
-        let header_digest =
-            <Sha256 as CRHScheme>::evaluate(&(), header_chain[header_chain.len() - 1].to_bytes())
-                .unwrap();
-        let digest_digest = <Sha256 as CRHScheme>::evaluate(&(), header_digest).unwrap();
-        let digest_digest_scalars: Vec<G1::ScalarField> = digest_digest
-            .iter()
-            .map(|byte| G1::ScalarField::from(byte.clone()))
-            .collect();
-        assert_eq!(nova_proof.z_i(), digest_digest_scalars);
+        // check z_i is equal to the state after the final block
+        let expected_state = header_chain.iter().fold(initial_state, |state, header| {
+            state.next::<G1::ScalarField>(&chain_params, header)
+        });
+        assert_eq!(
+            ivc.proof().z_i(),
+            expected_state.to_field_elements::<G1::ScalarField>()
+        );
+        assert_eq!(proven_state, expected_state);
+        assert_eq!(ivc.state(), &expected_state);
 
         // compress IVCProof
         start = Instant::now();
-        let key = SNARK::<
-            G1,
-            G2,
-            PC,
-            C2,
-            PoseidonSponge<G1::ScalarField>,
-            BitcoinHeaderCircuit<G1::ScalarField>,
-        >::setup(&params, &srs)
-        .unwrap();
+        let key = BitcoinSNARK::<G1, G2, PC, C2>::setup(ivc.params(), &srs).unwrap();
         println!("SPARTAN_SETUP { } s", start.elapsed().as_secs());
 
         start = Instant::now();
-        let compressed_nova_proof = SNARK::<
-            G1,
-            G2,
-            PC,
-            C2,
-            PoseidonSponge<G1::ScalarField>,
-            BitcoinHeaderCircuit<G1::ScalarField>,
-        >::compress(&params, &key, nova_proof)
-        .unwrap();
+        let compressed_nova_proof = ivc.compress(&key)?;
         println!("SPARTAN_PROOF { } s", start.elapsed().as_secs());
 
         // verify compressed proof.
         start = Instant::now();
-        SNARK::<
-            G1,
-            G2,
-            PC,
-            C2,
-            PoseidonSponge<G1::ScalarField>,
-            BitcoinHeaderCircuit<G1::ScalarField>,
-        >::verify(&key, &params, &compressed_nova_proof)
-        .unwrap();
+        let compressed_state = verify_compressed(
+            &key,
+            ivc.params(),
+            ivc.chain_params(),
+            ivc.anchor(),
+            &compressed_nova_proof,
+        )?;
+        assert_eq!(compressed_state, expected_state);
         println!("SPARTAN_VERIFY {}", start.elapsed().as_secs());
 
+        // proof sizes and verify times of the compression backends
+        for report in [
+            measure_compression::<NoCompression, _, _, _, _>(&(), &ivc)?,
+            measure_compression::<SpartanCompression<PC>, _, _, _, _>(&key, &ivc)?,
+        ] {
+            println!(
+                "{:?}_PROOF_SIZE {} bytes, VERIFY {} ms",
+                report.backend,
+                report.proof_size,
+                report.verify_time.as_millis()
+            );
+        }
+
         Ok(())
     }
 
+    // mainnet checkpoint before `header`, the chainwork and the timestamps of the previous blocks
+    // are not part of the test data, they are not needed by the mainnet difficulty rules and the
+    // median time past of zero timestamps is always met.
+    fn checkpoint_before(header: &BitcoinHeader, height: u32) -> Checkpoint {
+        Checkpoint {
+            height: height - 1,
+            hash: header.hash_prev_block.clone(),
+            chainwork: 0u32.into(),
+            bits: header.bits(),
+            period_start_timestamp: 0,
+            mtp_window: vec![0; 11],
+        }
+    }
+
+    fn state_before(header: &BitcoinHeader, height: u32) -> ChainState {
+        ChainState::from_checkpoint::<Fr>(Network::Mainnet, &checkpoint_before(header, height))
+    }
+
+    // checks the header circuit for `header` is satisfied on top of `state` at step `step`, the
+    // state must be z_0 at step 0
+    fn is_header_circuit_satisfied(
+        chain_params: &ChainParams,
+        state: &ChainState,
+        header: &BitcoinHeader,
+        step: u64,
+    ) -> bool {
+        is_circuit_satisfied(
+            &BitcoinHeaderCircuit::new(header.clone(), chain_params.clone()),
+            state,
+            step,
+        )
+    }
+
+    fn is_circuit_satisfied(
+        circuit: &BitcoinHeaderCircuit<Fr>,
+        state: &ChainState,
+        step: u64,
+    ) -> bool {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let i = FpVar::new_witness(cs.clone(), || Ok(Fr::from(step))).unwrap();
+        let z = Vec::<FpVar<Fr>>::new_witness(cs.clone(), || Ok(state.to_field_elements::<Fr>()))
+            .unwrap();
+        let z_out = circuit.generate_constraints(cs.clone(), &i, &z).unwrap();
+        let z_out = z_out.value().unwrap();
+        assert_eq!(
+            z_out,
+            state
+                .next::<Fr>(&circuit.params, &circuit.header)
+                .to_field_elements::<Fr>()
+        );
+        cs.is_satisfied().unwrap()
+    }
+
     #[test]
     fn bitcoin_fold_one_step() {
         // read a test block
@@ -363,7 +649,11 @@ pub(crate) mod bitcoin_fold_tests {
             GrumpkinConfig,
             Zeromorph<Bn254>,
             PedersenCommitment<GrumpkinProjective>,
-        >(vec![header])
+        >(
+            Anchor::Unanchored(checkpoint_before(&header, 838637)),
+            vec![header],
+            block_reader.params().clone(),
+        )
         .unwrap();
     }
 
@@ -376,13 +666,413 @@ pub(crate) mod bitcoin_fold_tests {
             .into_iter()
             .map(|(_, header)| header)
             .collect();
+        let checkpoint = checkpoint_before(&block_headers[0], 838637);
+
+        bitcoin_fold_with_cycles::<
+            Bn254Config,
+            GrumpkinConfig,
+            Zeromorph<Bn254>,
+            PedersenCommitment<GrumpkinProjective>,
+        >(
+            Anchor::Unanchored(checkpoint),
+            block_headers,
+            block_reader.params().clone(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn bitcoin_fold_min_difficulty_blocks() {
+        let chain_params = min_difficulty_test_params();
+        let block_reader =
+            BlockReader::new_from_json_with_params(TEST_JSON_RPC_MIN_DIFFICULTY, chain_params)
+                .unwrap();
+        let block_headers = block_reader.get_block_headers().unwrap();
+        let (height, first_header) = &block_headers[0];
+        let checkpoint = Checkpoint::from_header(first_header, *height);
+        let block_headers: Vec<BitcoinHeader> = block_headers[1..]
+            .iter()
+            .map(|(_, header)| header.clone())
+            .collect();
 
         bitcoin_fold_with_cycles::<
             Bn254Config,
             GrumpkinConfig,
             Zeromorph<Bn254>,
             PedersenCommitment<GrumpkinProjective>,
-        >(block_headers)
+        >(
+            Anchor::Unanchored(checkpoint),
+            block_headers,
+            block_reader.params().clone(),
+        )
         .unwrap()
     }
+
+    #[test]
+    fn bitcoin_fold_from_genesis() {
+        let block_reader = BlockReader::new_from_json(TEST_JSON_RPC_GENESIS).unwrap();
+        let block_headers: Vec<BitcoinHeader> = block_reader.get_block_headers().unwrap()[1..]
+            .iter()
+            .map(|(_, header)| header.clone())
+            .collect();
+
+        bitcoin_fold_with_cycles::<
+            Bn254Config,
+            GrumpkinConfig,
+            Zeromorph<Bn254>,
+            PedersenCommitment<GrumpkinProjective>,
+        >(
+            Anchor::Genesis,
+            block_headers,
+            block_reader.params().clone(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn bitcoin_fold_from_checkpoint() {
+        let block_reader = BlockReader::new_from_json(TEST_JSON_RPC_GENESIS).unwrap();
+        let checkpoint = block_reader.get_checkpoint(1).unwrap();
+        let header = block_reader.get_block_header(2).unwrap();
+
+        let srs = setup_srs::<Bn254Config, Zeromorph<Bn254>>();
+        let mut ivc = BitcoinIVC::<
+            Bn254Config,
+            GrumpkinConfig,
+            PVC<Bn254Config, Zeromorph<Bn254>>,
+            PedersenCommitment<GrumpkinProjective>,
+        >::setup(
+            block_reader.params().clone(),
+            Anchor::Checkpoint(checkpoint.clone()),
+            &srs,
+        )
+        .unwrap();
+        ivc.extend(&[header]).unwrap();
+
+        // the verifier learns the checkpoint from the proven state
+        let state = ivc.verify().unwrap();
+        let checkpoints = [Checkpoint::genesis(block_reader.params()), checkpoint];
+        assert_eq!(
+            state.find_checkpoint::<Fr>(&checkpoints),
+            Some(&checkpoints[1])
+        );
+        assert_eq!(state.chainwork, 0x300030003u64.into());
+
+        // and checks proven blocks against the accumulator of the proven state
+        let block_2 = block_reader.get_block_header(2).unwrap();
+        let proof = ivc.prove_membership(2).unwrap();
+        assert_eq!(proof.verify::<Fr>(&state, &block_2.hash()), Ok(()));
+        assert_eq!(
+            proof.verify::<Fr>(&state, &checkpoints[1].hash),
+            Err(MmrError::RootMismatch)
+        );
+
+        // the proven tip is not buried yet
+        assert_eq!(
+            proof.verify_depth::<Fr>(&state, &block_2.hash(), 1),
+            Err(MmrError::NotBuried(2, 0, 1))
+        );
+    }
+
+    #[test]
+    fn header_circuit_enforces_anchor() {
+        let chain_params = ChainParams::mainnet();
+        let genesis_state = Anchor::Genesis.initial_state::<Fr>(&chain_params);
+        let block_reader = BlockReader::new_from_json(TEST_JSON_RPC_GENESIS).unwrap();
+        let block_1 = block_reader.get_block_header(1).unwrap();
+        let circuit = BitcoinHeaderCircuit::new(block_1, chain_params.clone())
+            .with_anchor(Some(genesis_state.clone()));
+        assert!(is_circuit_satisfied(&circuit, &genesis_state, 0));
+
+        // the first step of an anchored proof can not start from any other block
+        let block_reader = BlockReader::new_from_json(TEST_JSON_RPC).unwrap();
+        let header = block_reader.get_block_header(838637).unwrap();
+        let state = state_before(&header, 838637);
+        let circuit =
+            BitcoinHeaderCircuit::new(header, chain_params).with_anchor(Some(genesis_state));
+        assert!(!is_circuit_satisfied(&circuit, &state, 0));
+    }
+
+    #[test]
+    fn header_circuit_enforces_anchor_digest() {
+        let block_reader = BlockReader::new_from_json(TEST_JSON_RPC).unwrap();
+        let header = block_reader.get_block_header(838637).unwrap();
+        let mut state = state_before(&header, 838637);
+        state.anchor = Fr::from(1u64).into_bigint().to_bytes_le();
+
+        // the anchor of z_0 must be its digest, later states pass the anchor of z_0 on
+        let chain_params = ChainParams::mainnet();
+        assert!(!is_header_circuit_satisfied(
+            &chain_params,
+            &state,
+            &header,
+            0
+        ));
+        assert!(is_header_circuit_satisfied(
+            &chain_params,
+            &state,
+            &header,
+            1
+        ));
+    }
+
+    #[test]
+    fn header_circuit_enforces_median_time_past() {
+        let chain_params = ChainParams::mainnet();
+        let block_reader = BlockReader::new_from_json(TEST_JSON_RPC_GENESIS).unwrap();
+        let header = block_reader.get_block_header(1).unwrap();
+
+        // 6 of the 11 last timestamps are not before the header
+        let mut checkpoint = Checkpoint::genesis(&chain_params);
+        for timestamp in &mut checkpoint.mtp_window[5..] {
+            *timestamp = header.timestamp;
+        }
+        let state = ChainState::from_checkpoint::<Fr>(Network::Mainnet, &checkpoint);
+        assert!(!is_header_circuit_satisfied(
+            &chain_params,
+            &state,
+            &header,
+            0
+        ));
+
+        checkpoint.mtp_window[5] -= 1;
+        let state = ChainState::from_checkpoint::<Fr>(Network::Mainnet, &checkpoint);
+        assert!(is_header_circuit_satisfied(
+            &chain_params,
+            &state,
+            &header,
+            0
+        ));
+    }
+
+    #[test]
+    fn header_circuit_binds_network() {
+        let block_reader = BlockReader::new_from_json(TEST_JSON_RPC).unwrap();
+        let header = block_reader.get_block_header(838637).unwrap();
+        let state = state_before(&header, 838637);
+
+        assert!(is_header_circuit_satisfied(
+            &ChainParams::mainnet(),
+            &state,
+            &header,
+            0
+        ));
+        assert!(!is_header_circuit_satisfied(
+            &ChainParams::regtest(),
+            &state,
+            &header,
+            0
+        ));
+    }
+
+    #[test]
+    fn header_circuit_enforces_proof_of_work() {
+        let block_reader = BlockReader::new_from_json(TEST_JSON_RPC).unwrap();
+        let mut header = block_reader.get_block_header(838637).unwrap();
+        let state = state_before(&header, 838637);
+        header.nonce += 1;
+
+        assert!(!is_header_circuit_satisfied(
+            &ChainParams::mainnet(),
+            &state,
+            &header,
+            0
+        ));
+    }
+
+    #[test]
+    fn header_circuit_min_difficulty_blocks() {
+        let chain_params = min_difficulty_test_params();
+        let block_reader = BlockReader::new_from_json_with_params(
+            TEST_JSON_RPC_MIN_DIFFICULTY,
+            chain_params.clone(),
+        )
+        .unwrap();
+        let headers = block_reader.get_block_headers().unwrap();
+        assert_eq!(
+            HeaderValidator::new(chain_params.clone()).validate_headers(&headers),
+            Ok(())
+        );
+
+        let (height, first_header) = &headers[0];
+        let mut state = ChainState::from_header::<Fr>(&chain_params, first_header, *height);
+        for (step, (_, header)) in headers[1..].iter().enumerate() {
+            assert!(is_header_circuit_satisfied(
+                &chain_params,
+                &state,
+                header,
+                step as u64
+            ));
+            state = state.next::<Fr>(&chain_params, header);
+        }
+        // the lookback skipped the min-difficulty blocks 1004 and 1005
+        assert_eq!(state.bits, headers[3].1.bits());
+
+        // the strict rules reject the late block 1002 mined at min-difficulty
+        let strict_params = ChainParams {
+            pow_allow_min_difficulty_blocks: false,
+            ..chain_params.clone()
+        };
+        let state = ChainState::from_header::<Fr>(&chain_params, &headers[1].1, headers[1].0);
+        assert!(!is_header_circuit_satisfied(
+            &strict_params,
+            &state,
+            &headers[2].1,
+            0
+        ));
+    }
+
+    #[test]
+    fn header_circuit_rejects_early_min_difficulty_block() {
+        let chain_params = min_difficulty_test_params();
+        let block_reader = BlockReader::new_from_json_with_params(
+            TEST_JSON_RPC_MIN_DIFFICULTY,
+            chain_params.clone(),
+        )
+        .unwrap();
+        let headers = block_reader.get_block_headers().unwrap();
+
+        // block 1003 is on time, so it can not be mined at min-difficulty
+        let state = ChainState::from_header::<Fr>(&chain_params, &headers[1].1, headers[1].0)
+            .next::<Fr>(&chain_params, &headers[2].1);
+        let mut header = headers[3].1.clone();
+        header.target_bits = chain_params.pow_limit_bits.to_le_bytes().to_vec();
+        assert!(!is_header_circuit_satisfied(
+            &chain_params,
+            &state,
+            &header,
+            1
+        ));
+    }
+
+    #[test]
+    fn header_circuit_enforces_retarget() {
+        // regtest rules with retargeting every 4 blocks
+        let chain_params = ChainParams {
+            pow_allow_min_difficulty_blocks: false,
+            pow_no_retargeting: false,
+            pow_target_timespan: 4 * 600,
+            ..ChainParams::regtest()
+        };
+        let headers = mine_regtest_headers(&chain_params.genesis, 4, 0);
+        let circuit = |header: &BitcoinHeader| {
+            BitcoinHeaderCircuit::new(header.clone(), chain_params.clone())
+        };
+
+        let mut state = ChainState::from_header::<Fr>(&chain_params, &chain_params.genesis, 0);
+        for header in &headers[..3] {
+            assert!(is_circuit_satisfied(&circuit(header), &state, 1));
+            state = state.next::<Fr>(&chain_params, header);
+        }
+
+        // the period took 3 / 4 of the target timespan, block 4 must not keep the bits
+        let same_bits = &headers[3];
+        assert!(!is_circuit_satisfied(&circuit(same_bits), &state, 1));
+
+        let bits = pow::calculate_next_work_required(
+            chain_params.pow_limit_bits,
+            chain_params.genesis.timestamp,
+            headers[2].timestamp,
+            &chain_params,
+        )
+        .unwrap();
+        assert_ne!(bits, chain_params.pow_limit_bits);
+        let target = pow::compact_to_target(bits).unwrap();
+        let mut retargeted = BitcoinHeader {
+            target_bits: bits.to_le_bytes().to_vec(),
+            ..same_bits.clone()
+        };
+        while pow::hash_to_uint(&retargeted.hash()) > target {
+            retargeted.nonce += 1;
+        }
+        assert_eq!(
+            HeaderValidator::new(chain_params.clone()).validate_headers(
+                &(0..)
+                    .zip(
+                        [chain_params.genesis.clone()]
+                            .iter()
+                            .chain(&headers[..3])
+                            .chain([&retargeted])
+                            .cloned()
+                    )
+                    .collect::<Vec<_>>()
+            ),
+            Ok(())
+        );
+        assert!(is_circuit_satisfied(&circuit(&retargeted), &state, 1));
+    }
+
+    #[test]
+    fn header_circuit_rejects_timewarp() {
+        // regtest rules with BIP94 and retargeting every 4 blocks
+        let chain_params = ChainParams {
+            pow_allow_min_difficulty_blocks: false,
+            pow_no_retargeting: false,
+            pow_target_timespan: 4 * 600,
+            enforce_bip94: true,
+            ..ChainParams::regtest()
+        };
+        let target = pow::compact_to_target(chain_params.pow_limit_bits).unwrap();
+        let mine = |mut header: BitcoinHeader| {
+            while pow::hash_to_uint(&header.hash()) > target {
+                header.nonce += 1;
+            }
+            header
+        };
+        let child = |parent: &BitcoinHeader, timestamp: u32| {
+            mine(BitcoinHeader {
+                hash_prev_block: parent.hash(),
+                timestamp,
+                ..parent.clone()
+            })
+        };
+
+        // block 3 is late, so block 4 can be earlier than it and still after the median time past
+        let mut headers = mine_regtest_headers(&chain_params.genesis, 2, 0);
+        headers.push(child(&headers[1], headers[1].timestamp + 1800));
+        let warped = child(&headers[2], headers[2].timestamp - 601);
+        let honest = child(&headers[2], headers[2].timestamp - 600);
+
+        let validator = HeaderValidator::new(chain_params.clone());
+        let chain = |tip: &BitcoinHeader| {
+            (0..)
+                .zip(
+                    [chain_params.genesis.clone()]
+                        .iter()
+                        .chain(&headers)
+                        .chain([tip])
+                        .cloned(),
+                )
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            validator.validate_headers(&chain(&warped)),
+            Err(ValidationError::TimeWarp(4))
+        );
+        assert_eq!(validator.validate_headers(&chain(&honest)), Ok(()));
+
+        let circuit = |header: &BitcoinHeader| {
+            BitcoinHeaderCircuit::new(header.clone(), chain_params.clone())
+        };
+        let mut state = ChainState::from_header::<Fr>(&chain_params, &chain_params.genesis, 0);
+        for header in &headers {
+            assert!(is_circuit_satisfied(&circuit(header), &state, 1));
+            state = state.next::<Fr>(&chain_params, header);
+        }
+        assert!(!is_circuit_satisfied(&circuit(&warped), &state, 1));
+        assert!(is_circuit_satisfied(&circuit(&honest), &state, 1));
+    }
+
+    #[test]
+    fn bitcoin_fold_rejects_signet() {
+        let chain_params = ChainParams::signet();
+        // without the signet solution in-circuit, a proof would not attest to consensus validity
+        let error = CycleIVC::setup(chain_params, Anchor::Genesis, &CycleSetup::PallasVesta)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&BitcoinIVCError::UnsupportedNetwork(Network::Signet))
+        );
+    }
 }
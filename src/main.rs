@@ -1,3 +1,379 @@
-fn main() {
-    println!("Hello, world!");
+//! Command line of bitfold.
+//!
+//! `bitfold serve --vk <file> --proofs <dir>` serves the proof files written to `<dir>` over
+//! HTTP, see [`ProofServer`]. Files are verified with the key before they are published and the
+//! directory is polled for new ones, files of a lower tip than the latest published proof are
+//! skipped.
+//!
+//! `bitfold prove --headers <file> --out <file>` proves the headers of a json file as returned by
+//! bitcoind from genesis and writes the proof file, uncompressed unless `--compression spartan`
+//! or its shorthand `--compress` is given, see [`CompressionBackend`]. `--cycle pallas-vesta`
+//! proves without a trusted SRS, its proofs can not be compressed. With `--from-proof <file>` the
+//! proof of that file is extended by the headers past its tip instead, see [`ProofFile::extend`].
+//!
+//! `bitfold daemon --headers <file>` proves the chain of a json file of headers as returned by
+//! bitcoind from genesis, following the file as it grows, and serves the proofs, see
+//! [`ProvingDaemon`]. With `--checkpoints <dir>` it persists its proofs at checkpoint heights and
+//! resumes from the newest one on restart, see [`resume_from_checkpoints`].
+//!
+//! `bitfold vk --out <file>` writes the verifier key of the proofs of `bitfold prove`, which
+//! `bitfold serve` verifies them with, see [`Bn254GrumpkinVerifierKey::to_bytes`].
+//!
+//! `prove`, `vk` and `daemon` over BN254/Grumpkin load the SRS from `--srs <file>`, the canonical
+//! compressed serialization of a [`ZeromorphSRS`] of a ceremony. Whoever knows the seed of a
+//! seeded SRS knows its trapdoor and can forge proofs, so `--srs-seed <seed>` is only taken on
+//! regtest, other networks refuse it unless `--insecure-srs-seed` is given as well.
+//!
+//! `bitfold checkpoint --headers <file> --height <height>` prints the checkpoint at `height` in
+//! the format of the curated checkpoint files, see
+//! [`Checkpoint::curated`](bitcoin_fold::Checkpoint::curated).
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use ark_bn254::Bn254;
+use ark_serialize::CanonicalDeserialize;
+use ark_spartan::polycommitments::{zeromorph::Zeromorph, PolyCommitmentScheme};
+use ark_std::rand::{rngs::StdRng, SeedableRng};
+use bitcoin_fold::{
+    resume_from_checkpoints, verify_proof_file, Anchor, BlockReader, Bn254GrumpkinVerifierKey,
+    ChainParams, CompressionBackend, CurveCycle, CycleIVC, CycleSetup, JsonFileSource, Network,
+    ProofFile, ProofServer, ProofStore, ProvingDaemon, ZeromorphSRS,
+};
+use tracing::{info, warn};
+
+const USAGE: &str = "usage:
+  bitfold serve --vk <file> --proofs <dir> [--addr <host:port>] [--checkpoint-interval <blocks>]
+                [--poll-secs <secs>]
+  bitfold prove --headers <file> --out <file> [--network <name>] [--srs <file>]
+                [--srs-seed <seed> [--insecure-srs-seed]] [--cycle <bn254-grumpkin|pallas-vesta>]
+                [--compression <uncompressed|spartan>] [--compress] [--from-proof <file>]
+                [--to <height>]
+  bitfold vk --out <file> [--network <name>] [--srs <file>]
+             [--srs-seed <seed> [--insecure-srs-seed]]
+  bitfold daemon --headers <file> [--network <name>] [--srs <file>]
+                 [--srs-seed <seed> [--insecure-srs-seed]] [--addr <host:port>]
+                 [--checkpoints <dir>] [--checkpoint-interval <blocks>]
+                 [--publish-interval <blocks>] [--batch-size <blocks>] [--poll-secs <secs>]
+  bitfold checkpoint --headers <file> --height <height> [--network <name>]";
+
+/// Options of a command, given as `--name value`, or as `--name` for the boolean `flags`.
+struct Options(HashMap<String, String>);
+
+impl Options {
+    fn parse(args: &[String], flags: &[&str]) -> Result<Self, String> {
+        let mut options = HashMap::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("unexpected argument {arg}"))?;
+            let value = if flags.contains(&name) {
+                String::new()
+            } else {
+                args.next()
+                    .ok_or_else(|| format!("missing value of --{name}"))?
+                    .clone()
+            };
+            options.insert(name.to_string(), value);
+        }
+        Ok(Options(options))
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    fn required(&self, name: &str) -> Result<&str, String> {
+        self.0
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| format!("missing --{name}"))
+    }
+
+    fn optional(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    fn parse_or<T: FromStr>(&self, name: &str, default: T) -> Result<T, String> {
+        match self.0.get(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| format!("invalid value of --{name}: {value}")),
+            None => Ok(default),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt().with_target(false).init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("serve") => Options::parse(&args[1..], &[])
+            .map_err(Into::into)
+            .and_then(|options| serve(&options)),
+        Some("prove") => Options::parse(&args[1..], &["compress", "insecure-srs-seed"])
+            .map_err(Into::into)
+            .and_then(|options| prove(&options)),
+        Some("vk") => Options::parse(&args[1..], &["insecure-srs-seed"])
+            .map_err(Into::into)
+            .and_then(|options| vk(&options)),
+        Some("daemon") => Options::parse(&args[1..], &["insecure-srs-seed"])
+            .map_err(Into::into)
+            .and_then(|options| daemon(&options)),
+        Some("checkpoint") => Options::parse(&args[1..], &[])
+            .map_err(Into::into)
+            .and_then(|options| checkpoint(&options)),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn serve(options: &Options) -> Result<(), Box<dyn Error>> {
+    let vk = fs::read(options.required("vk")?)?;
+    let key = Bn254GrumpkinVerifierKey::from_bytes(&vk)?;
+    let proofs_dir = PathBuf::from(options.required("proofs")?);
+    let addr = options.parse_or("addr", "127.0.0.1:8080".to_string())?;
+    let checkpoint_interval = options.parse_or("checkpoint-interval", 2016)?;
+    let poll = Duration::from_secs(options.parse_or("poll-secs", 10)?);
+
+    let store = ProofStore::new(vk, checkpoint_interval);
+    let server = ProofServer::bind(&addr, store.clone())?;
+    info!("serving proofs of {:?} on {addr}", key.network);
+    let _server = server.spawn();
+
+    let mut seen = HashSet::new();
+    loop {
+        publish_new_proofs(&key, &store, &proofs_dir, &mut seen)?;
+        thread::sleep(poll);
+    }
+}
+
+fn prove(options: &Options) -> Result<(), Box<dyn Error>> {
+    let from_proof = match options.optional("from-proof") {
+        Some(path) => Some(ProofFile::from_bytes(&fs::read(path)?)?),
+        None => None,
+    };
+    // an extended proof stays on the network and cycle it was proven for
+    let (network, cycle) = match &from_proof {
+        Some(file) => (file.network, file.cycle),
+        None => (
+            parse_network(&options.parse_or("network", "mainnet".to_string())?)?,
+            parse_cycle(&options.parse_or("cycle", "bn254-grumpkin".to_string())?)?,
+        ),
+    };
+    let compression = match (options.flag("compress"), options.optional("compression")) {
+        (false, name) => parse_compression(name.unwrap_or("uncompressed"))?,
+        (true, None | Some("spartan")) => CompressionBackend::Spartan,
+        (true, Some(name)) => {
+            return Err(format!("--compress conflicts with --compression {name}").into())
+        }
+    };
+    let chain_params = ChainParams::for_network(network);
+    let setup = match cycle {
+        CurveCycle::Bn254Grumpkin => CycleSetup::Bn254Grumpkin(srs(options, network)?),
+        CurveCycle::PallasVesta => CycleSetup::PallasVesta,
+    };
+
+    let proven_height = match &from_proof {
+        Some(file) => file.anchor.checkpoint(&chain_params).height + file.num_steps as u32,
+        None => 0,
+    };
+    let to = options.parse_or("to", u32::MAX)?;
+    let reader =
+        BlockReader::new_from_file_with_params(options.required("headers")?, chain_params.clone())?;
+    let headers: Vec<_> = reader
+        .get_block_headers()?
+        .into_iter()
+        .filter(|(height, _)| *height > proven_height && *height <= to)
+        .map(|(_, header)| header)
+        .collect();
+    if headers.is_empty() {
+        return Err(format!("no headers past height {proven_height}").into());
+    }
+
+    info!(
+        "proving {} headers from height {}",
+        headers.len(),
+        proven_height + 1
+    );
+    let file = match from_proof {
+        Some(file) => file.extend(&chain_params, &setup, &headers)?,
+        None => {
+            let mut ivc = CycleIVC::setup(chain_params, Anchor::Genesis, &setup)?;
+            ivc.extend(&headers)?;
+            ivc.proof_file()?
+        }
+    };
+    let file = match compression {
+        CompressionBackend::Uncompressed => file,
+        CompressionBackend::Spartan => file.compress(&chain_params, &setup)?,
+    };
+    fs::write(options.required("out")?, file.to_bytes())?;
+    Ok(())
+}
+
+fn vk(options: &Options) -> Result<(), Box<dyn Error>> {
+    let network = parse_network(&options.parse_or("network", "mainnet".to_string())?)?;
+    let chain_params = ChainParams::for_network(network);
+    let key =
+        Bn254GrumpkinVerifierKey::setup(&chain_params, Anchor::Genesis, &srs(options, network)?)?;
+    fs::write(options.required("out")?, key.to_bytes()?)?;
+    Ok(())
+}
+
+fn daemon(options: &Options) -> Result<(), Box<dyn Error>> {
+    let network = parse_network(&options.parse_or("network", "mainnet".to_string())?)?;
+    let chain_params = ChainParams::for_network(network);
+    let addr = options.parse_or("addr", "127.0.0.1:8080".to_string())?;
+    let checkpoint_interval = options.parse_or("checkpoint-interval", 2016)?;
+
+    let srs = srs(options, network)?;
+    let key = Bn254GrumpkinVerifierKey::setup(&chain_params, Anchor::Genesis, &srs)?;
+    let setup = CycleSetup::Bn254Grumpkin(srs);
+    let mut source = JsonFileSource::new(options.required("headers")?, chain_params.clone());
+    let resumed = match options.optional("checkpoints") {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            resume_from_checkpoints(&mut source, Path::new(dir), &chain_params, &setup)?
+        }
+        None => None,
+    };
+    let ivc = match resumed {
+        Some(ivc) => ivc,
+        None => CycleIVC::setup(chain_params, Anchor::Genesis, &setup)?,
+    };
+
+    let store = ProofStore::new(key.to_bytes()?, checkpoint_interval);
+    let server = ProofServer::bind(&addr, store.clone())?;
+    info!("serving proofs of {network:?} on {addr}");
+    let _server = server.spawn();
+
+    let mut daemon = ProvingDaemon::new(source, ivc, key, store)?
+        .with_publish_interval(options.parse_or("publish-interval", 100)?)
+        .with_batch_size(options.parse_or("batch-size", 100)?)
+        .with_poll_interval(Duration::from_secs(options.parse_or("poll-secs", 10)?));
+    if let Some(dir) = options.optional("checkpoints") {
+        daemon = daemon.with_checkpoints(dir, checkpoint_interval);
+    }
+    daemon.run()
+}
+
+fn checkpoint(options: &Options) -> Result<(), Box<dyn Error>> {
+    let network = parse_network(&options.parse_or("network", "mainnet".to_string())?)?;
+    let height = options
+        .required("height")?
+        .parse()
+        .map_err(|_| "invalid value of --height")?;
+    let reader = BlockReader::new_from_file_with_params(
+        options.required("headers")?,
+        ChainParams::for_network(network),
+    )?;
+    let checkpoint = reader.get_checkpoint(height)?;
+    println!("{}", checkpoint.to_curated_json());
+    Ok(())
+}
+
+// the SRS of `--srs`, or of `--srs-seed` on regtest or with `--insecure-srs-seed`
+fn srs(options: &Options, network: Network) -> Result<ZeromorphSRS, Box<dyn Error>> {
+    if let Some(path) = options.optional("srs") {
+        return Ok(ZeromorphSRS::deserialize_compressed(&fs::read(path)?[..])?);
+    }
+    if network != Network::Regtest {
+        if !options.flag("insecure-srs-seed") {
+            return Err(format!(
+                "missing --srs, seeded SRSs are forgeable and refused on {network:?} without \
+                 --insecure-srs-seed"
+            )
+            .into());
+        }
+        warn!("the SRS is set up from a seed, whoever knows it can forge proofs of {network:?}");
+    }
+    let mut rng = StdRng::seed_from_u64(options.parse_or("srs-seed", 0)?);
+    let srs = <Zeromorph<Bn254> as PolyCommitmentScheme<_>>::setup(25, b"bitfold_srs", &mut rng)
+        .map_err(|error| error.to_string())?;
+    Ok(srs)
+}
+
+fn parse_cycle(name: &str) -> Result<CurveCycle, String> {
+    match name {
+        "bn254-grumpkin" => Ok(CurveCycle::Bn254Grumpkin),
+        "pallas-vesta" => Ok(CurveCycle::PallasVesta),
+        _ => Err(format!("unknown curve cycle {name}")),
+    }
+}
+
+fn parse_compression(name: &str) -> Result<CompressionBackend, String> {
+    match name {
+        "uncompressed" => Ok(CompressionBackend::Uncompressed),
+        "spartan" => Ok(CompressionBackend::Spartan),
+        _ => Err(format!("unknown compression backend {name}")),
+    }
+}
+
+fn parse_network(name: &str) -> Result<Network, String> {
+    match name {
+        "mainnet" => Ok(Network::Mainnet),
+        "testnet3" => Ok(Network::Testnet3),
+        "testnet4" => Ok(Network::Testnet4),
+        "signet" => Ok(Network::Signet),
+        "regtest" => Ok(Network::Regtest),
+        _ => Err(format!("unknown network {name}")),
+    }
+}
+
+/// Verifies and publishes the proof files in `dir` not seen before, by ascending height.
+fn publish_new_proofs(
+    key: &Bn254GrumpkinVerifierKey,
+    store: &ProofStore,
+    dir: &Path,
+    seen: &mut HashSet<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let mut verified = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != "json") || seen.contains(&path) {
+            continue;
+        }
+        // files that do not parse may still be being written, they are read again next time
+        let Ok(file) = ProofFile::from_bytes(&fs::read(&path)?) else {
+            continue;
+        };
+        seen.insert(path.clone());
+        match verify_proof_file(key, &file) {
+            Ok(state) => verified.push((state, file)),
+            Err(error) => warn!("skipping {}: {error}", path.display()),
+        }
+    }
+
+    verified.sort_by_key(|(state, _)| state.tip_height);
+    let latest_height = store.latest_state().map(|state| state.tip_height);
+    for (state, file) in verified {
+        if latest_height.is_some_and(|height| state.tip_height < height) {
+            warn!(
+                "skipping proof of height {}, below the latest proof",
+                state.tip_height
+            );
+            continue;
+        }
+        info!("publishing proof of height {}", state.tip_height);
+        store.publish(state, &file);
+    }
+    Ok(())
 }
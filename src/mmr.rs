@@ -0,0 +1,470 @@
+//! Merkle mountain range of the proven block hashes, accumulated in the folded state.
+//!
+//! Leaves are appended in the order the blocks are proven. The range is a list of perfect binary
+//! trees, the peaks, one per set bit of the number of leaves: peak `k` is the root of a tree of
+//! `2^k` leaves. The folded state carries the number of leaves and a slot for each of the
+//! [`MAX_MMR_PEAKS`] possible peaks, zero if the peak is absent.
+//!
+//! A leaf commits to the height and hash of its block and to the chainwork up to the block, so a
+//! membership proof also tells how buried the block is below the tip of the state.
+//!
+//! ref: https://github.com/opentimestamps/opentimestamps-server/blob/master/doc/merkle-mountain-range.md
+
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ff::{BigInteger, PrimeField};
+use ark_std::{vec, vec::Vec};
+use num_bigint::BigUint;
+use thiserror::Error;
+
+use crate::state::{poseidon_hash, ChainState};
+
+/// Max number of peaks, i.e. the range holds up to 2^32 - 1 leaves.
+pub const MAX_MMR_PEAKS: usize = 32;
+
+// domain separation of leaves and inner nodes
+pub(crate) const LEAF_TAG: u64 = 0;
+pub(crate) const NODE_TAG: u64 = 1;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MmrError {
+    #[error("no block at height {0} in the range")]
+    UnknownHeight(u32),
+    #[error("leaf {0} is not in a range of {1} leaves")]
+    LeafOutOfRange(u32, u32),
+    #[error("range has {0} peaks, expected {1}")]
+    InvalidPeaks(usize, usize),
+    #[error("membership proof does not lead to the accumulator root")]
+    RootMismatch,
+    #[error("block at height {0} has {1} blocks on top, expected at least {2}")]
+    NotBuried(u32, u32, u32),
+    #[error("block hash has {0} bytes, expected 32")]
+    InvalidBlockHash(usize),
+}
+
+/// Leaf committing to the hash of the block at `height` and the `chainwork` up to the block.
+pub fn leaf_hash<F: PrimeField + Absorb>(
+    height: u32,
+    block_hash: &[u8],
+    chainwork: &BigUint,
+) -> Result<F, MmrError> {
+    if block_hash.len() != 32 {
+        return Err(MmrError::InvalidBlockHash(block_hash.len()));
+    }
+    let mut chainwork = chainwork.to_bytes_le();
+    chainwork.resize(32, 0);
+    Ok(poseidon_hash(&[
+        F::from(LEAF_TAG),
+        F::from(height),
+        F::from_le_bytes_mod_order(&block_hash[..16]),
+        F::from_le_bytes_mod_order(&block_hash[16..]),
+        F::from_le_bytes_mod_order(&chainwork[..16]),
+        F::from_le_bytes_mod_order(&chainwork[16..]),
+    ]))
+}
+
+pub fn node_hash<F: PrimeField + Absorb>(left: &F, right: &F) -> F {
+    poseidon_hash(&[F::from(NODE_TAG), *left, *right])
+}
+
+/// Root of a range, committing to its number of leaves and all its peak slots.
+pub fn bag_peaks<F: PrimeField + Absorb>(num_leaves: u32, peaks: &[F]) -> F {
+    let mut elements = vec![F::from(num_leaves)];
+    elements.extend_from_slice(peaks);
+    poseidon_hash(&elements)
+}
+
+/// Native builder of the range, keeping all nodes to produce membership proofs.
+#[derive(Clone, Debug)]
+pub struct HeaderMmr<F: PrimeField> {
+    // heights of the leaves, in the order they were appended
+    heights: Vec<u32>,
+    // chainwork up to the block of each leaf
+    chainworks: Vec<BigUint>,
+    // nodes of the perfect trees by level, level 0 are the leaves
+    levels: Vec<Vec<F>>,
+}
+
+impl<F: PrimeField + Absorb> HeaderMmr<F> {
+    pub fn new() -> Self {
+        HeaderMmr {
+            heights: Vec::new(),
+            chainworks: Vec::new(),
+            levels: vec![Vec::new()],
+        }
+    }
+
+    pub fn num_leaves(&self) -> u32 {
+        self.heights.len() as u32
+    }
+
+    /// Appends the hash of the block at `height`, with the `chainwork` up to the block.
+    pub fn push(
+        &mut self,
+        height: u32,
+        block_hash: &[u8],
+        chainwork: &BigUint,
+    ) -> Result<(), MmrError> {
+        let leaf = leaf_hash(height, block_hash, chainwork)?;
+        self.heights.push(height);
+        self.chainworks.push(chainwork.clone());
+        self.levels[0].push(leaf);
+
+        // merge the two last nodes of a level, as long as they are siblings
+        let mut level = 0;
+        while self.levels[level].len().is_multiple_of(2) {
+            let nodes = &self.levels[level];
+            let parent = node_hash(&nodes[nodes.len() - 2], &nodes[nodes.len() - 1]);
+            if self.levels.len() == level + 1 {
+                self.levels.push(Vec::new());
+            }
+            self.levels[level + 1].push(parent);
+            level += 1;
+        }
+        Ok(())
+    }
+
+    /// Removes the leaves appended after the first `num_leaves`, e.g. the blocks of a reorg.
+    pub fn truncate(&mut self, num_leaves: u32) {
+        let num_leaves = num_leaves as usize;
+        self.heights.truncate(num_leaves);
+        self.chainworks.truncate(num_leaves);
+        // level k keeps the roots of the complete trees of 2^k leaves
+        for (k, nodes) in self.levels.iter_mut().enumerate() {
+            nodes.truncate(num_leaves >> k);
+        }
+    }
+
+    /// Peak slots as carried in the folded state.
+    pub fn peaks(&self) -> Vec<F> {
+        (0..MAX_MMR_PEAKS)
+            .map(|k| {
+                let is_peak = (self.num_leaves() >> k) & 1 == 1;
+                match self.levels.get(k) {
+                    Some(nodes) if is_peak => *nodes.last().expect("peak node"),
+                    _ => F::zero(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn root(&self) -> F {
+        bag_peaks(self.num_leaves(), &self.peaks())
+    }
+
+    /// Proves the block at `height` is part of the range.
+    pub fn prove(&self, height: u32) -> Result<MmrProof, MmrError> {
+        let leaf_index = self
+            .heights
+            .iter()
+            .position(|leaf_height| *leaf_height == height)
+            .ok_or(MmrError::UnknownHeight(height))?;
+
+        // climb until the node has no sibling, i.e. is a peak
+        let mut siblings = Vec::new();
+        let mut index = leaf_index;
+        for nodes in &self.levels {
+            match nodes.get(index ^ 1) {
+                Some(sibling) => siblings.push(field_to_bytes(sibling)),
+                None => break,
+            }
+            index >>= 1;
+        }
+
+        Ok(MmrProof {
+            leaf_index: leaf_index as u32,
+            height,
+            chainwork: self.chainworks[leaf_index].clone(),
+            num_leaves: self.num_leaves(),
+            siblings,
+            peaks: self.peaks().iter().map(field_to_bytes).collect(),
+        })
+    }
+}
+
+/// Proof that the block at `height` is a leaf of a range, i.e. part of the proven chain.
+///
+/// Field elements are serialized as little-endian bytes, as in [`ChainState`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MmrProof {
+    pub leaf_index: u32,
+    pub height: u32,
+    // chainwork up to the block
+    pub chainwork: BigUint,
+    pub num_leaves: u32,
+    // siblings from the leaf up to its peak
+    pub siblings: Vec<Vec<u8>>,
+    pub peaks: Vec<Vec<u8>>,
+}
+
+impl MmrProof {
+    /// Checks `block_hash` is the hash of the block at `self.height` in the range of `root`.
+    pub fn verify_root<F: PrimeField + Absorb>(
+        &self,
+        root: &F,
+        block_hash: &[u8],
+    ) -> Result<(), MmrError> {
+        if self.leaf_index >= self.num_leaves {
+            return Err(MmrError::LeafOutOfRange(self.leaf_index, self.num_leaves));
+        }
+        if self.siblings.len() >= MAX_MMR_PEAKS {
+            return Err(MmrError::RootMismatch);
+        }
+        if self.peaks.len() != MAX_MMR_PEAKS {
+            return Err(MmrError::InvalidPeaks(self.peaks.len(), MAX_MMR_PEAKS));
+        }
+        let peaks: Vec<F> = self
+            .peaks
+            .iter()
+            .map(|peak| F::from_le_bytes_mod_order(peak))
+            .collect();
+        if bag_peaks(self.num_leaves, &peaks) != *root {
+            return Err(MmrError::RootMismatch);
+        }
+
+        let mut node = leaf_hash::<F>(self.height, block_hash, &self.chainwork)?;
+        for (level, sibling) in self.siblings.iter().enumerate() {
+            let sibling = F::from_le_bytes_mod_order(sibling);
+            node = if (self.leaf_index >> level) & 1 == 0 {
+                node_hash(&node, &sibling)
+            } else {
+                node_hash(&sibling, &node)
+            };
+        }
+
+        // the leaf must be in the last tree of its level, which is the peak of that level
+        let level = self.siblings.len();
+        let is_last_tree = (self.leaf_index >> level) + 1 == self.num_leaves >> level;
+        let is_peak = (self.num_leaves >> level) & 1 == 1;
+        if !is_last_tree || !is_peak || peaks[level] != node {
+            return Err(MmrError::RootMismatch);
+        }
+        Ok(())
+    }
+
+    /// Checks `block_hash` is the hash of the block at `self.height` in the chain proven by
+    /// `state`, the state must have been verified.
+    pub fn verify<F: PrimeField + Absorb>(
+        &self,
+        state: &ChainState,
+        block_hash: &[u8],
+    ) -> Result<(), MmrError> {
+        self.verify_root(&state.mmr_root::<F>(), block_hash)
+    }
+
+    /// Checks the block of `block_hash` is in the chain proven by `state` with at least
+    /// `min_depth` blocks on top of it, and returns how buried the block is.
+    pub fn verify_depth<F: PrimeField + Absorb>(
+        &self,
+        state: &ChainState,
+        block_hash: &[u8],
+        min_depth: u32,
+    ) -> Result<Burial, MmrError> {
+        self.verify::<F>(state, block_hash)?;
+        // the accumulated leaves are below the tip, with less chainwork
+        let depth = state
+            .height
+            .checked_sub(self.height)
+            .ok_or(MmrError::RootMismatch)?;
+        if self.chainwork > state.chainwork {
+            return Err(MmrError::RootMismatch);
+        }
+        if depth < min_depth {
+            return Err(MmrError::NotBuried(self.height, depth, min_depth));
+        }
+        Ok(Burial {
+            height: self.height,
+            depth,
+            work_on_top: &state.chainwork - &self.chainwork,
+        })
+    }
+}
+
+/// How buried a block is below the tip of a proven state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Burial {
+    pub height: u32,
+    // number of blocks on top of the block, i.e. its confirmations minus one
+    pub depth: u32,
+    // chainwork of the blocks on top of the block
+    pub work_on_top: BigUint,
+}
+
+pub(crate) fn field_to_bytes<F: PrimeField>(value: &F) -> Vec<u8> {
+    value.into_bigint().to_bytes_le()
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::bitcoin::data::test_json::TEST_JSON_RPC;
+    use crate::bitcoin::{pow, BlockReader};
+    use ark_bn254::Fr;
+
+    fn block_hash(height: u32) -> Vec<u8> {
+        let mut hash = [0u8; 32].to_vec();
+        hash[..4].copy_from_slice(&height.to_le_bytes());
+        hash
+    }
+
+    fn chainwork(height: u32) -> BigUint {
+        BigUint::from(height) << 40
+    }
+
+    #[test]
+    fn prove_every_leaf() {
+        let mut mmr = HeaderMmr::<Fr>::new();
+        for height in 100..113 {
+            mmr.push(height, &block_hash(height), &chainwork(height))
+                .unwrap();
+            let root = mmr.root();
+            for proven in 100..=height {
+                let proof = mmr.prove(proven).unwrap();
+                assert_eq!(proof.verify_root(&root, &block_hash(proven)), Ok(()));
+            }
+        }
+        // 13 leaves are the peaks of 8, 4 and 1 leaves
+        let peaks = mmr.peaks();
+        assert!(peaks[0] != Fr::from(0u64) && peaks[2] != Fr::from(0u64));
+        assert_eq!(peaks[1], Fr::from(0u64));
+    }
+
+    #[test]
+    fn truncate_reorged_leaves() {
+        let mut mmr = HeaderMmr::<Fr>::new();
+        let mut expected = HeaderMmr::<Fr>::new();
+        for height in 0..13 {
+            mmr.push(height, &block_hash(height), &chainwork(height))
+                .unwrap();
+            if height < 6 {
+                expected
+                    .push(height, &block_hash(height), &chainwork(height))
+                    .unwrap();
+            }
+        }
+        mmr.truncate(6);
+        assert_eq!(mmr.root(), expected.root());
+        assert_eq!(mmr.prove(6), Err(MmrError::UnknownHeight(6)));
+
+        // the range keeps growing from the truncated leaves
+        mmr.push(6, &block_hash(7), &chainwork(6)).unwrap();
+        expected.push(6, &block_hash(7), &chainwork(6)).unwrap();
+        assert_eq!(mmr.root(), expected.root());
+        let proof = mmr.prove(3).unwrap();
+        assert_eq!(proof.verify_root(&mmr.root(), &block_hash(3)), Ok(()));
+    }
+
+    #[test]
+    fn reject_invalid_proofs() {
+        let mut mmr = HeaderMmr::<Fr>::new();
+        for height in 0..6 {
+            mmr.push(height, &block_hash(height), &chainwork(height))
+                .unwrap();
+        }
+        let root = mmr.root();
+        let proof = mmr.prove(3).unwrap();
+        assert_eq!(
+            proof.verify_root(&root, &block_hash(4)),
+            Err(MmrError::RootMismatch)
+        );
+
+        // the leaf commits to the height of the block
+        let wrong_height = MmrProof {
+            height: 4,
+            ..proof.clone()
+        };
+        assert_eq!(
+            wrong_height.verify_root(&root, &block_hash(3)),
+            Err(MmrError::RootMismatch)
+        );
+
+        // and to the chainwork up to the block
+        let wrong_chainwork = MmrProof {
+            chainwork: chainwork(4),
+            ..proof.clone()
+        };
+        assert_eq!(
+            wrong_chainwork.verify_root(&root, &block_hash(3)),
+            Err(MmrError::RootMismatch)
+        );
+
+        let mut wrong_sibling = proof.clone();
+        wrong_sibling.siblings[0][0] ^= 1;
+        assert_eq!(
+            wrong_sibling.verify_root(&root, &block_hash(3)),
+            Err(MmrError::RootMismatch)
+        );
+        assert_eq!(mmr.prove(6), Err(MmrError::UnknownHeight(6)));
+
+        // block hashes are 32 bytes, also when shorter than the two halves the leaf is made of
+        assert_eq!(
+            proof.verify_root(&root, &block_hash(3)[..8]),
+            Err(MmrError::InvalidBlockHash(8))
+        );
+        let mut long_hash = block_hash(3);
+        long_hash.push(0);
+        assert_eq!(
+            proof.verify_root(&root, &long_hash),
+            Err(MmrError::InvalidBlockHash(33))
+        );
+        assert_eq!(
+            mmr.push(6, &block_hash(6)[..31], &chainwork(6)),
+            Err(MmrError::InvalidBlockHash(31))
+        );
+    }
+
+    #[test]
+    fn state_accumulates_proven_blocks() {
+        let reader = BlockReader::new_from_json(TEST_JSON_RPC).unwrap();
+        let headers = reader.get_block_headers().unwrap();
+        let (height, first_header) = &headers[0];
+        let mut state = ChainState::from_header::<Fr>(reader.params(), first_header, *height);
+        let mut mmr = HeaderMmr::<Fr>::new();
+        assert_eq!(state.mmr_root::<Fr>(), mmr.root());
+
+        for (height, header) in &headers[1..] {
+            state = state.next::<Fr>(reader.params(), header);
+            mmr.push(*height, &header.hash(), &state.chainwork).unwrap();
+            assert_eq!(state.mmr_root::<Fr>(), mmr.root());
+        }
+        let (height, header) = &headers[2];
+        let proof = mmr.prove(*height).unwrap();
+        assert_eq!(proof.verify::<Fr>(&state, &header.hash()), Ok(()));
+    }
+
+    #[test]
+    fn block_burial() {
+        let reader = BlockReader::new_from_json(TEST_JSON_RPC).unwrap();
+        let headers = reader.get_block_headers().unwrap();
+        let (height, first_header) = &headers[0];
+        let mut state = ChainState::from_header::<Fr>(reader.params(), first_header, *height);
+        let mut mmr = HeaderMmr::<Fr>::new();
+        for (height, header) in &headers[1..] {
+            state = state.next::<Fr>(reader.params(), header);
+            mmr.push(*height, &header.hash(), &state.chainwork).unwrap();
+        }
+
+        // every block after the first one adds the same work at this difficulty
+        let (height, header) = &headers[1];
+        let proof = mmr.prove(*height).unwrap();
+        let depth = (headers.len() - 2) as u32;
+        let burial = proof
+            .verify_depth::<Fr>(&state, &header.hash(), depth)
+            .unwrap();
+        assert_eq!(burial.depth, depth);
+        let block_work = pow::block_work(&pow::compact_to_target(header.bits()).unwrap());
+        assert_eq!(burial.work_on_top, block_work * depth);
+        assert_eq!(
+            proof.verify_depth::<Fr>(&state, &header.hash(), depth + 1),
+            Err(MmrError::NotBuried(*height, depth, depth + 1))
+        );
+
+        // the tip is not buried
+        let (height, header) = headers.last().unwrap();
+        let burial = mmr
+            .prove(*height)
+            .unwrap()
+            .verify_depth::<Fr>(&state, &header.hash(), 0)
+            .unwrap();
+        assert_eq!(burial.work_on_top, BigUint::from(0u32));
+    }
+}
@@ -0,0 +1,516 @@
+//! Parallel proving of a header chain with Nova's proof-carrying data (PCD) trees.
+//!
+//! A tree of `2^k - 1` steps is proven bottom-up: the leaves prove the headers at even
+//! positions independently, and each parent proves the header between its children while
+//! folding them. Merging checks that the output state of the left child is the input state of
+//! the parent step, and that the parent step outputs the input state of the right child.
+//!
+//! A node covers an odd number of steps. A chain of an odd number of steps is proven as a single
+//! node: trees of such sizes, the largest first, are proven in parallel and joined by parents
+//! proving the step between them. A chain of an even number of steps is proven as the leaf of
+//! its first step followed by the node of the rest, the first step is left open for merging. The
+//! verifier checks the roots and that the output state of one is the input state of the next,
+//! so the proven state has the same semantics as the one of [`BitcoinIVC`](crate::BitcoinIVC).
+//!
+//! Proofs of consecutive segments of the chain, proven independently with the same public
+//! parameters, are merged into the proof of the whole range with [`BitcoinPCD::merge`], which
+//! proves the open first step of the right segment again as the parent of the nodes of both.
+
+use std::error::Error;
+
+use ark_crypto_primitives::sponge::{poseidon::PoseidonSponge, Absorb};
+use ark_ec::{
+    short_weierstrass::{Projective, SWCurveConfig},
+    CurveConfig,
+};
+use ark_ff::PrimeField;
+use ark_spartan::polycommitments::PolyCommitmentScheme;
+use nexus_nova::{
+    circuits::nova::{pcd, sequential::compression::PVC},
+    commitment::CommitmentScheme,
+    poseidon_config,
+};
+use rayon::prelude::*;
+
+use crate::ivc::{check_network, proven_state, BitcoinIVCError};
+use crate::{Anchor, BitcoinHeader, BitcoinHeaderCircuit, ChainParams, ChainState};
+
+pub type BitcoinPCDParams<G1, G2, PC, C2> = pcd::PublicParams<
+    G1,
+    G2,
+    PVC<G1, PC>,
+    C2,
+    PoseidonSponge<<G1 as CurveConfig>::ScalarField>,
+    BitcoinHeaderCircuit<<G1 as CurveConfig>::ScalarField>,
+>;
+
+pub type BitcoinPCDNode<G1, G2, PC, C2> = pcd::PCDNode<
+    G1,
+    G2,
+    PVC<G1, PC>,
+    C2,
+    PoseidonSponge<<G1 as CurveConfig>::ScalarField>,
+    BitcoinHeaderCircuit<<G1 as CurveConfig>::ScalarField>,
+>;
+
+/// Proof of a header chain as a PCD node, preceded by the leaf of the first step when the number
+/// of steps is even.
+pub struct BitcoinPCDProof<G1, G2, PC, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    PC: PolyCommitmentScheme<Projective<G1>>,
+    PC::Commitment: Copy + Into<Projective<G1>> + From<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    pub roots: Vec<BitcoinPCDNode<G1, G2, PC, C2>>,
+    // header of the first step when it is left open as a leaf, see `BitcoinPCD::merge`
+    pub first_header: Option<BitcoinHeader>,
+}
+
+/// Parallel prover and verifier of a header chain.
+///
+/// As for [`BitcoinIVC`](crate::BitcoinIVC), the public parameters are specific to the network
+/// and the anchor.
+pub struct BitcoinPCD<G1, G2, PC, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    PC: PolyCommitmentScheme<Projective<G1>>,
+    PC::Commitment: Copy + Into<Projective<G1>> + From<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    chain_params: ChainParams,
+    anchor: Anchor,
+    params: BitcoinPCDParams<G1, G2, PC, C2>,
+}
+
+impl<G1, G2, PC, C2> BitcoinPCD<G1, G2, PC, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    PC: PolyCommitmentScheme<Projective<G1>>,
+    PC::Commitment: Copy + Into<Projective<G1>> + From<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+    BitcoinPCDParams<G1, G2, PC, C2>: Sync,
+    BitcoinPCDNode<G1, G2, PC, C2>: Send,
+{
+    /// Sets up the public parameters for proving the chain of `chain_params` from `anchor`.
+    pub fn setup(
+        chain_params: ChainParams,
+        anchor: Anchor,
+        srs: &PC::SRS,
+    ) -> Result<Self, Box<dyn Error>> {
+        check_network(&chain_params)?;
+        let step_circuit = BitcoinHeaderCircuit::setup(chain_params.clone())
+            .with_anchor(anchor.pinned_state::<G1::ScalarField>(&chain_params));
+        let params =
+            BitcoinPCDParams::<G1, G2, PC, C2>::setup(poseidon_config(), &step_circuit, srs, &())?;
+        Ok(BitcoinPCD {
+            chain_params,
+            anchor,
+            params,
+        })
+    }
+
+    pub fn params(&self) -> &BitcoinPCDParams<G1, G2, PC, C2> {
+        &self.params
+    }
+
+    /// Proves `headers` on top of the anchor, the trees of the node are proven in parallel.
+    pub fn prove(
+        &self,
+        headers: &[BitcoinHeader],
+    ) -> Result<BitcoinPCDProof<G1, G2, PC, C2>, Box<dyn Error>> {
+        self.prove_segment(
+            self.anchor
+                .initial_state::<G1::ScalarField>(&self.chain_params),
+            headers,
+        )
+    }
+
+    /// Proves the segment of `headers` on top of `state`, a state of the chain proven from the
+    /// anchor, to be merged with the proof of the chain up to `state`.
+    pub fn prove_segment(
+        &self,
+        state: ChainState,
+        headers: &[BitcoinHeader],
+    ) -> Result<BitcoinPCDProof<G1, G2, PC, C2>, Box<dyn Error>> {
+        // steps are numbered from the anchor
+        let anchor_height = self.anchor.checkpoint(&self.chain_params).height;
+        let first_step = state
+            .height
+            .checked_sub(anchor_height)
+            .ok_or(BitcoinIVCError::UnexpectedAnchor(anchor_height))?
+            as usize;
+
+        // states before each header, the i-th header is proven on top of states[i]
+        let mut states = vec![state];
+        for header in headers {
+            let state = states.last().expect("initial state");
+            if header.hash_prev_block != state.tip_hash {
+                return Err(BitcoinIVCError::BrokenLink(state.height + 1).into());
+            }
+            states.push(state.next::<G1::ScalarField>(&self.chain_params, header));
+        }
+
+        let mut roots = Vec::new();
+        let mut first_header = None;
+        let mut start = 0;
+        if !headers.is_empty() && headers.len().is_multiple_of(2) {
+            roots.push(self.prove_tree(headers, &states, first_step, 0, 1)?);
+            first_header = Some(headers[0].clone());
+            start = 1;
+        }
+
+        // the trees of the node over the rest, each followed by the step joining it to the next
+        let mut trees = Vec::new();
+        for len in tree_sizes(headers.len() - start) {
+            trees.push((start, len));
+            start += len + 1;
+        }
+        let mut trees = trees
+            .into_par_iter()
+            .map(|(start, len)| {
+                self.prove_tree(headers, &states, first_step, start, len)
+                    .map_err(|error| error.to_string())
+            })
+            .collect::<Result<Vec<_>, String>>()?
+            .into_iter();
+        if let Some(mut node) = trees.next() {
+            for tree in trees {
+                let header = &headers[node.j as usize + 1 - first_step];
+                node = BitcoinPCDNode::<G1, G2, PC, C2>::prove_parent(
+                    &self.params,
+                    &self.step_circuit(header),
+                    &node,
+                    &tree,
+                )?;
+            }
+            roots.push(node);
+        }
+        Ok(BitcoinPCDProof {
+            roots,
+            first_header,
+        })
+    }
+
+    fn step_circuit(&self, header: &BitcoinHeader) -> BitcoinHeaderCircuit<G1::ScalarField> {
+        BitcoinHeaderCircuit::new(header.clone(), self.chain_params.clone()).with_anchor(
+            self.anchor
+                .pinned_state::<G1::ScalarField>(&self.chain_params),
+        )
+    }
+
+    // proves the tree of the `len` headers from `start`, `len` is one less than a power of two,
+    // the first header is proven at `first_step`
+    fn prove_tree(
+        &self,
+        headers: &[BitcoinHeader],
+        states: &[ChainState],
+        first_step: usize,
+        start: usize,
+        len: usize,
+    ) -> Result<BitcoinPCDNode<G1, G2, PC, C2>, Box<dyn Error>> {
+        let circuit = |step: usize| self.step_circuit(&headers[step - first_step]);
+
+        let mut nodes = (start..start + len)
+            .step_by(2)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|index| {
+                let step = first_step + index;
+                BitcoinPCDNode::<G1, G2, PC, C2>::prove_leaf(
+                    &self.params,
+                    &circuit(step),
+                    step as u64,
+                    &states[index].to_field_elements(),
+                )
+                .map_err(|error| error.to_string())
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        // each parent proves the step between its children
+        while nodes.len() > 1 {
+            nodes = nodes
+                .par_chunks(2)
+                .map(|children| {
+                    let (left, right) = (&children[0], &children[1]);
+                    BitcoinPCDNode::<G1, G2, PC, C2>::prove_parent(
+                        &self.params,
+                        &circuit(left.j as usize + 1),
+                        left,
+                        right,
+                    )
+                    .map_err(|error| error.to_string())
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+        }
+        Ok(nodes.remove(0))
+    }
+
+    /// Verifies the proof against the public parameters and returns the proven state.
+    pub fn verify(
+        &self,
+        proof: &BitcoinPCDProof<G1, G2, PC, C2>,
+    ) -> Result<ChainState, Box<dyn Error>> {
+        let first = proof.roots.first().ok_or(BitcoinIVCError::EmptyProof)?;
+        if first.i != 0 {
+            return Err(BitcoinIVCError::BrokenSegments(0).into());
+        }
+        self.verify_segment(proof)?;
+        let last = proof.roots.last().expect("non-empty proof");
+        proven_state::<G1::ScalarField>(&last.z_i, &self.chain_params, &self.anchor)
+    }
+
+    /// Verifies the proof of a segment, which may not start at the anchor, and returns its start
+    /// and end states.
+    pub fn verify_segment(
+        &self,
+        proof: &BitcoinPCDProof<G1, G2, PC, C2>,
+    ) -> Result<(ChainState, ChainState), Box<dyn Error>> {
+        let (Some(first), Some(last)) = (proof.roots.first(), proof.roots.last()) else {
+            return Err(BitcoinIVCError::EmptyProof.into());
+        };
+        for root in &proof.roots {
+            root.verify(&self.params)?;
+        }
+        // each tree starts where the previous one ends
+        for (left, right) in proof.roots.iter().zip(&proof.roots[1..]) {
+            if left.j + 1 != right.i || left.z_i != right.z_0 {
+                return Err(BitcoinIVCError::BrokenSegments(right.i).into());
+            }
+        }
+        Ok((
+            ChainState::from_field_elements(&first.z_0)?,
+            ChainState::from_field_elements(&last.z_i)?,
+        ))
+    }
+
+    /// Merges the proofs of two consecutive segments, proven with the same public parameters,
+    /// into the proof of both: the end state of `left` must be the start state of `right`.
+    ///
+    /// The first step of `right` is proven again as the parent of the last node of `left` and the
+    /// node of the rest of `right`, so the node of the merged proof covers both segments. A
+    /// parent proves the step between its children, and `right` must leave its first step open,
+    /// i.e. have an even number of steps. The merged proof has the parity and the open first step
+    /// of `left`, so merges chain from left to right.
+    pub fn merge(
+        &self,
+        left: BitcoinPCDProof<G1, G2, PC, C2>,
+        right: BitcoinPCDProof<G1, G2, PC, C2>,
+    ) -> Result<BitcoinPCDProof<G1, G2, PC, C2>, Box<dyn Error>> {
+        let (_, left_end) = self.verify_segment(&left)?;
+        let (right_start, _) = self.verify_segment(&right)?;
+        check_continuity(&left_end, &right_start)?;
+        let next_step = left.roots.last().expect("verified segment").j + 1;
+        let right_step = right.roots.first().expect("verified segment").i;
+        if next_step != right_step {
+            return Err(BitcoinIVCError::BrokenSegments(right_step).into());
+        }
+
+        let (Some(header), [first, rest]) = (&right.first_header, &right.roots[..]) else {
+            return Err(BitcoinIVCError::UnmergeableSegment(right_step).into());
+        };
+        // the open step is the leaf of `header`, which leads to the node of the rest
+        let next = right_start.next::<G1::ScalarField>(&self.chain_params, header);
+        if first.i != first.j
+            || header.hash_prev_block != right_start.tip_hash
+            || next.to_field_elements::<G1::ScalarField>() != rest.z_0
+        {
+            return Err(BitcoinIVCError::BrokenSegments(right_step).into());
+        }
+
+        let mut roots = left.roots;
+        let last = roots.pop().expect("verified segment");
+        roots.push(BitcoinPCDNode::<G1, G2, PC, C2>::prove_parent(
+            &self.params,
+            &self.step_circuit(header),
+            &last,
+            rest,
+        )?);
+        Ok(BitcoinPCDProof {
+            roots,
+            first_header: left.first_header,
+        })
+    }
+}
+
+// checks the chain of `next` continues at the end of `end`, field by field of the state layout
+fn check_continuity(end: &ChainState, next: &ChainState) -> Result<(), BitcoinIVCError> {
+    let fields = [
+        ("network", end.network == next.network),
+        ("tip hash", end.tip_hash == next.tip_hash),
+        ("height", end.height == next.height),
+        ("bits", end.bits == next.bits),
+        ("chainwork", end.chainwork == next.chainwork),
+        (
+            "period start timestamp",
+            end.period_start_timestamp == next.period_start_timestamp,
+        ),
+        ("median time past window", end.mtp_window == next.mtp_window),
+        (
+            "header accumulator",
+            end.mmr_num_leaves == next.mmr_num_leaves && end.mmr_peaks == next.mmr_peaks,
+        ),
+        ("anchor", end.anchor == next.anchor),
+    ];
+    match fields.iter().find(|(_, is_equal)| !is_equal) {
+        Some((field, _)) => Err(BitcoinIVCError::SegmentMismatch(end.height, *field)),
+        None => Ok(()),
+    }
+}
+
+/// Sizes of the trees of a node over an odd `num_steps` steps, the largest first, consecutive
+/// trees are joined by the step between them.
+fn tree_sizes(num_steps: usize) -> Vec<usize> {
+    let mut sizes = Vec::new();
+    let mut remaining = num_steps;
+    while remaining > 0 {
+        // largest 2^k - 1 not above the remaining steps, the rest is even
+        let size = (1usize << (usize::BITS - (remaining + 1).leading_zeros() - 1)) - 1;
+        sizes.push(size);
+        remaining = (remaining - size).saturating_sub(1);
+    }
+    sizes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bitcoin_fold_tests::setup_srs;
+    use crate::test_utils::mine_regtest_headers;
+    use ark_bn254::{g1::Config as Bn254Config, Bn254, Fr};
+    use ark_grumpkin::{GrumpkinConfig, Projective as GrumpkinProjective};
+    use ark_spartan::polycommitments::zeromorph::Zeromorph;
+    use nexus_nova::pedersen::PedersenCommitment;
+
+    // state after `headers` on top of the genesis of `chain_params`
+    fn state_after(chain_params: &ChainParams, headers: &[BitcoinHeader]) -> ChainState {
+        headers.iter().fold(
+            Anchor::Genesis.initial_state::<Fr>(chain_params),
+            |state, header| state.next::<Fr>(chain_params, header),
+        )
+    }
+
+    #[test]
+    fn prove_and_merge_segments() {
+        let chain_params = ChainParams::regtest();
+        // trees of 3 and 1 steps joined by the fourth
+        let headers = mine_regtest_headers(&chain_params.genesis, 5, 1);
+
+        let srs = setup_srs::<Bn254Config, Zeromorph<Bn254>>();
+        let pcd = BitcoinPCD::<
+            Bn254Config,
+            GrumpkinConfig,
+            Zeromorph<Bn254>,
+            PedersenCommitment<GrumpkinProjective>,
+        >::setup(chain_params.clone(), Anchor::Genesis, &srs)
+        .unwrap();
+        let proof = pcd.prove(&headers).unwrap();
+        assert_eq!(proof.roots.len(), 1);
+        assert!(proof.first_header.is_none());
+
+        // same state as the sequential proof
+        let expected_state = state_after(&chain_params, &headers);
+        assert_eq!(pcd.verify(&proof).unwrap(), expected_state);
+
+        // an even segment leaves its first step open
+        let state_1 = state_after(&chain_params, &headers[..1]);
+        let mut right = pcd.prove_segment(state_1, &headers[1..]).unwrap();
+        assert_eq!(right.roots.len(), 2);
+        assert_eq!(right.first_header.as_ref(), Some(&headers[1]));
+        assert_eq!(pcd.verify_segment(&right).unwrap().1, expected_state);
+
+        // the proof of the chain starts at the anchor and the roots must link
+        let error = pcd.verify(&right).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BitcoinIVCError>(),
+            Some(&BitcoinIVCError::BrokenSegments(0))
+        );
+        right.roots.swap(0, 1);
+        let error = pcd.verify_segment(&right).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BitcoinIVCError>(),
+            Some(&BitcoinIVCError::BrokenSegments(1))
+        );
+        right.roots.swap(0, 1);
+
+        // the open step joins the segments into a single node
+        let left = pcd.prove(&headers[..1]).unwrap();
+        let merged = pcd.merge(left, right).unwrap();
+        assert_eq!(merged.roots.len(), 1);
+        assert_eq!(merged.roots[0].j, 4);
+        assert_eq!(pcd.verify(&merged).unwrap(), expected_state);
+
+        // an odd segment has no open step
+        let state_2 = state_after(&chain_params, &headers[..2]);
+        let left = pcd.prove(&headers[..2]).unwrap();
+        let right = pcd.prove_segment(state_2.clone(), &headers[2..]).unwrap();
+        let error = pcd.merge(left, right).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BitcoinIVCError>(),
+            Some(&BitcoinIVCError::UnmergeableSegment(2))
+        );
+
+        // the segments must be consecutive
+        let left = pcd.prove(&headers[..1]).unwrap();
+        let right = pcd.prove_segment(state_2, &headers[2..4]).unwrap();
+        let error = pcd.merge(left, right).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BitcoinIVCError>(),
+            Some(&BitcoinIVCError::SegmentMismatch(1, "tip hash"))
+        );
+    }
+
+    #[test]
+    fn segments_must_continue() {
+        use crate::bitcoin::data::test_json::TEST_JSON_RPC;
+        use crate::bitcoin::BlockReader;
+        use ark_bn254::Fr;
+
+        let reader = BlockReader::new_from_json(TEST_JSON_RPC).unwrap();
+        let headers = reader.get_block_headers().unwrap();
+        let (height, header) = &headers[0];
+        let state = ChainState::from_header::<Fr>(reader.params(), header, *height);
+        let next = state.next::<Fr>(reader.params(), &headers[1].1);
+        assert_eq!(check_continuity(&next, &next.clone()), Ok(()));
+        assert_eq!(
+            check_continuity(&state, &next),
+            Err(BitcoinIVCError::SegmentMismatch(*height, "tip hash"))
+        );
+
+        let mut other_window = next.clone();
+        other_window.mtp_window[0] += 1;
+        assert_eq!(
+            check_continuity(&next, &other_window),
+            Err(BitcoinIVCError::SegmentMismatch(
+                next.height,
+                "median time past window"
+            ))
+        );
+    }
+
+    #[test]
+    fn node_of_perfect_trees() {
+        assert_eq!(tree_sizes(0), Vec::<usize>::new());
+        assert_eq!(tree_sizes(1), vec![1]);
+        assert_eq!(tree_sizes(5), vec![3, 1]);
+        assert_eq!(tree_sizes(7), vec![7]);
+        assert_eq!(tree_sizes(13), vec![7, 3, 1]);
+
+        // the trees and the steps joining them cover the node, the joins are proven one after
+        // the other and are bounded by the depth of the largest tree
+        for num_steps in (1..5000usize).step_by(2).chain([850_001]) {
+            let sizes = tree_sizes(num_steps);
+            assert_eq!(sizes.iter().sum::<usize>() + sizes.len() - 1, num_steps);
+            assert!(sizes.len() <= (num_steps + 1).ilog2() as usize);
+        }
+    }
+}
@@ -0,0 +1,469 @@
+//! Self-describing container of a proof of a header chain.
+//!
+//! The file records what the proof was produced for, i.e. the curve cycle, the network and the
+//! anchor, and how it was compressed, so that a verifier can set up the matching public
+//! parameters.
+
+use std::error::Error;
+
+use ark_bn254::{g1::Config as Bn254Config, Bn254};
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ec::short_weierstrass::{Projective, SWCurveConfig};
+use ark_ff::PrimeField;
+use ark_grumpkin::{GrumpkinConfig, Projective as GrumpkinProjective};
+use ark_pallas::{PallasConfig, Projective as PallasProjective};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_spartan::polycommitments::{zeromorph::Zeromorph, PolyCommitmentScheme};
+use ark_vesta::{Projective as VestaProjective, VestaConfig};
+use nexus_nova::{
+    circuits::nova::sequential::compression::PVC, commitment::CommitmentScheme,
+    pedersen::PedersenCommitment,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::compression::CompressionBackend;
+use crate::cycle::{CurveCycle, CycleSetup};
+use crate::ivc::{self, BitcoinCompressedProof, BitcoinIVCProof, BitcoinSNARK};
+use crate::{Anchor, BitcoinHeader, ChainParams, ChainState, Network};
+
+pub const PROOF_FILE_VERSION: u32 = 1;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ProofFileError {
+    #[error("malformed proof file: {0}")]
+    Malformed(String),
+    #[error("unsupported proof file version {0}")]
+    UnsupportedVersion(u32),
+    #[error("proof is over the {1:?} cycle, not {0:?}")]
+    CycleMismatch(CurveCycle, CurveCycle),
+    #[error("proof is for {1:?}, not {0:?}")]
+    NetworkMismatch(Network, Network),
+    #[error(
+        "proof is anchored to the checkpoint at height {1}, not the expected one at height {0}"
+    )]
+    AnchorMismatch(u32, u32),
+    #[error("proofs over the {0:?} cycle can not be compressed with {1:?}")]
+    UnsupportedCompression(CurveCycle, CompressionBackend),
+    #[error("proofs compressed with {0:?} can not be extended")]
+    NotExtendable(CompressionBackend),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProofFile {
+    pub version: u32,
+    pub cycle: CurveCycle,
+    pub network: Network,
+    pub anchor: Anchor,
+    pub num_steps: usize,
+    // files without a backend predate compression and hold the IVC proof
+    #[serde(default)]
+    pub compression: CompressionBackend,
+    // canonical compressed serialization of the proof produced by the backend
+    #[serde(with = "hex")]
+    pub proof: Vec<u8>,
+}
+
+impl ProofFile {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("proof files serialize to json")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProofFileError> {
+        let file: ProofFile = serde_json::from_slice(bytes)
+            .map_err(|error| ProofFileError::Malformed(error.to_string()))?;
+        if file.version != PROOF_FILE_VERSION {
+            return Err(ProofFileError::UnsupportedVersion(file.version));
+        }
+        Ok(file)
+    }
+
+    /// Sets up the public parameters of the proof and verifies it, the verifier brings the chain
+    /// parameters of the network, the anchor and the setup of the cycle it accepts proofs over.
+    ///
+    /// The anchor recorded in the file is not trusted: a proof from a checkpoint of the prover's
+    /// choosing verifies against its own public parameters, so it must be the verifier's `anchor`.
+    pub fn verify(
+        &self,
+        chain_params: &ChainParams,
+        anchor: &Anchor,
+        setup: &CycleSetup,
+    ) -> Result<ChainState, Box<dyn Error>> {
+        if setup.cycle() != self.cycle {
+            return Err(ProofFileError::CycleMismatch(setup.cycle(), self.cycle).into());
+        }
+        if chain_params.network != self.network {
+            return Err(ProofFileError::NetworkMismatch(chain_params.network, self.network).into());
+        }
+        if *anchor != self.anchor {
+            return Err(ProofFileError::AnchorMismatch(
+                anchor.checkpoint(chain_params).height,
+                self.anchor.checkpoint(chain_params).height,
+            )
+            .into());
+        }
+        match (setup, self.compression) {
+            (CycleSetup::Bn254Grumpkin(srs), CompressionBackend::Uncompressed) => {
+                verify_proof::<
+                    Bn254Config,
+                    GrumpkinConfig,
+                    PVC<Bn254Config, Zeromorph<Bn254>>,
+                    PedersenCommitment<GrumpkinProjective>,
+                >(self, chain_params, srs)
+            }
+            (CycleSetup::Bn254Grumpkin(srs), CompressionBackend::Spartan) => {
+                verify_compressed_proof::<
+                    Bn254Config,
+                    GrumpkinConfig,
+                    Zeromorph<Bn254>,
+                    PedersenCommitment<GrumpkinProjective>,
+                >(self, chain_params, srs)
+            }
+            (CycleSetup::PallasVesta, CompressionBackend::Spartan) => {
+                Err(ProofFileError::UnsupportedCompression(self.cycle, self.compression).into())
+            }
+            (CycleSetup::PallasVesta, CompressionBackend::Uncompressed) => {
+                verify_proof::<
+                    PallasConfig,
+                    VestaConfig,
+                    PedersenCommitment<PallasProjective>,
+                    PedersenCommitment<VestaProjective>,
+                >(self, chain_params, &())
+            }
+        }
+    }
+
+    /// Compresses the uncompressed proof with Spartan, which only proofs over BN254/Grumpkin can
+    /// be, see [`crate::compression`]. The prover brings the same setup as a verifier does.
+    pub fn compress(
+        &self,
+        chain_params: &ChainParams,
+        setup: &CycleSetup,
+    ) -> Result<ProofFile, Box<dyn Error>> {
+        if setup.cycle() != self.cycle {
+            return Err(ProofFileError::CycleMismatch(setup.cycle(), self.cycle).into());
+        }
+        if chain_params.network != self.network {
+            return Err(ProofFileError::NetworkMismatch(chain_params.network, self.network).into());
+        }
+        match (setup, self.compression) {
+            (_, CompressionBackend::Spartan) => Ok(self.clone()),
+            (CycleSetup::Bn254Grumpkin(srs), CompressionBackend::Uncompressed) => {
+                compress_proof::<
+                    Bn254Config,
+                    GrumpkinConfig,
+                    Zeromorph<Bn254>,
+                    PedersenCommitment<GrumpkinProjective>,
+                >(self, chain_params, srs)
+            }
+            (CycleSetup::PallasVesta, CompressionBackend::Uncompressed) => Err(
+                ProofFileError::UnsupportedCompression(self.cycle, CompressionBackend::Spartan)
+                    .into(),
+            ),
+        }
+    }
+
+    /// Extends the uncompressed proof by `headers` without re-proving the proven chain, see
+    /// [`ivc::extend_proof`]. The prover brings the same setup as a verifier does.
+    pub fn extend(
+        &self,
+        chain_params: &ChainParams,
+        setup: &CycleSetup,
+        headers: &[BitcoinHeader],
+    ) -> Result<ProofFile, Box<dyn Error>> {
+        if setup.cycle() != self.cycle {
+            return Err(ProofFileError::CycleMismatch(setup.cycle(), self.cycle).into());
+        }
+        if chain_params.network != self.network {
+            return Err(ProofFileError::NetworkMismatch(chain_params.network, self.network).into());
+        }
+        if self.compression != CompressionBackend::Uncompressed {
+            return Err(ProofFileError::NotExtendable(self.compression).into());
+        }
+        match setup {
+            CycleSetup::Bn254Grumpkin(srs) => extend_proof::<
+                Bn254Config,
+                GrumpkinConfig,
+                PVC<Bn254Config, Zeromorph<Bn254>>,
+                PedersenCommitment<GrumpkinProjective>,
+            >(self, chain_params, srs, headers),
+            CycleSetup::PallasVesta => extend_proof::<
+                PallasConfig,
+                VestaConfig,
+                PedersenCommitment<PallasProjective>,
+                PedersenCommitment<VestaProjective>,
+            >(self, chain_params, &(), headers),
+        }
+    }
+}
+
+fn extend_proof<G1, G2, C1, C2>(
+    file: &ProofFile,
+    chain_params: &ChainParams,
+    setup_aux: &C1::SetupAux,
+    headers: &[BitcoinHeader],
+) -> Result<ProofFile, Box<dyn Error>>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    C1: CommitmentScheme<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    let params = ivc::setup_params::<G1, G2, C1, C2>(chain_params, &file.anchor, setup_aux)?;
+    let proof = ivc::extend_proof(
+        &params,
+        chain_params,
+        &file.anchor,
+        &file.proof,
+        file.num_steps,
+        headers,
+    )?;
+    let mut bytes = Vec::new();
+    proof.serialize_compressed(&mut bytes)?;
+    Ok(ProofFile {
+        num_steps: file.num_steps + headers.len(),
+        proof: bytes,
+        ..file.clone()
+    })
+}
+
+fn compress_proof<G1, G2, PC, C2>(
+    file: &ProofFile,
+    chain_params: &ChainParams,
+    srs: &PC::SRS,
+) -> Result<ProofFile, Box<dyn Error>>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    PC: PolyCommitmentScheme<Projective<G1>>,
+    PC::Commitment: Copy + Into<Projective<G1>> + From<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    if file.num_steps == 0 {
+        return Err(ivc::BitcoinIVCError::EmptyProof.into());
+    }
+    let proof =
+        BitcoinIVCProof::<G1, G2, PVC<G1, PC>, C2>::deserialize_compressed(&file.proof[..])?;
+    let params = ivc::setup_params::<G1, G2, PVC<G1, PC>, C2>(chain_params, &file.anchor, srs)?;
+    let key = BitcoinSNARK::<G1, G2, PC, C2>::setup(&params, srs)?;
+    let compressed = BitcoinSNARK::<G1, G2, PC, C2>::compress(&params, &key, proof)?;
+    let mut bytes = Vec::new();
+    compressed.serialize_compressed(&mut bytes)?;
+    Ok(ProofFile {
+        compression: CompressionBackend::Spartan,
+        proof: bytes,
+        ..file.clone()
+    })
+}
+
+fn verify_proof<G1, G2, C1, C2>(
+    file: &ProofFile,
+    chain_params: &ChainParams,
+    setup_aux: &C1::SetupAux,
+) -> Result<ChainState, Box<dyn Error>>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    C1: CommitmentScheme<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    let proof = BitcoinIVCProof::<G1, G2, C1, C2>::deserialize_compressed(&file.proof[..])?;
+    let params = ivc::setup_params::<G1, G2, C1, C2>(chain_params, &file.anchor, setup_aux)?;
+    ivc::verify_proof(&params, chain_params, &file.anchor, &proof, file.num_steps)
+}
+
+fn verify_compressed_proof<G1, G2, PC, C2>(
+    file: &ProofFile,
+    chain_params: &ChainParams,
+    srs: &PC::SRS,
+) -> Result<ChainState, Box<dyn Error>>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    PC: PolyCommitmentScheme<Projective<G1>>,
+    PC::Commitment: Copy + Into<Projective<G1>> + From<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    let proof = BitcoinCompressedProof::<G1, G2, PC, C2>::deserialize_compressed(&file.proof[..])?;
+    let params = ivc::setup_params::<G1, G2, PVC<G1, PC>, C2>(chain_params, &file.anchor, srs)?;
+    let key = BitcoinSNARK::<G1, G2, PC, C2>::setup(&params, srs)?;
+    ivc::verify_compressed(&key, &params, chain_params, &file.anchor, &proof)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::mine_regtest_headers;
+    use crate::{BitcoinIVCError, CycleIVC};
+
+    fn test_file() -> ProofFile {
+        ProofFile {
+            version: PROOF_FILE_VERSION,
+            cycle: CurveCycle::PallasVesta,
+            network: Network::Regtest,
+            anchor: Anchor::Genesis,
+            num_steps: 2,
+            compression: CompressionBackend::Uncompressed,
+            proof: vec![1, 2, 3],
+        }
+    }
+
+    #[test]
+    fn proof_file_roundtrip() {
+        let file = test_file();
+        assert_eq!(ProofFile::from_bytes(&file.to_bytes()), Ok(file));
+
+        let file = ProofFile {
+            anchor: Anchor::Checkpoint(crate::Checkpoint::genesis(&ChainParams::mainnet())),
+            cycle: CurveCycle::Bn254Grumpkin,
+            compression: CompressionBackend::Spartan,
+            ..test_file()
+        };
+        assert_eq!(ProofFile::from_bytes(&file.to_bytes()), Ok(file));
+
+        // files without a compression backend hold the IVC proof
+        let mut json: serde_json::Value = serde_json::from_slice(&test_file().to_bytes()).unwrap();
+        json.as_object_mut().unwrap().remove("compression");
+        let file = ProofFile::from_bytes(&serde_json::to_vec(&json).unwrap()).unwrap();
+        assert_eq!(file.compression, CompressionBackend::Uncompressed);
+    }
+
+    #[test]
+    fn rejects_unknown_files() {
+        let file = ProofFile {
+            version: PROOF_FILE_VERSION + 1,
+            ..test_file()
+        };
+        assert_eq!(
+            ProofFile::from_bytes(&file.to_bytes()),
+            Err(ProofFileError::UnsupportedVersion(PROOF_FILE_VERSION + 1))
+        );
+        assert!(matches!(
+            ProofFile::from_bytes(b"{}"),
+            Err(ProofFileError::Malformed(_))
+        ));
+
+        // the verifier picks the cycle and the network
+        let file = test_file();
+        let error = file
+            .verify(
+                &ChainParams::mainnet(),
+                &Anchor::Genesis,
+                &CycleSetup::PallasVesta,
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&ProofFileError::NetworkMismatch(
+                Network::Mainnet,
+                Network::Regtest
+            ))
+        );
+
+        // and the anchor, a proof from another checkpoint does not verify
+        let checkpoint = crate::Checkpoint {
+            height: 10,
+            ..crate::Checkpoint::genesis(&ChainParams::regtest())
+        };
+        let error = file
+            .verify(
+                &ChainParams::regtest(),
+                &Anchor::Checkpoint(checkpoint),
+                &CycleSetup::PallasVesta,
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&ProofFileError::AnchorMismatch(10, 0))
+        );
+
+        let file = ProofFile {
+            compression: CompressionBackend::Spartan,
+            ..test_file()
+        };
+        let error = file
+            .verify(
+                &ChainParams::regtest(),
+                &Anchor::Genesis,
+                &CycleSetup::PallasVesta,
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&ProofFileError::UnsupportedCompression(
+                CurveCycle::PallasVesta,
+                CompressionBackend::Spartan
+            ))
+        );
+
+        // compressed proofs are not extended
+        let error = file
+            .extend(&ChainParams::regtest(), &CycleSetup::PallasVesta, &[])
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&ProofFileError::NotExtendable(CompressionBackend::Spartan))
+        );
+
+        // and proofs over Pallas/Vesta are not compressed
+        let error = test_file()
+            .compress(&ChainParams::regtest(), &CycleSetup::PallasVesta)
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&ProofFileError::UnsupportedCompression(
+                CurveCycle::PallasVesta,
+                CompressionBackend::Spartan
+            ))
+        );
+    }
+
+    #[test]
+    fn extend_proof_file() {
+        let chain_params = ChainParams::regtest();
+        let headers = mine_regtest_headers(&chain_params.genesis, 4, 1);
+
+        let setup = CycleSetup::PallasVesta;
+        let mut ivc = CycleIVC::setup(chain_params.clone(), Anchor::Genesis, &setup).unwrap();
+        ivc.extend(&headers[..2]).unwrap();
+        let file = ProofFile::from_bytes(&ivc.proof_file().unwrap().to_bytes()).unwrap();
+
+        // the received proof is extended without the prover that produced it
+        let extended = file.extend(&chain_params, &setup, &headers[2..]).unwrap();
+        assert_eq!(extended.num_steps, 4);
+        ivc.extend(&headers[2..]).unwrap();
+        let state = extended
+            .verify(&chain_params, &Anchor::Genesis, &setup)
+            .unwrap();
+        assert_eq!(state, ivc.verify().unwrap());
+        assert_eq!(state.tip_hash, headers[3].hash());
+
+        // the first header must link to the proven tip
+        let error = file
+            .extend(&chain_params, &setup, &headers[3..])
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BitcoinIVCError>(),
+            Some(&BitcoinIVCError::BrokenLink(3))
+        );
+        let malformed = ProofFile {
+            proof: file.proof[1..].to_vec(),
+            ..file
+        };
+        let error = malformed
+            .extend(&chain_params, &setup, &headers[2..])
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<BitcoinIVCError>(),
+            Some(&BitcoinIVCError::MalformedProof)
+        );
+    }
+}
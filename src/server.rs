@@ -0,0 +1,455 @@
+//! HTTP server of the latest proof of a header chain for light clients.
+//!
+//! A [`ProofStore`] holds the verifier key, the latest published proof and the proofs at
+//! checkpoint heights, a [`ProofServer`] serves it:
+//!
+//! - `GET /proof/latest`: the latest proof as a [`ProofFile`]
+//! - `GET /proof/{height}`: the proof of the checkpoint at `height`
+//! - `GET /vk`: the bytes of the verifier key, see [`crate::VerifierKey::to_bytes`]
+//! - `GET /status`: the proven tip and the proving lag as [`ServerStatus`]
+//! - `GET /metrics`: the [`ProverMetrics`] of the prover, in the Prometheus text format
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::thread::{self, JoinHandle};
+
+use ark_serialize::CanonicalDeserialize;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::compression::CompressionBackend;
+use crate::cycle::CurveCycle;
+use crate::daemon::ProverMetrics;
+use crate::proof_file::ProofFile;
+use crate::{Bn254GrumpkinCompressedProof, Bn254GrumpkinVerifierKey, VerifiedChainState};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ServerError {
+    #[error("only compressed proofs over BN254/Grumpkin are served")]
+    UnsupportedProof,
+    #[error("proof is not for the network and anchor of the verifier key")]
+    KeyMismatch,
+}
+
+/// Verifies a proof file with the key the server serves, only files that verify are published.
+pub fn verify_proof_file(
+    key: &Bn254GrumpkinVerifierKey,
+    file: &ProofFile,
+) -> Result<VerifiedChainState, Box<dyn Error>> {
+    if file.cycle != CurveCycle::Bn254Grumpkin || file.compression != CompressionBackend::Spartan {
+        return Err(ServerError::UnsupportedProof.into());
+    }
+    if file.network != key.network || file.anchor != key.anchor {
+        return Err(ServerError::KeyMismatch.into());
+    }
+    let proof = Bn254GrumpkinCompressedProof::deserialize_compressed(&file.proof[..])?;
+    key.verify(&proof)
+}
+
+/// What `GET /status` returns, hashes and chainwork are hex as bitcoind reports them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ServerStatus {
+    pub tip_hash: Option<String>,
+    pub height: Option<u32>,
+    pub chainwork: Option<String>,
+    // blocks the source is ahead of the latest proof, unknown until the source height is set
+    pub proving_lag: Option<u32>,
+    pub checkpoints: Vec<u32>,
+}
+
+struct PublishedProof {
+    state: VerifiedChainState,
+    file: Arc<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct Proofs {
+    latest: Option<PublishedProof>,
+    checkpoints: BTreeMap<u32, Arc<Vec<u8>>>,
+    source_height: Option<u32>,
+    metrics: Option<ProverMetrics>,
+}
+
+/// Proofs served by a [`ProofServer`], shared with whatever publishes them.
+#[derive(Clone)]
+pub struct ProofStore {
+    vk: Arc<Vec<u8>>,
+    // proofs at heights that are a multiple of the interval are kept as checkpoints
+    checkpoint_interval: u32,
+    proofs: Arc<RwLock<Proofs>>,
+}
+
+impl ProofStore {
+    pub fn new(vk: Vec<u8>, checkpoint_interval: u32) -> Self {
+        ProofStore {
+            vk: Arc::new(vk),
+            checkpoint_interval: checkpoint_interval.max(1),
+            proofs: Arc::default(),
+        }
+    }
+
+    /// Proofs of heights that are a multiple of the interval are kept as checkpoints.
+    pub fn checkpoint_interval(&self) -> u32 {
+        self.checkpoint_interval
+    }
+
+    /// Publishes the proof of `state` as the latest proof.
+    ///
+    /// A proof of a lower tip than the latest one follows a reorg, the checkpoints from its
+    /// height on are dropped. A reorg to a longer branch keeps the checkpoints below the new tip,
+    /// the publisher drops those of the reorged blocks with [`Self::drop_checkpoints_above`].
+    pub fn publish(&self, state: VerifiedChainState, file: &ProofFile) {
+        let file = Arc::new(file.to_bytes());
+        let mut proofs = self.proofs.write().unwrap();
+        proofs.checkpoints.split_off(&state.tip_height);
+        if state.tip_height.is_multiple_of(self.checkpoint_interval) {
+            proofs.checkpoints.insert(state.tip_height, file.clone());
+        }
+        proofs.latest = Some(PublishedProof { state, file });
+    }
+
+    /// Drops the checkpoints above `height`, the fork point of a reorg.
+    pub fn drop_checkpoints_above(&self, height: u32) {
+        let mut proofs = self.proofs.write().unwrap();
+        proofs.checkpoints.split_off(&height.saturating_add(1));
+    }
+
+    /// Height of the tip of the header source, the proving lag is measured against it.
+    pub fn set_source_height(&self, height: u32) {
+        self.proofs.write().unwrap().source_height = Some(height);
+    }
+
+    /// Metrics of the prover publishing to the store, see [`crate::ProvingDaemon`].
+    pub fn set_metrics(&self, metrics: ProverMetrics) {
+        self.proofs.write().unwrap().metrics = Some(metrics);
+    }
+
+    pub fn metrics(&self) -> Option<ProverMetrics> {
+        self.proofs.read().unwrap().metrics.clone()
+    }
+
+    pub fn latest_state(&self) -> Option<VerifiedChainState> {
+        let proofs = self.proofs.read().unwrap();
+        proofs.latest.as_ref().map(|latest| latest.state.clone())
+    }
+
+    pub fn status(&self) -> ServerStatus {
+        let proofs = self.proofs.read().unwrap();
+        let state = proofs.latest.as_ref().map(|latest| &latest.state);
+        ServerStatus {
+            tip_hash: state.map(|state| {
+                let mut tip_hash = state.tip_hash.clone();
+                tip_hash.reverse();
+                hex::encode(tip_hash)
+            }),
+            height: state.map(|state| state.tip_height),
+            chainwork: state.map(|state| format!("{:064x}", state.chainwork)),
+            proving_lag: proofs.source_height.map(|source_height| {
+                source_height.saturating_sub(state.map_or(0, |state| state.tip_height))
+            }),
+            checkpoints: proofs.checkpoints.keys().copied().collect(),
+        }
+    }
+
+    fn latest_file(&self) -> Option<Arc<Vec<u8>>> {
+        let proofs = self.proofs.read().unwrap();
+        proofs.latest.as_ref().map(|latest| latest.file.clone())
+    }
+
+    fn checkpoint_file(&self, height: u32) -> Option<Arc<Vec<u8>>> {
+        self.proofs
+            .read()
+            .unwrap()
+            .checkpoints
+            .get(&height)
+            .cloned()
+    }
+}
+
+/// HTTP server of a [`ProofStore`], see the module documentation for the endpoints.
+pub struct ProofServer {
+    server: Arc<Server>,
+    store: ProofStore,
+}
+
+impl ProofServer {
+    /// Binds to `addr`, port 0 binds to a free port, see [`ProofServer::local_addr`].
+    pub fn bind(addr: &str, store: ProofStore) -> Result<Self, Box<dyn Error>> {
+        let server = Server::http(addr).map_err(|error| error.to_string())?;
+        Ok(ProofServer {
+            server: Arc::new(server),
+            store,
+        })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// Serves requests until the server is shut down.
+    pub fn run(&self) {
+        for request in self.server.incoming_requests() {
+            let response = self.respond(&request);
+            // the client may be gone already, there is nobody to report the error to
+            let _ = request.respond(response);
+        }
+    }
+
+    /// Serves requests on a thread, the returned handle shuts the server down.
+    pub fn spawn(self) -> ServerHandle {
+        let server = self.server.clone();
+        let thread = thread::spawn(move || self.run());
+        ServerHandle { server, thread }
+    }
+
+    fn respond(&self, request: &Request) -> Response<std::io::Cursor<Vec<u8>>> {
+        if *request.method() != Method::Get {
+            return Response::from_string("method not allowed").with_status_code(405);
+        }
+        let path = request.url().split('?').next().unwrap_or_default();
+        let file = match path {
+            "/vk" => return octet_stream(self.store.vk.to_vec()),
+            "/status" => {
+                let status = serde_json::to_vec(&self.store.status()).expect("status is json");
+                return json(status);
+            }
+            "/metrics" => {
+                return match self.store.metrics() {
+                    Some(metrics) => Response::from_string(prometheus_text(&metrics))
+                        .with_header(content_type("text/plain; version=0.0.4")),
+                    None => Response::from_string("no prover").with_status_code(404),
+                }
+            }
+            "/proof/latest" => self.store.latest_file(),
+            _ => match path.strip_prefix("/proof/").map(str::parse::<u32>) {
+                Some(Ok(height)) => self.store.checkpoint_file(height),
+                Some(Err(_)) => {
+                    return Response::from_string("invalid height").with_status_code(400)
+                }
+                None => None,
+            },
+        };
+        match file {
+            Some(file) => json(file.to_vec()),
+            None => Response::from_string("not found").with_status_code(404),
+        }
+    }
+}
+
+fn prometheus_text(metrics: &ProverMetrics) -> String {
+    let mut text = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: &dyn Display| {
+        let name = format!("bitfold_{name}");
+        text += &format!("# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n");
+    };
+    metric(
+        "proven_height",
+        "gauge",
+        "Height of the proven tip",
+        &metrics.proven_height,
+    );
+    metric(
+        "source_height",
+        "gauge",
+        "Height of the tip of the header source",
+        &metrics.source_height,
+    );
+    metric(
+        "lag",
+        "gauge",
+        "Blocks the header source is ahead of the proven tip",
+        &metrics.lag,
+    );
+    metric(
+        "steps_total",
+        "counter",
+        "Proving steps since the prover started",
+        &metrics.steps,
+    );
+    metric(
+        "steps_per_sec",
+        "gauge",
+        "Proving steps per second of proving time",
+        &metrics.steps_per_sec,
+    );
+    metric(
+        "reorgs_total",
+        "counter",
+        "Reorgs followed since the prover started",
+        &metrics.reorgs,
+    );
+    if let Some(height) = metrics.published_height {
+        metric(
+            "published_height",
+            "gauge",
+            "Height of the latest published proof",
+            &height,
+        );
+    }
+    text
+}
+
+fn json(body: Vec<u8>) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_data(body).with_header(content_type("application/json"))
+}
+
+fn octet_stream(body: Vec<u8>) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_data(body).with_header(content_type("application/octet-stream"))
+}
+
+fn content_type(value: &str) -> Header {
+    Header::from_bytes("Content-Type", value).expect("valid header")
+}
+
+/// Handle of a server serving on a thread.
+pub struct ServerHandle {
+    server: Arc<Server>,
+    thread: JoinHandle<()>,
+}
+
+impl ServerHandle {
+    /// Stops accepting requests and waits for the server thread.
+    pub fn shutdown(self) {
+        self.server.unblock();
+        let _ = self.thread.join();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Anchor, Network, PROOF_FILE_VERSION};
+    use num_bigint::BigUint;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    // minimal HTTP/1.1 client, returns the status code and the body
+    fn get(addr: SocketAddr, path: &str) -> (u16, Vec<u8>) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n"
+        )
+        .unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let split = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .unwrap();
+        let head = String::from_utf8(response[..split].to_vec()).unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, response[split + 4..].to_vec())
+    }
+
+    fn published(height: u32) -> (VerifiedChainState, ProofFile) {
+        let state = VerifiedChainState {
+            tip_hash: vec![height as u8; 32],
+            tip_height: height,
+            chainwork: BigUint::from(2u32 * (height + 1)),
+            accumulator_root: vec![0; 32],
+        };
+        let file = ProofFile {
+            version: PROOF_FILE_VERSION,
+            cycle: CurveCycle::Bn254Grumpkin,
+            network: Network::Regtest,
+            anchor: Anchor::Genesis,
+            num_steps: height as usize,
+            compression: CompressionBackend::Spartan,
+            proof: vec![height as u8],
+        };
+        (state, file)
+    }
+
+    #[test]
+    fn proof_store() {
+        let store = ProofStore::new(vec![1, 2, 3], 10);
+        assert_eq!(store.status().height, None);
+
+        for height in [5, 10, 15, 20, 25] {
+            let (state, file) = published(height);
+            store.publish(state, &file);
+        }
+        store.set_source_height(30);
+        let status = store.status();
+        assert_eq!(status.height, Some(25));
+        assert_eq!(status.tip_hash, Some("19".repeat(32)));
+        assert_eq!(status.chainwork, Some(format!("{:064x}", 52)));
+        assert_eq!(status.proving_lag, Some(5));
+        assert_eq!(status.checkpoints, vec![10, 20]);
+
+        // after a reorg to a lower tip, the checkpoints of the reorged chain are dropped
+        let (state, file) = published(18);
+        store.publish(state.clone(), &file);
+        assert_eq!(store.latest_state(), Some(state));
+        assert_eq!(store.status().checkpoints, vec![10]);
+
+        // a reorg to a longer branch drops the checkpoints above the fork point
+        for height in [20, 30] {
+            let (state, file) = published(height);
+            store.publish(state, &file);
+        }
+        store.drop_checkpoints_above(25);
+        let (state, file) = published(35);
+        store.publish(state, &file);
+        assert_eq!(store.status().checkpoints, vec![10, 20]);
+        store.drop_checkpoints_above(9);
+        assert_eq!(store.status().checkpoints, Vec::<u32>::new());
+    }
+
+    #[test]
+    fn serves_proofs() {
+        let store = ProofStore::new(vec![1, 2, 3], 10);
+        let server = ProofServer::bind("127.0.0.1:0", store.clone()).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.spawn();
+
+        assert_eq!(get(addr, "/proof/latest").0, 404);
+        assert_eq!(get(addr, "/vk"), (200, vec![1, 2, 3]));
+
+        let (state, checkpoint) = published(10);
+        store.publish(state, &checkpoint);
+        let (state, latest) = published(12);
+        store.publish(state, &latest);
+        store.set_source_height(13);
+
+        let (status, body) = get(addr, "/proof/latest");
+        assert_eq!(status, 200);
+        assert_eq!(ProofFile::from_bytes(&body), Ok(latest));
+        let (status, body) = get(addr, "/proof/10");
+        assert_eq!(status, 200);
+        assert_eq!(ProofFile::from_bytes(&body), Ok(checkpoint));
+        assert_eq!(get(addr, "/proof/12").0, 404);
+        assert_eq!(get(addr, "/proof/tip").0, 400);
+        assert_eq!(get(addr, "/unknown").0, 404);
+
+        let (status, body) = get(addr, "/status");
+        assert_eq!(status, 200);
+        let status: ServerStatus = serde_json::from_slice(&body).unwrap();
+        assert_eq!(status, store.status());
+        assert_eq!(status.height, Some(12));
+        assert_eq!(status.proving_lag, Some(1));
+
+        assert_eq!(get(addr, "/metrics").0, 404);
+        store.set_metrics(ProverMetrics {
+            proven_height: 12,
+            source_height: 13,
+            lag: 1,
+            steps: 12,
+            published_height: Some(12),
+            ..Default::default()
+        });
+        let (status, body) = get(addr, "/metrics");
+        assert_eq!(status, 200);
+        let body = String::from_utf8(body).unwrap();
+        assert!(body.contains("# TYPE bitfold_lag gauge\nbitfold_lag 1\n"));
+        assert!(body.contains("bitfold_steps_total 12\n"));
+        assert!(body.contains("bitfold_published_height 12\n"));
+
+        handle.shutdown();
+    }
+}
@@ -0,0 +1,114 @@
+//! Inclusion of transactions in the chain proven by a bitfold proof.
+
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ff::PrimeField;
+use thiserror::Error;
+
+use crate::bitcoin::tx::{self, SpvError, Transaction};
+use crate::mmr::{MmrError, MmrProof};
+use crate::{BitcoinHeader, ChainState};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum InclusionError {
+    #[error(transparent)]
+    Spv(#[from] SpvError),
+    #[error("header at height {0} is not the tip of the proven state")]
+    NotTip(u32),
+    #[error("header at height {0} is not in the proven chain: {1}")]
+    NotInChain(u32, MmrError),
+}
+
+/// Proof that a transaction is confirmed in the chain proven by a bitfold proof.
+///
+/// The header of the block is attested by the verified state, either as its tip or, for older
+/// blocks, by a membership proof against the accumulator of the state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxInclusionProof {
+    pub raw_tx: Vec<u8>,
+    // index of the transaction in the block
+    pub tx_index: u32,
+    pub merkle_branch: Vec<Vec<u8>>,
+    pub header: BitcoinHeader,
+    pub height: u32,
+    // `None` if the block is the tip of the proven state
+    pub header_membership: Option<MmrProof>,
+}
+
+impl TxInclusionProof {
+    /// Checks the transaction is in the chain proven by `state` and returns it, the state must
+    /// have been verified.
+    pub fn verify<F: PrimeField + Absorb>(
+        &self,
+        state: &ChainState,
+    ) -> Result<Transaction, InclusionError> {
+        let block_hash = self.header.hash();
+        match &self.header_membership {
+            None if self.height != state.height || block_hash != state.tip_hash => {
+                return Err(InclusionError::NotTip(self.height));
+            }
+            None => {}
+            Some(membership) => {
+                if membership.height != self.height {
+                    return Err(InclusionError::NotInChain(
+                        self.height,
+                        MmrError::UnknownHeight(self.height),
+                    ));
+                }
+                membership
+                    .verify::<F>(state, &block_hash)
+                    .map_err(|source| InclusionError::NotInChain(self.height, source))?;
+            }
+        }
+        Ok(tx::verify_tx_inclusion(
+            &self.raw_tx,
+            self.tx_index,
+            &self.merkle_branch,
+            &self.header,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bitcoin::data::test_json::TEST_JSON_RPC_GENESIS;
+    use crate::bitcoin::BlockReader;
+    use crate::mmr::HeaderMmr;
+    use ark_bn254::Fr;
+
+    // coinbase of block 1, the only transaction of the block
+    const BLOCK_1_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0704ffff001d0104ffffffff0100f2052a0100000043410496b538e853519c726a2c91e61ec11600ae1390813a627c66fb8be7947be63c52da7589379515d4e0a604f8141781e62294721166bf621e73a82cbf2342c858eeac00000000";
+
+    #[test]
+    fn verify_transaction_in_proven_chain() {
+        let reader = BlockReader::new_from_json(TEST_JSON_RPC_GENESIS).unwrap();
+        let headers = reader.get_block_headers().unwrap();
+        let mut state = ChainState::from_header::<Fr>(reader.params(), &headers[0].1, 0);
+        let mut mmr = HeaderMmr::<Fr>::new();
+        for (height, header) in &headers[1..] {
+            state = state.next::<Fr>(reader.params(), header);
+            mmr.push(*height, &header.hash(), &state.chainwork).unwrap();
+        }
+
+        // block 1 is attested by the accumulator, not by the tip
+        let mut proof = TxInclusionProof {
+            raw_tx: hex::decode(BLOCK_1_COINBASE).unwrap(),
+            tx_index: 0,
+            merkle_branch: Vec::new(),
+            header: headers[1].1.clone(),
+            height: 1,
+            header_membership: None,
+        };
+        assert_eq!(proof.verify::<Fr>(&state), Err(InclusionError::NotTip(1)));
+
+        proof.header_membership = Some(mmr.prove(1).unwrap());
+        let tx = proof.verify::<Fr>(&state).unwrap();
+        assert!(tx.is_coinbase());
+
+        proof.header_membership = Some(mmr.prove(2).unwrap());
+        assert!(matches!(
+            proof.verify::<Fr>(&state),
+            Err(InclusionError::NotInChain(1, _))
+        ));
+    }
+}
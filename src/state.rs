@@ -0,0 +1,341 @@
+//! Layout of the folded state `z` carried by [`BitcoinHeaderCircuit`](crate::BitcoinHeaderCircuit).
+
+use core::ops::Range;
+
+use ark_crypto_primitives::sponge::{poseidon::PoseidonSponge, Absorb, CryptographicSponge};
+use ark_ff::{BigInteger, PrimeField};
+use ark_std::{vec, vec::Vec};
+use nexus_nova::poseidon_config;
+use num_bigint::BigUint;
+use thiserror::Error;
+
+use crate::bitcoin::{pow, BitcoinHeader, ChainParams, Checkpoint, Network};
+use crate::mmr::{self, MAX_MMR_PEAKS};
+
+// id of the network the proven chain belongs to
+pub(crate) const NETWORK_ID: usize = 0;
+// hash of the last proven block, one byte per field element in internal byte order
+pub(crate) const TIP_HASH: Range<usize> = 1..33;
+// height of the last proven block
+pub(crate) const HEIGHT: usize = 33;
+// compact target of the last block that was not mined at min-difficulty
+pub(crate) const BITS: usize = 34;
+// total work of the proven chain as its low and high 128-bit limbs
+pub(crate) const CHAINWORK: Range<usize> = 35..37;
+// timestamp of the first block of the current difficulty adjustment period
+pub(crate) const PERIOD_START_TIMESTAMP: usize = 37;
+// timestamps of the last 11 proven blocks, oldest first
+pub(crate) const MTP_WINDOW: Range<usize> = 38..49;
+// timestamp of the last proven block
+pub(crate) const TIMESTAMP: usize = MTP_WINDOW.end - 1;
+// number of leaves of the merkle mountain range of the proven block hashes
+pub(crate) const MMR_NUM_LEAVES: usize = 49;
+// peak slots of the merkle mountain range, see `mmr`
+pub(crate) const MMR_PEAKS: Range<usize> = 50..82;
+// digest of the elements of z_0 before the anchor, identifies the state the proof started from
+pub(crate) const ANCHOR: usize = 82;
+
+pub(crate) const STATE_LEN: usize = 83;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum StateError {
+    #[error("state has {0} elements, expected {1}")]
+    InvalidLength(usize, usize),
+    #[error("state element {0} is out of range")]
+    OutOfRange(usize),
+    #[error("unknown network id {0:#010x}")]
+    UnknownNetwork(u32),
+}
+
+/// Native view of the folded state, i.e. the public input z_0 and output z_i of the IVC proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainState {
+    pub network: Network,
+    pub tip_hash: Vec<u8>,
+    pub height: u32,
+    // bits required for the next block, unless it is a retarget or a min-difficulty block.
+    // On networks without min-difficulty blocks these are the bits of the tip.
+    pub bits: u32,
+    pub chainwork: BigUint,
+    pub period_start_timestamp: u32,
+    // timestamps of the last 11 blocks, oldest first
+    pub mtp_window: Vec<u32>,
+    // merkle mountain range of the blocks proven since the anchor, peaks as little-endian field
+    // elements
+    pub mmr_num_leaves: u32,
+    pub mmr_peaks: Vec<Vec<u8>>,
+    // anchor digest as a little-endian field element, see `anchor_digest`
+    pub anchor: Vec<u8>,
+}
+
+impl ChainState {
+    /// State of a proof starting at `checkpoint`.
+    pub fn from_checkpoint<F: PrimeField + Absorb>(
+        network: Network,
+        checkpoint: &Checkpoint,
+    ) -> ChainState {
+        let mut state = ChainState {
+            network,
+            tip_hash: checkpoint.hash.clone(),
+            height: checkpoint.height,
+            bits: checkpoint.bits,
+            chainwork: checkpoint.chainwork.clone(),
+            period_start_timestamp: checkpoint.period_start_timestamp,
+            mtp_window: checkpoint.mtp_window.clone(),
+            mmr_num_leaves: 0,
+            mmr_peaks: vec![mmr::field_to_bytes(&F::zero()); MAX_MMR_PEAKS],
+            anchor: Vec::new(),
+        };
+        state.anchor = state.anchor_digest::<F>().into_bigint().to_bytes_le();
+        state
+    }
+
+    /// State of a proof starting at `header`, see [`Checkpoint::from_header`].
+    pub fn from_header<F: PrimeField + Absorb>(
+        params: &ChainParams,
+        header: &BitcoinHeader,
+        height: u32,
+    ) -> ChainState {
+        Self::from_checkpoint::<F>(params.network, &Checkpoint::from_header(header, height))
+    }
+
+    /// Timestamp of the tip.
+    pub fn timestamp(&self) -> u32 {
+        *self.mtp_window.last().expect("non-empty window")
+    }
+
+    /// Poseidon digest of the elements of the state before the anchor.
+    ///
+    /// The step circuit enforces the anchor of z_0 to be the digest of z_0 and passes it on to
+    /// z_i, so the anchor of a proven state identifies the state the proof started from.
+    pub fn anchor_digest<F: PrimeField + Absorb>(&self) -> F {
+        poseidon_hash(&self.to_field_elements::<F>()[..ANCHOR])
+    }
+
+    /// Poseidon digest of the whole state, binds statements proven about the chain, e.g. by a
+    /// [`TxInclusionCircuit`](crate::TxInclusionCircuit), to a verified z_i.
+    pub fn digest<F: PrimeField + Absorb>(&self) -> F {
+        poseidon_hash(&self.to_field_elements::<F>())
+    }
+
+    /// Root of the merkle mountain range of the blocks proven since the anchor, membership of a
+    /// block is checked with [`MmrProof::verify`](crate::MmrProof::verify).
+    pub fn mmr_root<F: PrimeField + Absorb>(&self) -> F {
+        let peaks: Vec<F> = self
+            .mmr_peaks
+            .iter()
+            .map(|peak| F::from_le_bytes_mod_order(peak))
+            .collect();
+        mmr::bag_peaks(self.mmr_num_leaves, &peaks)
+    }
+
+    /// Finds the checkpoint the proof of this state started from among `checkpoints`.
+    pub fn find_checkpoint<'a, F: PrimeField + Absorb>(
+        &self,
+        checkpoints: &'a [Checkpoint],
+    ) -> Option<&'a Checkpoint> {
+        checkpoints.iter().find(|checkpoint| {
+            ChainState::from_checkpoint::<F>(self.network, checkpoint).anchor == self.anchor
+        })
+    }
+
+    /// State after proving `header` on top of this state, as computed by the step circuit.
+    pub fn next<F: PrimeField + Absorb>(
+        &self,
+        params: &ChainParams,
+        header: &BitcoinHeader,
+    ) -> ChainState {
+        let height = self.height + 1;
+        let is_retarget = !params.pow_no_retargeting
+            && height.is_multiple_of(params.difficulty_adjustment_interval());
+        // min-difficulty blocks are skipped when looking back for the bits of the next block
+        let bits = if !params.pow_allow_min_difficulty_blocks
+            || is_retarget
+            || header.bits() != params.pow_limit_bits
+        {
+            header.bits()
+        } else {
+            self.bits
+        };
+        let work = pow::compact_to_target(header.bits())
+            .map(|target| pow::block_work(&target))
+            .unwrap_or_default();
+        let period_start_timestamp = if is_retarget {
+            header.timestamp
+        } else {
+            self.period_start_timestamp
+        };
+        let mut mtp_window = self.mtp_window[1..].to_vec();
+        mtp_window.push(header.timestamp);
+
+        // append the block hash, merging the peaks of the set low bits of the number of leaves
+        let chainwork = &self.chainwork + work;
+        let mut node = mmr::leaf_hash::<F>(height, &header.hash(), &chainwork)
+            .expect("block hashes are 32 bytes");
+        let mut mmr_peaks = self.mmr_peaks.clone();
+        for (k, peak) in mmr_peaks.iter_mut().enumerate() {
+            if (self.mmr_num_leaves >> k) & 1 == 0 {
+                *peak = mmr::field_to_bytes(&node);
+                break;
+            }
+            node = mmr::node_hash(&F::from_le_bytes_mod_order(peak), &node);
+            *peak = mmr::field_to_bytes(&F::zero());
+        }
+
+        ChainState {
+            network: self.network,
+            tip_hash: header.hash(),
+            height,
+            bits,
+            chainwork,
+            period_start_timestamp,
+            mtp_window,
+            mmr_num_leaves: self.mmr_num_leaves + 1,
+            mmr_peaks,
+            anchor: self.anchor.clone(),
+        }
+    }
+
+    pub fn to_field_elements<F: PrimeField>(&self) -> Vec<F> {
+        let mut chainwork = self.chainwork.to_bytes_le();
+        chainwork.resize(32, 0);
+
+        let mut z = Vec::with_capacity(STATE_LEN);
+        z.push(F::from(self.network.id()));
+        z.extend(self.tip_hash.iter().map(|byte| F::from(*byte)));
+        z.push(F::from(self.height));
+        z.push(F::from(self.bits));
+        z.push(F::from_le_bytes_mod_order(&chainwork[..16]));
+        z.push(F::from_le_bytes_mod_order(&chainwork[16..]));
+        z.push(F::from(self.period_start_timestamp));
+        z.extend(self.mtp_window.iter().map(|timestamp| F::from(*timestamp)));
+        z.push(F::from(self.mmr_num_leaves));
+        z.extend(
+            self.mmr_peaks
+                .iter()
+                .map(|peak| F::from_le_bytes_mod_order(peak)),
+        );
+        z.push(F::from_le_bytes_mod_order(&self.anchor));
+        z
+    }
+
+    pub fn from_field_elements<F: PrimeField>(z: &[F]) -> Result<ChainState, StateError> {
+        if z.len() != STATE_LEN {
+            return Err(StateError::InvalidLength(z.len(), STATE_LEN));
+        }
+        let network_id = field_to_u32(NETWORK_ID, &z[NETWORK_ID])?;
+        let network = Network::from_id(network_id).ok_or(StateError::UnknownNetwork(network_id))?;
+        let tip_hash = TIP_HASH
+            .map(|index| {
+                let value = field_to_u32(index, &z[index])?;
+                u8::try_from(value).map_err(|_| StateError::OutOfRange(index))
+            })
+            .collect::<Result<Vec<u8>, StateError>>()?;
+        let mut chainwork = Vec::with_capacity(32);
+        for index in CHAINWORK {
+            let bytes = z[index].into_bigint().to_bytes_le();
+            if bytes[16..].iter().any(|byte| *byte != 0) {
+                return Err(StateError::OutOfRange(index));
+            }
+            chainwork.extend_from_slice(&bytes[..16]);
+        }
+        let mtp_window = MTP_WINDOW
+            .map(|index| field_to_u32(index, &z[index]))
+            .collect::<Result<Vec<u32>, StateError>>()?;
+
+        Ok(ChainState {
+            network,
+            tip_hash,
+            height: field_to_u32(HEIGHT, &z[HEIGHT])?,
+            bits: field_to_u32(BITS, &z[BITS])?,
+            chainwork: BigUint::from_bytes_le(&chainwork),
+            period_start_timestamp: field_to_u32(
+                PERIOD_START_TIMESTAMP,
+                &z[PERIOD_START_TIMESTAMP],
+            )?,
+            mtp_window,
+            mmr_num_leaves: field_to_u32(MMR_NUM_LEAVES, &z[MMR_NUM_LEAVES])?,
+            mmr_peaks: z[MMR_PEAKS].iter().map(mmr::field_to_bytes).collect(),
+            anchor: z[ANCHOR].into_bigint().to_bytes_le(),
+        })
+    }
+}
+
+/// Poseidon digest of `elements`, with the sponge of the folding scheme.
+pub(crate) fn poseidon_hash<F: PrimeField + Absorb>(elements: &[F]) -> F {
+    let mut sponge = PoseidonSponge::<F>::new(&poseidon_config());
+    sponge.absorb(&elements.to_vec());
+    sponge.squeeze_field_elements::<F>(1)[0]
+}
+
+fn field_to_u32<F: PrimeField>(index: usize, value: &F) -> Result<u32, StateError> {
+    let bytes = value.into_bigint().to_bytes_le();
+    if bytes[4..].iter().any(|byte| *byte != 0) {
+        return Err(StateError::OutOfRange(index));
+    }
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_bn254::Fr;
+
+    fn test_checkpoint() -> Checkpoint {
+        Checkpoint {
+            height: 838637,
+            hash: (0..32).collect(),
+            chainwork: BigUint::from(1u32) << 200,
+            bits: 0x1d00ffff,
+            period_start_timestamp: 1712000000,
+            mtp_window: (0..11).map(|i| 1712770500 + i).collect(),
+        }
+    }
+
+    #[test]
+    fn state_field_elements_round_trip() {
+        let state = ChainState::from_checkpoint::<Fr>(Network::Testnet4, &test_checkpoint());
+        let z = state.to_field_elements::<Fr>();
+        assert_eq!(z.len(), STATE_LEN);
+        assert_eq!(z[TIMESTAMP], Fr::from(state.timestamp()));
+        assert_eq!(ChainState::from_field_elements(&z), Ok(state));
+    }
+
+    #[test]
+    fn anchor_identifies_checkpoint() {
+        let genesis = Checkpoint::genesis(&ChainParams::mainnet());
+        let checkpoints = [genesis.clone(), test_checkpoint()];
+        let state = ChainState::from_checkpoint::<Fr>(Network::Mainnet, &test_checkpoint());
+        assert_eq!(
+            state.find_checkpoint::<Fr>(&checkpoints),
+            Some(&checkpoints[1])
+        );
+
+        // the anchor is passed on to the following states
+        let header = ChainParams::mainnet().genesis;
+        let next = state.next::<Fr>(&ChainParams::mainnet(), &header);
+        assert_eq!(
+            next.find_checkpoint::<Fr>(&checkpoints),
+            Some(&checkpoints[1])
+        );
+
+        // any other start state is a different anchor
+        let other = Checkpoint {
+            height: genesis.height + 1,
+            ..genesis
+        };
+        let state = ChainState::from_checkpoint::<Fr>(Network::Mainnet, &other);
+        assert_eq!(state.find_checkpoint::<Fr>(&checkpoints), None);
+    }
+
+    #[test]
+    fn reject_unknown_network() {
+        let mut z = ChainState::from_checkpoint::<Fr>(Network::Mainnet, &test_checkpoint())
+            .to_field_elements::<Fr>();
+        z[NETWORK_ID] = Fr::from(7u32);
+        assert_eq!(
+            ChainState::from_field_elements(&z),
+            Err(StateError::UnknownNetwork(7))
+        );
+    }
+}
@@ -0,0 +1,30 @@
+//! Helpers for the tests of this crate and of the crates built on it, see the `test-utils`
+//! feature.
+
+use ark_std::{vec, vec::Vec};
+
+use crate::bitcoin::pow;
+use crate::{BitcoinHeader, ChainParams};
+
+/// Mines `count` regtest headers on top of `parent` with merkle roots of `tag`, so that branches
+/// from the same parent differ, 10 minutes apart. The regtest target is met by about half of the
+/// hashes.
+pub fn mine_regtest_headers(parent: &BitcoinHeader, count: usize, tag: u8) -> Vec<BitcoinHeader> {
+    let target = pow::compact_to_target(ChainParams::regtest().pow_limit_bits).unwrap();
+    let mut headers: Vec<BitcoinHeader> = Vec::with_capacity(count);
+    for _ in 0..count {
+        let parent = headers.last().unwrap_or(parent);
+        let mut header = BitcoinHeader {
+            hash_prev_block: parent.hash(),
+            hash_merkle_root: vec![tag; 32],
+            timestamp: parent.timestamp + 600,
+            nonce: 0,
+            ..parent.clone()
+        };
+        while pow::hash_to_uint(&header.hash()) > target {
+            header.nonce += 1;
+        }
+        headers.push(header);
+    }
+    headers
+}
@@ -0,0 +1,253 @@
+//! Verification of compressed proofs of a header chain for consumers that do not prove.
+//!
+//! A [`VerifierKey`] is set up once for a network and an anchor and handed to verifiers, which
+//! verify compressed proofs with it and learn the [`VerifiedChainState`] they prove.
+//!
+//! The key is not compact yet. It should only carry the network, the anchor, the digest of the
+//! public parameters and the SNARK key, but `SNARK::verify` of nexus-nova takes the full public
+//! parameters, of which it reads the digest, the random oracle config and the shape and commitment
+//! parameters of the secondary circuit. This is blocked until nexus-nova verifies from these
+//! alone; then `params` is replaced by the secondary parts and the primary shape and commitment
+//! key, the bulk of the key, are dropped. Until then `from_bytes` checks the carried parameters
+//! against `params_digest`.
+
+use core::error::Error;
+
+use ark_bn254::{g1::Config as Bn254Config, Bn254};
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ec::short_weierstrass::{Projective, SWCurveConfig};
+use ark_ff::PrimeField;
+use ark_grumpkin::{GrumpkinConfig, Projective as GrumpkinProjective};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use ark_spartan::polycommitments::{zeromorph::Zeromorph, PolyCommitmentScheme};
+use ark_std::{boxed::Box, vec::Vec};
+use nexus_nova::{
+    circuits::nova::sequential::compression::{SNARKKey, PVC},
+    commitment::CommitmentScheme,
+    pedersen::PedersenCommitment,
+};
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::ivc::{self, BitcoinCompressedProof, BitcoinPublicParams, BitcoinSNARK};
+use crate::mmr;
+use crate::{Anchor, ChainParams, ChainState, Network};
+
+/// Key verifying compressed proofs over BN254/Grumpkin, the cycle whose proofs compress.
+pub type Bn254GrumpkinVerifierKey = VerifierKey<
+    Bn254Config,
+    GrumpkinConfig,
+    Zeromorph<Bn254>,
+    PedersenCommitment<GrumpkinProjective>,
+>;
+
+/// Compressed proof over BN254/Grumpkin as verified by a [`Bn254GrumpkinVerifierKey`].
+pub type Bn254GrumpkinCompressedProof = BitcoinCompressedProof<
+    Bn254Config,
+    GrumpkinConfig,
+    Zeromorph<Bn254>,
+    PedersenCommitment<GrumpkinProjective>,
+>;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum VerifierError {
+    #[error("unknown network id {0:#x}")]
+    UnknownNetwork(u32),
+    #[error("public parameters do not match the digest of the key")]
+    ParamsMismatch,
+}
+
+/// What a verified proof shows about the chain.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VerifiedChainState {
+    // block hash in internal byte order
+    pub tip_hash: Vec<u8>,
+    pub tip_height: u32,
+    pub chainwork: BigUint,
+    // root of the merkle mountain range of the proven blocks as a little-endian field element,
+    // see `ChainState::mmr_root`
+    pub accumulator_root: Vec<u8>,
+}
+
+impl VerifiedChainState {
+    pub fn of<F: PrimeField + Absorb>(state: &ChainState) -> Self {
+        VerifiedChainState {
+            tip_hash: state.tip_hash.clone(),
+            tip_height: state.height,
+            chainwork: state.chainwork.clone(),
+            accumulator_root: mmr::field_to_bytes(&state.mmr_root::<F>()),
+        }
+    }
+}
+
+/// Key verifying compressed proofs of the chain of `network` from `anchor`.
+pub struct VerifierKey<G1, G2, PC, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    PC: PolyCommitmentScheme<Projective<G1>>,
+    PC::Commitment: Copy + Into<Projective<G1>> + From<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    pub network: Network,
+    pub anchor: Anchor,
+    // digest of the public parameters, binds the key to the step circuit
+    pub params_digest: G1::ScalarField,
+    pub snark_key: SNARKKey<G1, PC>,
+    // full public parameters, as nexus-nova verifies with them, see the module docs
+    params: BitcoinPublicParams<G1, G2, PVC<G1, PC>, C2>,
+}
+
+impl<G1, G2, PC, C2> VerifierKey<G1, G2, PC, C2>
+where
+    G1: SWCurveConfig,
+    G2: SWCurveConfig<BaseField = G1::ScalarField, ScalarField = G1::BaseField>,
+    G1::BaseField: PrimeField + Absorb,
+    G2::BaseField: PrimeField + Absorb,
+    PC: PolyCommitmentScheme<Projective<G1>>,
+    PC::Commitment: Copy + Into<Projective<G1>> + From<Projective<G1>>,
+    C2: CommitmentScheme<Projective<G2>, SetupAux = ()>,
+{
+    /// Sets up the key from the SRS the proofs are compressed with.
+    pub fn setup(
+        chain_params: &ChainParams,
+        anchor: Anchor,
+        srs: &PC::SRS,
+    ) -> Result<Self, Box<dyn Error>> {
+        let params = ivc::setup_params::<G1, G2, PVC<G1, PC>, C2>(chain_params, &anchor, srs)?;
+        let snark_key = BitcoinSNARK::<G1, G2, PC, C2>::setup(&params, srs)?;
+        Ok(VerifierKey {
+            network: chain_params.network,
+            anchor,
+            params_digest: params.digest,
+            snark_key,
+            params,
+        })
+    }
+
+    /// Verifies a compressed proof and returns what it shows about the chain.
+    pub fn verify(
+        &self,
+        proof: &BitcoinCompressedProof<G1, G2, PC, C2>,
+    ) -> Result<VerifiedChainState, Box<dyn Error>> {
+        let chain_params = ChainParams::for_network(self.network);
+        let state = ivc::verify_compressed(
+            &self.snark_key,
+            &self.params,
+            &chain_params,
+            &self.anchor,
+            proof,
+        )?;
+        Ok(VerifiedChainState::of::<G1::ScalarField>(&state))
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SerializationError> {
+        let mut bytes = Vec::new();
+        self.network.id().serialize_compressed(&mut bytes)?;
+        serde_json::to_vec(&self.anchor)
+            .expect("anchors serialize to json")
+            .serialize_compressed(&mut bytes)?;
+        self.params_digest.serialize_compressed(&mut bytes)?;
+        self.snark_key.serialize_compressed(&mut bytes)?;
+        self.params.serialize_compressed(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let network_id = u32::deserialize_compressed(&mut bytes)?;
+        let network =
+            Network::from_id(network_id).ok_or(VerifierError::UnknownNetwork(network_id))?;
+        let anchor = serde_json::from_slice(&Vec::<u8>::deserialize_compressed(&mut bytes)?)?;
+        let params_digest = G1::ScalarField::deserialize_compressed(&mut bytes)?;
+        let snark_key = SNARKKey::<G1, PC>::deserialize_compressed(&mut bytes)?;
+        let params =
+            BitcoinPublicParams::<G1, G2, PVC<G1, PC>, C2>::deserialize_compressed(&mut bytes)?;
+        if params.digest != params_digest {
+            return Err(VerifierError::ParamsMismatch.into());
+        }
+        Ok(VerifierKey {
+            network,
+            anchor,
+            params_digest,
+            snark_key,
+            params,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(feature = "std")]
+    use crate::bitcoin_fold_tests::setup_srs;
+    #[cfg(feature = "std")]
+    use crate::test_utils::mine_regtest_headers;
+    use crate::HeaderMmr;
+    #[cfg(feature = "std")]
+    use crate::{Bn254GrumpkinIVC, Checkpoint};
+    use ark_bn254::Fr;
+
+    #[test]
+    fn verified_chain_state() {
+        let chain_params = ChainParams::regtest();
+        let state = Anchor::Genesis.initial_state::<Fr>(&chain_params);
+        let verified = VerifiedChainState::of::<Fr>(&state);
+        assert_eq!(verified.tip_hash, chain_params.genesis_hash);
+        assert_eq!(verified.tip_height, 0);
+        assert_eq!(verified.chainwork, state.chainwork);
+
+        // the accumulator is the merkle mountain range of the blocks proven since the anchor
+        let mut mmr = HeaderMmr::<Fr>::new();
+        assert_eq!(verified.accumulator_root, mmr::field_to_bytes(&mmr.root()));
+        let header = chain_params.genesis.clone();
+        let next = state.next::<Fr>(&chain_params, &header);
+        mmr.push(next.height, &next.tip_hash, &next.chainwork)
+            .unwrap();
+        assert_eq!(
+            VerifiedChainState::of::<Fr>(&next).accumulator_root,
+            mmr::field_to_bytes(&mmr.root())
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn verify_with_key() {
+        let chain_params = ChainParams::regtest();
+        let headers = mine_regtest_headers(&chain_params.genesis, 2, 1);
+
+        let srs = setup_srs::<Bn254Config, Zeromorph<Bn254>>();
+        let mut ivc = Bn254GrumpkinIVC::setup(chain_params.clone(), Anchor::Genesis, &srs).unwrap();
+        ivc.extend(&headers).unwrap();
+        let key = Bn254GrumpkinVerifierKey::setup(&chain_params, Anchor::Genesis, &srs).unwrap();
+        let proof = ivc.compress(&key.snark_key).unwrap();
+
+        // the verifier only needs the key and the compressed proof
+        let key = Bn254GrumpkinVerifierKey::from_bytes(&key.to_bytes().unwrap()).unwrap();
+        let verified = key.verify(&proof).unwrap();
+        assert_eq!(
+            verified,
+            VerifiedChainState::of::<Fr>(&ivc.verify().unwrap())
+        );
+        assert_eq!(verified.tip_hash, headers[1].hash());
+        assert_eq!(verified.tip_height, 2);
+
+        // keys are specific to the anchor
+        let checkpoint = Checkpoint::from_header(&headers[0], 1);
+        let other_key =
+            Bn254GrumpkinVerifierKey::setup(&chain_params, Anchor::Unanchored(checkpoint), &srs)
+                .unwrap();
+        assert!(other_key.verify(&proof).is_err());
+
+        // keys of unknown networks are rejected
+        let mut bytes = key.to_bytes().unwrap();
+        bytes[..4].copy_from_slice(&[0; 4]);
+        let error = Bn254GrumpkinVerifierKey::from_bytes(&bytes).err().unwrap();
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&VerifierError::UnknownNetwork(0))
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Proves a regtest chain with `bitfold prove` and serves the proof with `bitfold serve`.
+#![cfg(feature = "server")]
+
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bitcoin_fold::test_utils::mine_regtest_headers;
+use bitcoin_fold::{BitcoinHeader, ChainParams};
+use serde_json::json;
+
+// hex of a hash in the reversed byte order of bitcoind
+fn rpc_hex(hash: &[u8]) -> String {
+    hash.iter()
+        .rev()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+// headers from genesis in the format of bitcoind's `getblockheader`
+fn rpc_json(headers: &[BitcoinHeader]) -> String {
+    let headers: Vec<_> = headers
+        .iter()
+        .enumerate()
+        .map(|(height, header)| {
+            json!({
+                "hash": rpc_hex(&header.hash()),
+                "confirmations": headers.len() - height,
+                "height": height,
+                "version": header.version,
+                "merkleroot": rpc_hex(&header.hash_merkle_root),
+                "time": header.timestamp,
+                "nonce": header.nonce,
+                "bits": format!("{:08x}", header.bits()),
+                "previousblockhash": rpc_hex(&header.hash_prev_block),
+            })
+        })
+        .collect();
+    serde_json::to_string(&headers).unwrap()
+}
+
+fn bitfold(args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_bitfold"));
+    command.args(args);
+    command
+}
+
+fn run(args: &[&str]) {
+    let status = bitfold(args).status().unwrap();
+    assert!(status.success(), "bitfold {args:?} failed");
+}
+
+fn path(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+// minimal HTTP/1.1 client, returns the status code and the body
+fn get(addr: SocketAddr, path: &str) -> Option<(u16, Vec<u8>)> {
+    let mut stream = TcpStream::connect(addr).ok()?;
+    write!(
+        stream,
+        "GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n"
+    )
+    .ok()?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).ok()?;
+    let split = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")?;
+    let head = String::from_utf8(response[..split].to_vec()).ok()?;
+    let status = head.split(' ').nth(1)?.parse().ok()?;
+    Some((status, response[split + 4..].to_vec()))
+}
+
+// kills the server when the test ends, also when it fails
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn prove_and_serve() {
+    let genesis = ChainParams::regtest().genesis;
+    let [block_1, block_2]: [BitcoinHeader; 2] =
+        mine_regtest_headers(&genesis, 2, 0).try_into().unwrap();
+
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cli");
+    let proofs = dir.join("proofs");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&proofs).unwrap();
+    let headers = dir.join("headers.json");
+    fs::write(&headers, rpc_json(&[genesis, block_1, block_2.clone()])).unwrap();
+    let vk = dir.join("vk.bin");
+    let proof = proofs.join("proof-2.json");
+
+    run(&["vk", "--network", "regtest", "--out", path(&vk)]);
+    run(&[
+        "prove",
+        "--headers",
+        path(&headers),
+        "--network",
+        "regtest",
+        "--compress",
+        "--out",
+        path(&proof),
+    ]);
+
+    // the port is free once the listener is dropped, the server binds it right after
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let _server = Server(
+        bitfold(&[
+            "serve",
+            "--vk",
+            path(&vk),
+            "--proofs",
+            path(&proofs),
+            "--addr",
+            &addr.to_string(),
+            "--poll-secs",
+            "1",
+        ])
+        .spawn()
+        .unwrap(),
+    );
+
+    // the proof is published once the server verified it
+    let deadline = Instant::now() + Duration::from_secs(120);
+    let served = loop {
+        match get(addr, "/proof/latest") {
+            Some((200, body)) => break body,
+            _ if Instant::now() < deadline => thread::sleep(Duration::from_millis(500)),
+            response => panic!("proof not served: {response:?}"),
+        }
+    };
+    assert_eq!(served, fs::read(&proof).unwrap());
+    assert_eq!(get(addr, "/vk"), Some((200, fs::read(&vk).unwrap())));
+
+    let (status, body) = get(addr, "/status").unwrap();
+    assert_eq!(status, 200);
+    let status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(status["height"], 2);
+    assert_eq!(status["tip_hash"], rpc_hex(&block_2.hash()));
+}
+
+#[test]
+fn refuse_seeded_srs_outside_regtest() {
+    let vk = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("vk-mainnet.bin");
+    let _ = fs::remove_file(&vk);
+    let status = bitfold(&["vk", "--out", path(&vk)]).status().unwrap();
+    assert!(!status.success());
+    assert!(!vk.exists());
+}
@@ -0,0 +1,93 @@
+//! WebAssembly bindings of the bitfold verifier.
+//!
+//! Builds for `wasm32-unknown-unknown` on top of the verifier path of `bitcoin-fold`, without its
+//! `std` feature, i.e. without the prover, its threads and its block sources. Proofs are canonical
+//! compressed serializations of a [`Bn254GrumpkinCompressedProof`], keys the bytes of
+//! [`Bn254GrumpkinVerifierKey::to_bytes`].
+
+use std::error::Error;
+
+use ark_serialize::CanonicalDeserialize;
+use bitcoin_fold::{Bn254GrumpkinCompressedProof, Bn254GrumpkinVerifierKey, VerifiedChainState};
+use wasm_bindgen::prelude::*;
+
+/// What a verified proof shows about the chain, see [`VerifiedChainState`].
+#[wasm_bindgen]
+pub struct VerifiedState(VerifiedChainState);
+
+#[wasm_bindgen]
+impl VerifiedState {
+    /// Block hash of the tip in internal byte order.
+    #[wasm_bindgen(getter, js_name = tipHash)]
+    pub fn tip_hash(&self) -> Vec<u8> {
+        self.0.tip_hash.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = tipHeight)]
+    pub fn tip_height(&self) -> u32 {
+        self.0.tip_height
+    }
+
+    /// Chainwork as a decimal string, it does not fit a js number.
+    #[wasm_bindgen(getter)]
+    pub fn chainwork(&self) -> String {
+        self.0.chainwork.to_string()
+    }
+
+    #[wasm_bindgen(getter, js_name = accumulatorRoot)]
+    pub fn accumulator_root(&self) -> Vec<u8> {
+        self.0.accumulator_root.clone()
+    }
+}
+
+/// Verifies a compressed proof with a verifier key and returns the state it proves.
+#[wasm_bindgen]
+pub fn verify(proof_bytes: &[u8], vk_bytes: &[u8]) -> Result<VerifiedState, JsError> {
+    verify_bytes(proof_bytes, vk_bytes)
+        .map(VerifiedState)
+        .map_err(|error| JsError::new(&error.to_string()))
+}
+
+fn verify_bytes(proof_bytes: &[u8], vk_bytes: &[u8]) -> Result<VerifiedChainState, Box<dyn Error>> {
+    let key = Bn254GrumpkinVerifierKey::from_bytes(vk_bytes)?;
+    let proof = Bn254GrumpkinCompressedProof::deserialize_compressed(proof_bytes)?;
+    key.verify(&proof)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ark_bn254::Bn254;
+    use ark_serialize::CanonicalSerialize;
+    use ark_spartan::polycommitments::{zeromorph::Zeromorph, PolyCommitmentScheme};
+    use ark_std::test_rng;
+    use bitcoin_fold::test_utils::mine_regtest_headers;
+    use bitcoin_fold::{Anchor, Bn254GrumpkinIVC, ChainParams};
+
+    #[test]
+    fn verifies_proof_bytes() {
+        let chain_params = ChainParams::regtest();
+        let header = mine_regtest_headers(&chain_params.genesis, 1, 0).remove(0);
+
+        let srs =
+            <Zeromorph<Bn254> as PolyCommitmentScheme<_>>::setup(25, b"test_srs", &mut test_rng())
+                .unwrap();
+        let key = Bn254GrumpkinVerifierKey::setup(&chain_params, Anchor::Genesis, &srs).unwrap();
+        let mut ivc = Bn254GrumpkinIVC::setup(chain_params.clone(), Anchor::Genesis, &srs).unwrap();
+        ivc.extend(&[header.clone()]).unwrap();
+        let mut proof_bytes = Vec::new();
+        ivc.compress(&key.snark_key)
+            .unwrap()
+            .serialize_compressed(&mut proof_bytes)
+            .unwrap();
+        let vk_bytes = key.to_bytes().unwrap();
+
+        let state = VerifiedState(verify_bytes(&proof_bytes, &vk_bytes).unwrap());
+        assert_eq!(state.tip_hash(), header.hash());
+        assert_eq!(state.tip_height(), 1);
+        assert_eq!(state.chainwork(), ivc.state().chainwork.to_string());
+
+        assert!(verify_bytes(&proof_bytes[1..], &vk_bytes).is_err());
+        assert!(verify_bytes(&proof_bytes, &vk_bytes[..vk_bytes.len() / 2]).is_err());
+    }
+}